@@ -0,0 +1,93 @@
+// headless.rs
+// Runs the simulation without opening a window, for CI and batch scenarios.
+use std::env;
+use traffic_simulation::options::Options;
+use traffic_simulation::pedestrian::Leg;
+use traffic_simulation::simulation::Simulation;
+
+const DEFAULT_STEPS: u64 = 3600;
+
+fn main() -> Result<(), String> {
+    let options = Options::from_args(env::args().skip(1))?;
+    let steps = options.steps.unwrap_or(DEFAULT_STEPS);
+    let mut simulation = Simulation::from_options(&options)?;
+    // Logged so a run can be replayed exactly with --seed
+    println!("seed: {}", simulation.seed);
+    println!("control: {}", simulation.traffic_light_system.control.name());
+    println!("controller: {}", simulation.traffic_light_system.controller.name());
+    println!("car following: {}", simulation.car_following.name());
+    println!("grant policy: {}", simulation.reservations.policy.name());
+    println!("traffic mix: {} ({:.1}% heavy)", simulation.traffic_mix.describe(), simulation.traffic_mix.heavy_percentage());
+    println!("drivers: {}", simulation.driver_mix.describe());
+    if simulation.demand.is_empty() {
+        println!("demand: spawning whenever the cooldown allows");
+    }
+    for generator in &simulation.demand.generators {
+        println!("generator: {}", generator.describe());
+    }
+    for origin in Leg::ALL {
+        println!("od matrix from {}: {}", origin.name(), simulation.od_matrix.describe(origin));
+    }
+
+    for _ in 0..steps {
+        if simulation.demand.is_empty() {
+            simulation.spawn_from_mix();
+        }
+        simulation.spawn_random_pedestrian();
+        simulation.step();
+    }
+
+    println!("steps: {}", steps);
+    println!("simulated time: {:.2}s", simulation.clock.now().as_secs_f64());
+    println!("vehicles spawned: {}", simulation.stats.vehicles_spawned);
+    println!(
+        "heavy vehicles spawned: {} ({:.1}%)",
+        simulation.stats.heavy_vehicles_spawned,
+        simulation.stats.heavy_vehicles_spawned as f32 / simulation.stats.vehicles_spawned.max(1) as f32 * 100.0
+    );
+    println!("vehicles exited: {}", simulation.stats.vehicles_exited);
+    println!("vehicles remaining: {}", simulation.vehicles.len());
+    println!("vehicles waiting to enter: {}", simulation.entry_queue.len());
+    println!("average entry wait: {:.2}s", simulation.average_entry_wait().as_secs_f64());
+    for (route, totals) in simulation.stats.routes.used() {
+        println!(
+            "route {}: {} spawned, {} exited, {} missed, average travel time {:.2}s, average stopped time {:.2}s",
+            route.describe(),
+            totals.spawned,
+            totals.exited,
+            totals.missed,
+            totals.average_travel_time(&simulation.clock).as_secs_f64(),
+            totals.average_stopped_time(&simulation.clock).as_secs_f64()
+        );
+    }
+    println!("collisions: {}", simulation.collisions.len());
+    println!("reservations granted: {}", simulation.reservations.grants);
+    println!("deadlocks: {}", simulation.reservations.deadlocks.len());
+    println!("average stopped time: {:.2}s", simulation.average_stopped_time().as_secs_f64());
+    println!("pedestrians spawned: {}", simulation.stats.pedestrians_spawned);
+    println!("pedestrians crossed: {}", simulation.stats.pedestrians_crossed);
+    println!("average pedestrian wait: {:.2}s", simulation.average_pedestrian_wait().as_secs_f64());
+    for crosswalk in &simulation.traffic_light_system.crosswalks {
+        println!("crosswalk {}: {} walk intervals", crosswalk.leg.name(), crosswalk.walks_served);
+    }
+    for detector in &simulation.traffic_light_system.detectors {
+        println!(
+            "detector {} {} at {}m: {} actuations, {:.1}% occupancy",
+            detector.spec.direction.name(),
+            detector.spec.kind.name(),
+            detector.spec.distance,
+            detector.actuations,
+            detector.occupancy(simulation.clock.tick) * 100.0
+        );
+    }
+    for entry in &simulation.traffic_light_system.events {
+        println!("[{:.2}s] {}", simulation.clock.ticks_to_duration(entry.tick).as_secs_f64(), entry.event.describe());
+    }
+    for collision in &simulation.collisions {
+        println!("[{:.2}s] {}", simulation.clock.ticks_to_duration(collision.tick).as_secs_f64(), collision.describe());
+    }
+    for deadlock in &simulation.reservations.deadlocks {
+        println!("[{:.2}s] {}", simulation.clock.ticks_to_duration(deadlock.tick).as_secs_f64(), deadlock.describe());
+    }
+    Ok(())
+}
@@ -0,0 +1,202 @@
+// car_following.rs
+// Car-following models: each gives a vehicle's acceleration from its own speed
+// and the gap to, and speed of, the vehicle ahead. Gipps and Krauss are stated
+// as the speed to reach after one reaction time, the driver's own, and turned
+// into the acceleration that gets there; their safe speed is also a limit the
+// vehicle keeps to at once.
+use crate::vehicle::Vehicle;
+
+// Exponent of the free-road term of the IDM
+const IDM_DELTA: i32 = 4;
+// Share of the maximum acceleration a Krauss driver randomly gives up
+pub const KRAUSS_DAWDLE: f32 = 0.5;
+
+/// Model used to set the acceleration of every vehicle.
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum CarFollowingModel {
+    /// Intelligent Driver Model (Treiber, Hennecke and Helbing).
+    #[default]
+    Idm,
+    /// Gipps' safe-speed model, which keeps a gap the vehicle can brake in.
+    Gipps,
+    /// Krauss' safe-speed model, with random dawdling.
+    Krauss,
+}
+
+/// The vehicle ahead, or a stop treated as a vehicle standing the minimum gap
+/// beyond the point where the vehicle must halt.
+#[derive(Clone, Copy)]
+pub struct Leader {
+    // Bumper-to-bumper gap in metres
+    pub gap: f32,
+    pub speed: f32,
+}
+
+impl Leader {
+    // Distance `vehicle` can still close before it is down to its minimum gap,
+    // which for a stop is the distance to the stop itself
+    pub fn room(self, vehicle: &Vehicle) -> f32 {
+        self.gap - vehicle.minimum_gap
+    }
+}
+
+impl CarFollowingModel {
+    pub fn name(self) -> &'static str {
+        match self {
+            CarFollowingModel::Idm => "idm",
+            CarFollowingModel::Gipps => "gipps",
+            CarFollowingModel::Krauss => "krauss",
+        }
+    }
+
+    /// Acceleration of `vehicle` behind `leader`, or on an open road without one,
+    /// within the vehicle's limits. Dawdling is left to the caller.
+    pub fn acceleration(self, vehicle: &Vehicle, leader: Option<Leader>) -> f32 {
+        let acceleration = match self {
+            CarFollowingModel::Idm => idm(vehicle, leader),
+            CarFollowingModel::Gipps => gipps(vehicle, leader),
+            CarFollowingModel::Krauss => krauss(vehicle, leader),
+        };
+        acceleration.clamp(-vehicle.max_deceleration, vehicle.acceleration)
+    }
+
+    /// Fastest `vehicle` may go behind `leader` at once, rather than after a
+    /// reaction time, for the models built on a safe speed. The IDM has none.
+    pub fn safe_speed(self, vehicle: &Vehicle, leader: Option<Leader>) -> f32 {
+        match self {
+            CarFollowingModel::Idm => f32::MAX,
+            CarFollowingModel::Gipps => gipps_safe_speed(vehicle, leader).max(0.0),
+            CarFollowingModel::Krauss => krauss_safe_speed(vehicle, leader).max(0.0),
+        }
+    }
+}
+
+impl std::str::FromStr for CarFollowingModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "idm" => Ok(CarFollowingModel::Idm),
+            "gipps" => Ok(CarFollowingModel::Gipps),
+            "krauss" => Ok(CarFollowingModel::Krauss),
+            _ => Err("expected idm, gipps or krauss".to_string()),
+        }
+    }
+}
+
+fn idm(vehicle: &Vehicle, leader: Option<Leader>) -> f32 {
+    let speed = vehicle.current_speed;
+    let free_road = 1.0 - (speed / vehicle.max_speed).powi(IDM_DELTA);
+    let interaction = leader.map_or(0.0, |leader| {
+        let braking = (vehicle.acceleration * vehicle.comfortable_deceleration).sqrt();
+        let dynamic_gap = speed * vehicle.time_headway + speed * (speed - leader.speed) / (2.0 * braking);
+        let desired_gap = vehicle.minimum_gap + dynamic_gap.max(0.0);
+        (desired_gap / leader.gap.max(0.01)).powi(2)
+    });
+    vehicle.acceleration * (free_road - interaction)
+}
+
+fn gipps(vehicle: &Vehicle, leader: Option<Leader>) -> f32 {
+    let speed = vehicle.current_speed;
+    let reaction = vehicle.driver.reaction_time;
+    let relative = speed / vehicle.max_speed;
+    let free_speed = speed + 2.5 * vehicle.acceleration * reaction * (1.0 - relative) * (0.025 + relative).sqrt();
+    (free_speed.min(gipps_safe_speed(vehicle, leader)).max(0.0) - speed) / reaction
+}
+
+// The leader is assumed to brake as hard as this vehicle would
+fn gipps_safe_speed(vehicle: &Vehicle, leader: Option<Leader>) -> f32 {
+    let speed = vehicle.current_speed;
+    let reaction = vehicle.driver.reaction_time;
+    leader.map_or(f32::MAX, |leader| {
+        let b = vehicle.comfortable_deceleration;
+        let radicand = b * b * reaction * reaction +
+            b * (2.0 * leader.room(vehicle) - speed * reaction + leader.speed * leader.speed / b);
+        if radicand > 0.0 { -b * reaction + radicand.sqrt() } else { 0.0 }
+    })
+}
+
+fn krauss(vehicle: &Vehicle, leader: Option<Leader>) -> f32 {
+    let speed = vehicle.current_speed;
+    let reaction = vehicle.driver.reaction_time;
+    let desired = (speed + vehicle.acceleration * reaction)
+        .min(vehicle.max_speed)
+        .min(krauss_safe_speed(vehicle, leader))
+        .max(0.0);
+    (desired - speed) / reaction
+}
+
+fn krauss_safe_speed(vehicle: &Vehicle, leader: Option<Leader>) -> f32 {
+    let speed = vehicle.current_speed;
+    let reaction = vehicle.driver.reaction_time;
+    leader.map_or(f32::MAX, |leader| {
+        let braking_time = (speed + leader.speed) / (2.0 * vehicle.comfortable_deceleration);
+        leader.speed + (leader.room(vehicle) - leader.speed * reaction) / (braking_time + reaction)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vehicle::{test_vehicle, Direction, Turn};
+
+    const MODELS: [CarFollowingModel; 3] = [CarFollowingModel::Idm, CarFollowingModel::Gipps, CarFollowingModel::Krauss];
+
+    // An average car and driver: 2.5 m/s² acceleration, 3 m/s² comfortable and
+    // 7.5 m/s² hardest braking, a one-second reaction time
+    fn car(speed: f32) -> Vehicle {
+        let mut vehicle = test_vehicle(Direction::East, Turn::Straight, 50.0);
+        vehicle.current_speed = speed;
+        vehicle
+    }
+
+    // A leader standing `room` metres beyond the car's minimum gap
+    fn standing(vehicle: &Vehicle, room: f32) -> Leader {
+        Leader { gap: vehicle.minimum_gap + room, speed: 0.0 }
+    }
+
+    #[test]
+    fn open_road_acceleration_fades_toward_the_desired_speed() {
+        let from_rest = car(0.0);
+        assert_eq!(CarFollowingModel::Idm.acceleration(&from_rest, None), 2.5);
+        assert_eq!(CarFollowingModel::Krauss.acceleration(&from_rest, None), 2.5);
+        let gipps = CarFollowingModel::Gipps.acceleration(&from_rest, None);
+        assert!((gipps - 2.5 * 2.5 * 0.025f32.sqrt()).abs() < 1e-5);
+
+        let mut cruising = car(0.0);
+        cruising.current_speed = cruising.max_speed;
+        for model in MODELS {
+            assert!(model.acceleration(&cruising, None).abs() < 1e-5, "{} still accelerates", model.name());
+        }
+    }
+
+    #[test]
+    fn every_model_brakes_hardest_at_a_standing_leader() {
+        let vehicle = car(10.0);
+        for model in MODELS {
+            assert_eq!(model.acceleration(&vehicle, Some(standing(&vehicle, 0.0))), -7.5, "{}", model.name());
+        }
+    }
+
+    #[test]
+    fn safe_speed_limits_only_gipps_and_krauss() {
+        let vehicle = car(10.0);
+        let leader = Some(standing(&vehicle, 20.0));
+        assert_eq!(CarFollowingModel::Idm.safe_speed(&vehicle, leader), f32::MAX);
+        assert_eq!(CarFollowingModel::Krauss.safe_speed(&vehicle, None), f32::MAX);
+        assert!((CarFollowingModel::Krauss.safe_speed(&vehicle, leader) - 7.5).abs() < 1e-5);
+        assert!((CarFollowingModel::Gipps.safe_speed(&vehicle, leader) - (99.0f32.sqrt() - 3.0)).abs() < 1e-5);
+        for model in [CarFollowingModel::Gipps, CarFollowingModel::Krauss] {
+            assert_eq!(model.safe_speed(&vehicle, Some(standing(&vehicle, -1.0))), 0.0);
+        }
+    }
+
+    #[test]
+    fn parses_model_names() {
+        let name = |s: &str| s.parse::<CarFollowingModel>().map(CarFollowingModel::name);
+        for model in MODELS {
+            assert_eq!(name(model.name()), Ok(model.name()));
+        }
+        assert_eq!(name("wiedemann"), Err("expected idm, gipps or krauss".to_string()));
+    }
+}
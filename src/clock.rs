@@ -0,0 +1,51 @@
+// clock.rs
+use std::time::Duration;
+
+/// Default tick length: one tick per frame at 60 frames per second.
+pub const DEFAULT_TICK_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// Simulation-time clock. Time only advances when the simulation steps, so a run
+/// behaves the same regardless of frame rate or machine load.
+#[derive(Clone, Copy)]
+pub struct SimClock {
+    pub tick: u64,
+    pub tick_duration: Duration,
+}
+
+impl SimClock {
+    pub fn new(tick_duration: Duration) -> Self {
+        SimClock {
+            tick: 0,
+            tick_duration,
+        }
+    }
+
+    pub fn advance(&mut self) {
+        self.tick += 1;
+    }
+
+    /// Simulation time elapsed since tick 0.
+    pub fn now(&self) -> Duration {
+        self.ticks_to_duration(self.tick)
+    }
+
+    /// Simulation time elapsed since `tick`.
+    pub fn elapsed_since(&self, tick: u64) -> Duration {
+        self.ticks_to_duration(self.tick.saturating_sub(tick))
+    }
+
+    pub fn ticks_to_duration(&self, ticks: u64) -> Duration {
+        Duration::from_nanos((self.tick_duration.as_nanos() * ticks as u128) as u64)
+    }
+
+    /// Number of whole ticks covering `duration`, rounded up.
+    pub fn duration_to_ticks(&self, duration: Duration) -> u64 {
+        duration.as_nanos().div_ceil(self.tick_duration.as_nanos().max(1)) as u64
+    }
+}
+
+impl Default for SimClock {
+    fn default() -> Self {
+        Self::new(DEFAULT_TICK_DURATION)
+    }
+}
@@ -0,0 +1,236 @@
+// collision.rs
+// Contact between vehicle bodies. Bodies are oriented boxes tested against each
+// other with the separating axis theorem, and every move is swept across the
+// tick so a fast vehicle cannot pass through another between two ticks.
+use std::f32::consts::{PI, TAU};
+
+// Longest step, in metres, between the poses a sweep tests
+const SWEEP_STEP: f32 = 0.25;
+
+/// A vehicle body: a box `length` by `width` metres centred on `center`, facing
+/// `heading` radians from east toward south.
+#[derive(Clone, Copy)]
+pub struct Body {
+    pub center: (f32, f32),
+    pub heading: f32,
+    pub length: f32,
+    pub width: f32,
+}
+
+/// Contact between two vehicles, recorded on the tick it began.
+#[derive(Clone, Copy)]
+pub struct CollisionEvent {
+    pub tick: u64,
+    // The vehicle whose move made contact, and the one it ran into
+    pub vehicle: u64,
+    pub other: u64,
+    // Centre of the moving vehicle at contact, in metres
+    pub position: (f32, f32),
+    // Speed of the moving vehicle just before contact
+    pub speed: f32,
+}
+
+impl Body {
+    pub fn corners(&self) -> [(f32, f32); 4] {
+        let (along, across) = (self.length / 2.0, self.width / 2.0);
+        let (sin, cos) = self.heading.sin_cos();
+        [(along, across), (along, -across), (-along, -across), (-along, across)]
+            .map(|(a, c)| (self.center.0 + a * cos - c * sin, self.center.1 + a * sin + c * cos))
+    }
+
+    /// Whether the two bodies overlap. Boxes that only touch along an edge do not.
+    pub fn overlaps(&self, other: &Body) -> bool {
+        if distance(self.center, other.center) >= self.radius() + other.radius() {
+            return false;
+        }
+        let (ours, theirs) = (self.corners(), other.corners());
+        self.axes().into_iter().chain(other.axes()).all(|axis| {
+            let (our_min, our_max) = project(&ours, axis);
+            let (their_min, their_max) = project(&theirs, axis);
+            our_min < their_max && their_min < our_max
+        })
+    }
+
+    /// Distance along the unit `axis` from the front of the body to the nearest
+    /// part of `other` straight ahead of it, or `None` if no part of `other` is
+    /// across from it. Negative once the two overlap along the axis.
+    pub fn gap_ahead(&self, other: &Body, axis: (f32, f32)) -> Option<f32> {
+        let across = (-axis.1, axis.0);
+        let ours = self.corners();
+        let (_, front) = project(&ours, axis);
+        let (left, right) = project(&ours, across);
+        let theirs = other.corners();
+        // Their corners across from the body, and where their edges cross its sides
+        let mut within = Vec::new();
+        for (i, &from) in theirs.iter().enumerate() {
+            let to = theirs[(i + 1) % 4];
+            let (from_across, to_across) = (dot(from, across), dot(to, across));
+            if (left..=right).contains(&from_across) {
+                within.push(from);
+            }
+            for side in [left, right] {
+                if (from_across - side) * (to_across - side) < 0.0 {
+                    let t = (side - from_across) / (to_across - from_across);
+                    within.push((from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t));
+                }
+            }
+        }
+        within.into_iter().map(|point| dot(point, axis) - front).min_by(f32::total_cmp)
+    }
+
+    /// The same body with `margin` metres added on every side.
+    pub fn grown(&self, margin: f32) -> Body {
+        Body {
+            length: self.length + 2.0 * margin,
+            width: self.width + 2.0 * margin,
+            ..*self
+        }
+    }
+
+    // Half the diagonal: no point of the body is further from its centre
+    fn radius(&self) -> f32 {
+        (self.length * self.length + self.width * self.width).sqrt() / 2.0
+    }
+
+    // Unit normals of the box edges, along and across the body
+    fn axes(&self) -> [(f32, f32); 2] {
+        let (sin, cos) = self.heading.sin_cos();
+        [(cos, sin), (-sin, cos)]
+    }
+
+    // Pose a fraction `t` of the way to `to`, turning the short way round
+    fn toward(&self, to: &Body, t: f32) -> Body {
+        let turn = (to.heading - self.heading + PI).rem_euclid(TAU) - PI;
+        Body {
+            center: (
+                self.center.0 + (to.center.0 - self.center.0) * t,
+                self.center.1 + (to.center.1 - self.center.1) * t,
+            ),
+            heading: self.heading + turn * t,
+            ..*self
+        }
+    }
+}
+
+/// Fraction of the move from `from` to `to`, between 0 and 1, the body can make
+/// before it first overlaps `obstacle`, or `None` if the whole move is clear.
+pub fn sweep(from: &Body, to: &Body, obstacle: &Body) -> Option<f32> {
+    let moved = distance(from.center, to.center);
+    if distance(from.center, obstacle.center) >= from.radius() + obstacle.radius() + moved {
+        return None;
+    }
+    // Rotation swings the corners through an arc as well
+    let turned = from.toward(to, 1.0).heading - from.heading;
+    let swept = moved + turned.abs() * from.radius();
+    let steps = (swept / SWEEP_STEP).ceil().max(1.0) as usize;
+    (1..=steps)
+        .find(|&step| from.toward(to, step as f32 / steps as f32).overlaps(obstacle))
+        .map(|step| (step - 1) as f32 / steps as f32)
+}
+
+impl CollisionEvent {
+    pub fn describe(&self) -> String {
+        format!(
+            "collision: vehicle {} ran into vehicle {} at {:.1} m/s at ({:.1}, {:.1})",
+            self.vehicle, self.other, self.speed, self.position.0, self.position.1
+        )
+    }
+}
+
+// Smallest and largest extent of `corners` along `axis`
+fn project(corners: &[(f32, f32); 4], axis: (f32, f32)) -> (f32, f32) {
+    corners.iter()
+        .map(|&corner| dot(corner, axis))
+        .fold((f32::MAX, f32::MIN), |(min, max), p| (min.min(p), max.max(p)))
+}
+
+fn dot(point: (f32, f32), axis: (f32, f32)) -> f32 {
+    point.0 * axis.0 + point.1 * axis.1
+}
+
+/// Straight-line distance between two points, in metres.
+pub fn distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::{FRAC_PI_4, SQRT_2};
+
+    fn car(x: f32, y: f32, heading: f32) -> Body {
+        Body { center: (x, y), heading, length: 4.0, width: 2.0 }
+    }
+
+    fn square(x: f32, y: f32, heading: f32) -> Body {
+        Body { center: (x, y), heading, length: 2.0, width: 2.0 }
+    }
+
+    #[test]
+    fn boxes_overlap_only_when_they_share_area() {
+        let body = car(0.0, 0.0, 0.0);
+        assert!(body.overlaps(&car(3.0, 1.0, 0.0)));
+        // Touching end to end or side by side is not overlapping
+        assert!(!body.overlaps(&car(4.0, 0.0, 0.0)));
+        assert!(!body.overlaps(&car(0.0, 2.0, 0.0)));
+        assert!(!body.overlaps(&car(10.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn rotated_boxes_are_separated_by_their_own_edges() {
+        let body = car(0.0, 0.0, 0.0);
+        // Near enough for the bounding circles to meet, but a diamond edge parts them
+        let diamond = square(2.9, 1.7, FRAC_PI_4);
+        assert!(distance(body.center, diamond.center) < body.radius() + diamond.radius());
+        assert!(!body.overlaps(&diamond));
+        assert!(!diamond.overlaps(&body));
+        // Closer in, the corner of the car lies inside the diamond
+        assert!(body.overlaps(&square(2.6, 1.4, FRAC_PI_4)));
+        assert!(square(2.6, 1.4, FRAC_PI_4).overlaps(&body));
+    }
+
+    #[test]
+    fn grown_body_overlaps_a_neighbour_within_the_margin() {
+        let body = car(0.0, 0.0, 0.0);
+        let behind = car(-4.2, 0.0, 0.0);
+        assert!(!body.overlaps(&behind));
+        assert!(body.grown(0.3).overlaps(&behind));
+    }
+
+    #[test]
+    fn gap_ahead_counts_only_what_lies_across_from_the_body() {
+        let body = car(0.0, 0.0, 0.0);
+        assert_eq!(body.gap_ahead(&car(10.0, 0.0, 0.0), (1.0, 0.0)), Some(6.0));
+        assert_eq!(body.gap_ahead(&car(10.0, 3.0, 0.0), (1.0, 0.0)), None);
+        // Only the lower corner of the diamond reaches across, and its near edge
+        // meets the side of the body at x + y = 6.8 - √2
+        let gap = body.gap_ahead(&square(5.0, 1.8, FRAC_PI_4), (1.0, 0.0)).expect("the corner is across from the body");
+        assert!((gap - (3.8 - SQRT_2)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn sweep_catches_a_move_through_an_obstacle() {
+        // Both ends of the move are clear of the obstacle, but not the path between
+        let (from, to, obstacle) = (car(0.0, 0.0, 0.0), car(20.0, 0.0, 0.0), car(10.0, 0.0, 0.0));
+        assert!(!from.overlaps(&obstacle) && !to.overlaps(&obstacle));
+        let t = sweep(&from, &to, &obstacle).expect("the move passes through the obstacle");
+        // Cut short where the front meets the obstacle's rear, 6 m of the 20
+        assert!((t - 0.3).abs() < 1e-6);
+        assert!(!from.toward(&to, t).overlaps(&obstacle));
+    }
+
+    #[test]
+    fn sweep_clears_a_move_that_passes_by() {
+        let (from, to) = (car(0.0, 0.0, 0.0), car(20.0, 0.0, 0.0));
+        assert!(sweep(&from, &to, &car(10.0, 2.5, 0.0)).is_none());
+        assert!(sweep(&from, &to, &car(30.0, 0.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn sweep_follows_the_swing_of_a_turn() {
+        // Turning in place toward south swings the front into a box beside it
+        let (from, to) = (car(0.0, 0.0, 0.0), car(0.0, 0.0, FRAC_PI_4 * 2.0));
+        assert!(!to.overlaps(&square(2.0, 2.5, 0.0)));
+        assert!(sweep(&from, &to, &square(2.0, 2.5, 0.0)).is_some());
+    }
+}
@@ -0,0 +1,85 @@
+// control.rs
+// How right of way is assigned at the intersection. Under signal control the
+// lights decide who may enter; otherwise vehicles obey the signs on their
+// approach and the rules of the road decide who goes first.
+use crate::traffic_light::Axis;
+use crate::vehicle::Direction;
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum IntersectionControl {
+    #[default]
+    Signalized,
+    /// Every approach stops at the line; vehicles go in the order they stopped,
+    /// with a vehicle on the right going first when two stop together.
+    AllWayStop,
+    /// Only the minor road stops, and it gives way to all traffic on `major`.
+    TwoWayStop { major: Axis },
+    /// No one stops, and every vehicle gives way to traffic from its right.
+    YieldToRight,
+}
+
+/// Sign posted at the stop line of an approach.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Sign {
+    Stop,
+    Yield,
+}
+
+impl IntersectionControl {
+    pub fn name(self) -> &'static str {
+        match self {
+            IntersectionControl::Signalized => "signal",
+            IntersectionControl::AllWayStop => "all-way-stop",
+            IntersectionControl::TwoWayStop { major: Axis::EastWest } => "two-way-stop",
+            IntersectionControl::TwoWayStop { major: Axis::NorthSouth } => "two-way-stop-ns",
+            IntersectionControl::YieldToRight => "yield-to-right",
+        }
+    }
+
+    pub fn is_signalized(self) -> bool {
+        self == IntersectionControl::Signalized
+    }
+
+    /// Sign facing traffic travelling in `direction`, if there is one.
+    pub fn sign_for(self, direction: Direction) -> Option<Sign> {
+        match self {
+            IntersectionControl::Signalized => None,
+            IntersectionControl::AllWayStop => Some(Sign::Stop),
+            IntersectionControl::TwoWayStop { major } => (Axis::of(direction) != major).then_some(Sign::Stop),
+            IntersectionControl::YieldToRight => Some(Sign::Yield),
+        }
+    }
+
+    /// Whether traffic travelling in `direction` is on the major road, which the
+    /// rest of the traffic gives way to.
+    pub fn is_major(self, direction: Direction) -> bool {
+        matches!(self, IntersectionControl::TwoWayStop { major } if Axis::of(direction) == major)
+    }
+}
+
+impl std::str::FromStr for IntersectionControl {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "signal" => Ok(IntersectionControl::Signalized),
+            "all-way-stop" => Ok(IntersectionControl::AllWayStop),
+            // The east-west road is the major road unless told otherwise
+            "two-way-stop" => Ok(IntersectionControl::TwoWayStop { major: Axis::EastWest }),
+            "two-way-stop-ns" => Ok(IntersectionControl::TwoWayStop { major: Axis::NorthSouth }),
+            "yield-to-right" => Ok(IntersectionControl::YieldToRight),
+            _ => Err("expected signal, all-way-stop, two-way-stop, two-way-stop-ns or yield-to-right".to_string()),
+        }
+    }
+}
+
+/// Direction of travel of the traffic that reaches the box from the right of a
+/// driver travelling in `direction`.
+pub fn from_right(direction: Direction) -> Direction {
+    match direction {
+        Direction::North => Direction::West,
+        Direction::West => Direction::South,
+        Direction::South => Direction::East,
+        Direction::East => Direction::North,
+    }
+}
@@ -0,0 +1,455 @@
+// controller.rs
+use std::time::Duration;
+use crate::clock::SimClock;
+use crate::detector::{Detector, DetectorKind};
+use crate::pedestrian::Crosswalk;
+use crate::signal_plan::{Phase, SignalPlan};
+use crate::traffic_light::ROAD_WIDTH;
+use crate::vehicle::{Vehicle, Direction, Turn};
+
+// Distance upstream of the stop line within which a vehicle counts as approaching
+const DETECTION_RANGE: f32 = 12.0;
+
+/// What a controller can see when deciding which phase should be green.
+pub struct Observation<'a> {
+    pub plan: &'a SignalPlan,
+    pub current_phase: usize,
+    pub clock: &'a SimClock,
+    pub green_elapsed: Duration,
+    // Vehicles held at the light, per phase that would serve them
+    pub phase_demand: &'a [u32],
+    pub detectors: &'a [Detector],
+    pub crosswalks: &'a [Crosswalk],
+    pub vehicles: &'a [Vehicle],
+}
+
+impl Observation<'_> {
+    pub fn current(&self) -> &Phase {
+        &self.plan.phases[self.current_phase]
+    }
+
+    pub fn next_in_cycle(&self) -> usize {
+        (self.current_phase + 1) % self.plan.phases.len()
+    }
+
+    /// Vehicles upstream of the stop line, within `range`, making this movement.
+    pub fn approaching(&self, direction: Direction, turn: Turn, range: f32) -> u32 {
+        self.vehicles.iter()
+            .filter(|v| v.direction == direction && v.turn == turn && !v.turn_executed)
+            .filter(|v| (0.0..=range).contains(&v.distance_to_stop_line()))
+            .count() as u32
+    }
+
+    /// Vehicles that have crossed the intersection and are still within `range`
+    /// of it on the exit leg heading in `direction`.
+    pub fn departing(&self, direction: Direction, range: f32) -> u32 {
+        self.vehicles.iter()
+            .filter(|v| v.direction == direction && v.turn_executed)
+            .filter(|v| v.distance_from_center() <= range + ROAD_WIDTH / 2.0)
+            .count() as u32
+    }
+
+    /// Crosswalks with an unserved push-button call that `phase` could serve.
+    pub fn pedestrian_calls(&self, phase: usize) -> u32 {
+        self.crosswalks.iter()
+            .filter(|c| c.called && self.plan.phase_serves_crosswalk(phase, c.leg))
+            .count() as u32
+    }
+}
+
+/// Decides which phase should be green. The signal enforces minimum green and
+/// runs the amber and all-red clearance whenever the desired phase changes.
+pub trait SignalController {
+    fn name(&self) -> &'static str;
+
+    /// Phase that should be green now; returning the current phase holds it.
+    fn desired_phase(&mut self, observation: &Observation) -> usize;
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ControllerKind {
+    FixedTime,
+    Congestion,
+    Actuated,
+    MaxPressure,
+}
+
+impl ControllerKind {
+    pub fn build(self) -> Box<dyn SignalController> {
+        match self {
+            ControllerKind::FixedTime => Box::new(FixedTimeController),
+            ControllerKind::Congestion => Box::new(CongestionController),
+            ControllerKind::Actuated => Box::new(ActuatedController::default()),
+            ControllerKind::MaxPressure => Box::new(MaxPressureController),
+        }
+    }
+}
+
+impl std::str::FromStr for ControllerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fixed-time" => Ok(ControllerKind::FixedTime),
+            "congestion" => Ok(ControllerKind::Congestion),
+            "actuated" => Ok(ControllerKind::Actuated),
+            "max-pressure" => Ok(ControllerKind::MaxPressure),
+            _ => Err("expected fixed-time, congestion, actuated or max-pressure".to_string()),
+        }
+    }
+}
+
+/// Runs every phase for its planned green, in cycle order.
+pub struct FixedTimeController;
+
+impl SignalController for FixedTimeController {
+    fn name(&self) -> &'static str {
+        "fixed-time"
+    }
+
+    fn desired_phase(&mut self, observation: &Observation) -> usize {
+        if observation.green_elapsed >= observation.current().green {
+            observation.next_in_cycle()
+        } else {
+            observation.current_phase
+        }
+    }
+}
+
+/// Shortens green to its minimum when other phases have a long queue and
+/// stretches it to its maximum when the whole intersection is quiet.
+pub struct CongestionController;
+
+impl SignalController for CongestionController {
+    fn name(&self) -> &'static str {
+        "congestion"
+    }
+
+    fn desired_phase(&mut self, observation: &Observation) -> usize {
+        let phase = observation.current();
+        let thresholds = observation.plan.adaptive;
+        let current_demand = observation.phase_demand[observation.current_phase];
+        let waiting_elsewhere = observation.phase_demand.iter()
+            .enumerate()
+            .filter(|(i, _)| *i != observation.current_phase)
+            .map(|(_, demand)| *demand)
+            .max()
+            .unwrap_or(0);
+
+        let green = if waiting_elsewhere >= thresholds.congestion_threshold {
+            if current_demand >= thresholds.congestion_threshold {
+                phase.green
+            } else {
+                phase.min_green
+            }
+        } else if current_demand < thresholds.light_traffic_threshold &&
+                  waiting_elsewhere < thresholds.light_traffic_threshold {
+            phase.max_green
+        } else {
+            phase.green
+        };
+
+        if observation.green_elapsed >= green {
+            observation.next_in_cycle()
+        } else {
+            observation.current_phase
+        }
+    }
+}
+
+/// NEMA-style actuated control driven by the loop detectors. Stop-bar loops
+/// call their phases while occupied; advance loops place a call that stays
+/// locked until the phase is served. Green is extended by the phase's passage
+/// time after each detection on its approaches and ends on a gap (gap-out) or
+/// once a conflicting call has waited for maximum green (max-out). Without a
+/// conflicting call the green rests.
+#[derive(Default)]
+pub struct ActuatedController {
+    timed_phase: Option<usize>,
+    locked_calls: Vec<bool>,
+    conflicting_call_since: Option<Duration>,
+}
+
+impl ActuatedController {
+    fn has_call(&self, observation: &Observation, phase: usize) -> bool {
+        self.locked_calls[phase] || observation.pedestrian_calls(phase) > 0 ||
+            observation.detectors.iter().any(|d| {
+                d.spec.kind == DetectorKind::StopBar && d.occupied &&
+                observation.plan.phase_serves_approach(phase, d.spec.direction)
+            })
+    }
+
+    fn gapped_out(observation: &Observation) -> bool {
+        let passage = observation.current().passage;
+        observation.detectors.iter()
+            .filter(|d| observation.plan.phase_serves_approach(observation.current_phase, d.spec.direction))
+            .all(|d| !d.occupied && d.last_occupied
+                .is_none_or(|tick| observation.clock.elapsed_since(tick) >= passage))
+    }
+}
+
+impl SignalController for ActuatedController {
+    fn name(&self) -> &'static str {
+        "actuated"
+    }
+
+    fn desired_phase(&mut self, observation: &Observation) -> usize {
+        let current = observation.current_phase;
+        let count = observation.plan.phases.len();
+        self.locked_calls.resize(count, false);
+        if self.timed_phase != Some(current) {
+            self.timed_phase = Some(current);
+            self.locked_calls[current] = false;
+            self.conflicting_call_since = None;
+        }
+
+        for detector in observation.detectors {
+            if detector.spec.kind == DetectorKind::Advance && detector.occupied {
+                for phase in (0..count).filter(|&i| i != current) {
+                    if observation.plan.phase_serves_approach(phase, detector.spec.direction) {
+                        self.locked_calls[phase] = true;
+                    }
+                }
+            }
+        }
+
+        let next_called = (1..count)
+            .map(|offset| (current + offset) % count)
+            .find(|&i| self.has_call(observation, i));
+        // A call that has gone away stops timing max-out, so a later one starts afresh
+        let Some(next_called) = next_called else {
+            self.conflicting_call_since = None;
+            return current;
+        };
+
+        let now = observation.clock.now();
+        let call_since = *self.conflicting_call_since.get_or_insert(now);
+        let max_out = now - call_since >= observation.current().max_green;
+        if max_out || Self::gapped_out(observation) {
+            next_called
+        } else {
+            current
+        }
+    }
+}
+
+/// Serves the phase with the greatest pressure: vehicles queued on its
+/// movements minus vehicles already occupying the exit legs they feed, plus
+/// one for each waiting pedestrian call. The current phase keeps green on ties,
+/// and after its maximum green gives way to any other phase with pressure.
+pub struct MaxPressureController;
+
+impl MaxPressureController {
+    fn pressure(observation: &Observation, phase: usize) -> i64 {
+        observation.plan.phases[phase].movements.iter()
+            .map(|m| {
+                let upstream = observation.approaching(m.direction, m.turn, DETECTION_RANGE) as i64;
+                let downstream = observation.departing(m.direction.turned(m.turn), DETECTION_RANGE) as i64;
+                upstream - downstream
+            })
+            .sum::<i64>() + observation.pedestrian_calls(phase) as i64
+    }
+}
+
+impl SignalController for MaxPressureController {
+    fn name(&self) -> &'static str {
+        "max-pressure"
+    }
+
+    fn desired_phase(&mut self, observation: &Observation) -> usize {
+        let current = Self::pressure(observation, observation.current_phase);
+        let maxed_out = observation.green_elapsed >= observation.current().max_green;
+        let busiest_other = (0..observation.plan.phases.len())
+            .filter(|&i| i != observation.current_phase)
+            .map(|i| (i, Self::pressure(observation, i)))
+            .max_by_key(|&(_, pressure)| pressure);
+        match busiest_other {
+            Some((i, pressure)) if pressure > current || (maxed_out && pressure > 0) => i,
+            _ => observation.current_phase,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pedestrian::Leg;
+    use crate::signal_plan::LeftTurnPhasing;
+    use crate::vehicle::test_vehicle;
+
+    // East-west is phase 0 and north-south phase 1, with one-second ticks
+    struct Scene {
+        plan: SignalPlan,
+        clock: SimClock,
+        phase_demand: Vec<u32>,
+        detectors: Vec<Detector>,
+        crosswalks: Vec<Crosswalk>,
+        vehicles: Vec<Vehicle>,
+    }
+
+    impl Scene {
+        fn new() -> Self {
+            let plan = SignalPlan::two_phase(LeftTurnPhasing::Permissive);
+            Scene {
+                phase_demand: vec![0; plan.phases.len()],
+                detectors: plan.detectors.iter().copied().map(Detector::new).collect(),
+                crosswalks: Leg::ALL.iter().copied().map(Crosswalk::new).collect(),
+                vehicles: Vec::new(),
+                clock: SimClock::new(Duration::from_secs(1)),
+                plan,
+            }
+        }
+
+        fn observation(&self, current_phase: usize, green_secs: u64) -> Observation<'_> {
+            Observation {
+                plan: &self.plan,
+                current_phase,
+                clock: &self.clock,
+                green_elapsed: Duration::from_secs(green_secs),
+                phase_demand: &self.phase_demand,
+                detectors: &self.detectors,
+                crosswalks: &self.crosswalks,
+                vehicles: &self.vehicles,
+            }
+        }
+
+        fn desired(&self, controller: &mut dyn SignalController, current_phase: usize, green_secs: u64) -> usize {
+            controller.desired_phase(&self.observation(current_phase, green_secs))
+        }
+
+        fn detector(&mut self, direction: Direction, kind: DetectorKind) -> &mut Detector {
+            self.detectors.iter_mut()
+                .find(|d| d.spec.direction == direction && d.spec.kind == kind)
+                .expect("the default layout has every loop")
+        }
+    }
+
+    #[test]
+    fn fixed_time_runs_each_phase_for_its_planned_green() {
+        let scene = Scene::new();
+        assert_eq!(scene.desired(&mut FixedTimeController, 0, 4), 0);
+        assert_eq!(scene.desired(&mut FixedTimeController, 0, 5), 1);
+        assert_eq!(scene.desired(&mut FixedTimeController, 1, 5), 0);
+    }
+
+    #[test]
+    fn congestion_controller_trades_green_between_queues() {
+        let mut scene = Scene::new();
+        // Quiet everywhere: green runs to its maximum
+        assert_eq!(scene.desired(&mut CongestionController, 0, 9), 0);
+        assert_eq!(scene.desired(&mut CongestionController, 0, 10), 1);
+        // A long queue elsewhere cuts a lightly used green to its minimum
+        scene.phase_demand = vec![1, 4];
+        assert_eq!(scene.desired(&mut CongestionController, 0, 2), 0);
+        assert_eq!(scene.desired(&mut CongestionController, 0, 3), 1);
+        // Queues on both keep the planned green
+        scene.phase_demand = vec![4, 4];
+        assert_eq!(scene.desired(&mut CongestionController, 0, 4), 0);
+        assert_eq!(scene.desired(&mut CongestionController, 0, 5), 1);
+    }
+
+    #[test]
+    fn max_pressure_serves_the_busiest_phase() {
+        let mut scene = Scene::new();
+        assert_eq!(scene.desired(&mut MaxPressureController, 0, 1), 0);
+        scene.vehicles = vec![
+            test_vehicle(Direction::East, Turn::Straight, 2.0),
+            test_vehicle(Direction::North, Turn::Straight, 2.0),
+            test_vehicle(Direction::North, Turn::Right, 8.0),
+        ];
+        assert_eq!(scene.desired(&mut MaxPressureController, 0, 1), 1);
+
+        // A vehicle still on the westbound exit holds back the two north-south
+        // movements feeding it but only one east-west one, leaving a tie on which
+        // the current phase keeps green
+        let mut departed = test_vehicle(Direction::West, Turn::Straight, -25.0);
+        departed.turn_executed = true;
+        scene.vehicles.push(departed);
+        assert_eq!(scene.desired(&mut MaxPressureController, 0, 1), 0);
+        assert_eq!(scene.desired(&mut MaxPressureController, 1, 1), 1);
+    }
+
+    #[test]
+    fn max_pressure_gives_way_after_maximum_green() {
+        let mut scene = Scene::new();
+        scene.vehicles = vec![
+            test_vehicle(Direction::East, Turn::Straight, 2.0),
+            test_vehicle(Direction::West, Turn::Straight, 2.0),
+            test_vehicle(Direction::North, Turn::Straight, 2.0),
+        ];
+        assert_eq!(scene.desired(&mut MaxPressureController, 0, 9), 0);
+        assert_eq!(scene.desired(&mut MaxPressureController, 0, 10), 1);
+    }
+
+    #[test]
+    fn actuated_green_rests_without_a_conflicting_call() {
+        let mut scene = Scene::new();
+        let mut controller = ActuatedController::default();
+        assert_eq!(scene.desired(&mut controller, 0, 30), 0);
+        scene.detector(Direction::East, DetectorKind::StopBar).occupied = true;
+        assert_eq!(scene.desired(&mut controller, 0, 30), 0);
+    }
+
+    #[test]
+    fn actuated_green_gaps_out_once_its_approaches_fall_quiet() {
+        let mut scene = Scene::new();
+        let mut controller = ActuatedController::default();
+        scene.clock.tick = 20;
+        scene.detector(Direction::North, DetectorKind::StopBar).occupied = true;
+        scene.detector(Direction::East, DetectorKind::Advance).last_occupied = Some(19);
+        // Within the two-second passage time of the last detection
+        assert_eq!(scene.desired(&mut controller, 0, 5), 0);
+        scene.clock.tick = 21;
+        assert_eq!(scene.desired(&mut controller, 0, 6), 1);
+    }
+
+    #[test]
+    fn actuated_green_maxes_out_under_a_waiting_call() {
+        let mut scene = Scene::new();
+        let mut controller = ActuatedController::default();
+        scene.detector(Direction::East, DetectorKind::StopBar).occupied = true;
+        scene.detector(Direction::North, DetectorKind::StopBar).occupied = true;
+        scene.clock.tick = 5;
+        assert_eq!(scene.desired(&mut controller, 0, 5), 0);
+        // Maximum green counts from the call, not from the start of green
+        scene.clock.tick = 14;
+        assert_eq!(scene.desired(&mut controller, 0, 14), 0);
+        scene.clock.tick = 15;
+        assert_eq!(scene.desired(&mut controller, 0, 15), 1);
+    }
+
+    #[test]
+    fn actuated_advance_call_stays_locked_until_served() {
+        let mut scene = Scene::new();
+        let mut controller = ActuatedController::default();
+        scene.detector(Direction::South, DetectorKind::Advance).occupied = true;
+        scene.detector(Direction::East, DetectorKind::StopBar).occupied = true;
+        assert_eq!(scene.desired(&mut controller, 0, 5), 0);
+        scene.detector(Direction::South, DetectorKind::Advance).occupied = false;
+        scene.detector(Direction::East, DetectorKind::StopBar).occupied = false;
+        assert_eq!(scene.desired(&mut controller, 0, 6), 1);
+        // Serving the phase clears its call
+        assert_eq!(scene.desired(&mut controller, 1, 0), 1);
+        assert_eq!(scene.desired(&mut controller, 0, 0), 0);
+    }
+
+    #[test]
+    fn pedestrian_calls_count_for_the_phases_that_serve_their_crosswalk() {
+        let mut scene = Scene::new();
+        scene.crosswalks.iter_mut().filter(|c| c.leg == Leg::East).for_each(|c| c.called = true);
+        // Westbound traffic enters across the east crosswalk, so only north-south serves it
+        let observation = scene.observation(0, 0);
+        assert_eq!([observation.pedestrian_calls(0), observation.pedestrian_calls(1)], [0, 1]);
+        assert_eq!(scene.desired(&mut ActuatedController::default(), 0, 5), 1);
+        assert_eq!(scene.desired(&mut MaxPressureController, 0, 1), 1);
+    }
+
+    #[test]
+    fn parses_controller_kinds() {
+        let name = |s: &str| s.parse::<ControllerKind>().map(|kind| kind.build().name());
+        for kind in ["fixed-time", "congestion", "actuated", "max-pressure"] {
+            assert_eq!(name(kind), Ok(kind));
+        }
+        assert_eq!(name("adaptive"), Err("expected fixed-time, congestion, actuated or max-pressure".to_string()));
+    }
+}
@@ -0,0 +1,121 @@
+// deadlock.rs
+// Wait-for graph between vehicles standing at or in the intersection. An edge
+// runs from each waiting vehicle to every vehicle it waits on, and a cycle means
+// none of them can ever move on its own.
+
+/// Who waits on whom, rebuilt every step.
+#[derive(Default)]
+pub struct WaitForGraph {
+    // (waiting vehicle, vehicle it waits on), sorted and without repeats
+    edges: Vec<(u64, u64)>,
+}
+
+/// A cycle of vehicles waiting on each other, recorded on the tick it was found.
+#[derive(Clone)]
+pub struct DeadlockEvent {
+    pub tick: u64,
+    // Each vehicle waits on the next, and the last on the first
+    pub vehicles: Vec<u64>,
+    // Vehicle let through to break the cycle, if one could be
+    pub released: Option<u64>,
+}
+
+impl WaitForGraph {
+    pub fn add(&mut self, waiter: u64, holder: u64) {
+        if let Err(i) = self.edges.binary_search(&(waiter, holder)) {
+            self.edges.insert(i, (waiter, holder));
+        }
+    }
+
+    /// A cycle in the graph, starting from its lowest id, or `None` if there is
+    /// none. The same graph always gives the same cycle, whatever order its
+    /// edges were added in.
+    pub fn find_cycle(&self) -> Option<Vec<u64>> {
+        let mut finished = Vec::new();
+        for &(waiter, _) in &self.edges {
+            if let Some(mut cycle) = self.visit(waiter, &mut Vec::new(), &mut finished) {
+                let lowest = (0..cycle.len()).min_by_key(|&i| cycle[i]).unwrap_or(0);
+                cycle.rotate_left(lowest);
+                return Some(cycle);
+            }
+        }
+        None
+    }
+
+    // Depth-first search from `vehicle`, with `path` the vehicles walked to reach
+    // it and `finished` those already known not to lead into a cycle
+    fn visit(&self, vehicle: u64, path: &mut Vec<u64>, finished: &mut Vec<u64>) -> Option<Vec<u64>> {
+        if let Some(start) = path.iter().position(|&other| other == vehicle) {
+            return Some(path[start..].to_vec());
+        }
+        if finished.contains(&vehicle) {
+            return None;
+        }
+
+        path.push(vehicle);
+        for &(_, holder) in self.edges.iter().filter(|&&(waiter, _)| waiter == vehicle) {
+            if let Some(cycle) = self.visit(holder, path, finished) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        finished.push(vehicle);
+        None
+    }
+}
+
+impl DeadlockEvent {
+    pub fn describe(&self) -> String {
+        let cycle = self.vehicles.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(" -> ");
+        match self.released {
+            Some(vehicle) => format!("deadlock: vehicles {} waiting in a cycle, vehicle {} let through", cycle, vehicle),
+            None => format!("deadlock: vehicles {} waiting in a cycle, unresolved", cycle),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(edges: &[(u64, u64)]) -> WaitForGraph {
+        let mut graph = WaitForGraph::default();
+        for &(waiter, holder) in edges {
+            graph.add(waiter, holder);
+        }
+        graph
+    }
+
+    #[test]
+    fn chains_of_waiting_vehicles_have_no_cycle() {
+        assert!(WaitForGraph::default().find_cycle().is_none());
+        assert!(graph(&[(1, 2), (2, 3), (1, 3), (4, 3)]).find_cycle().is_none());
+    }
+
+    #[test]
+    fn cycle_starts_from_its_lowest_id() {
+        assert_eq!(graph(&[(7, 5), (5, 9), (9, 7)]).find_cycle(), Some(vec![5, 9, 7]));
+        assert_eq!(graph(&[(3, 4), (4, 3)]).find_cycle(), Some(vec![3, 4]));
+    }
+
+    #[test]
+    fn cycle_is_found_behind_vehicles_that_only_wait() {
+        // 1 and 2 wait on the cycle without being part of it
+        let cycle = graph(&[(1, 2), (2, 10), (10, 11), (11, 12), (12, 10)]).find_cycle();
+        assert_eq!(cycle, Some(vec![10, 11, 12]));
+    }
+
+    #[test]
+    fn same_graph_gives_the_same_cycle_whatever_the_order_of_its_edges() {
+        let edges = [(1, 2), (2, 3), (3, 1), (4, 5), (5, 4), (2, 4), (6, 1)];
+        let expected = graph(&edges).find_cycle();
+        assert!(expected.is_some());
+        let mut reversed = edges;
+        reversed.reverse();
+        assert_eq!(graph(&reversed).find_cycle(), expected);
+        // Repeated edges are kept once
+        let repeated: Vec<_> = edges.iter().chain(&edges).copied().collect();
+        assert_eq!(graph(&repeated).find_cycle(), expected);
+        assert_eq!(graph(&repeated).edges.len(), edges.len());
+    }
+}
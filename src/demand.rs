@@ -0,0 +1,391 @@
+// demand.rs
+// Automatic traffic demand. Each generator sends vehicles down one approach at a
+// flow in vehicles per hour, scaled over the run by a demand profile. Arrivals
+// are counted against the vehicles expected so far, the flow integrated over
+// time, so a varying flow needs no special handling: a Poisson generator waits
+// an exponentially distributed share of one vehicle between arrivals, a uniform
+// one exactly one vehicle, and a platooned one a whole platoon's worth before
+// the platoon arrives in quick succession.
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use rand::Rng;
+use serde::Deserialize;
+use crate::signal_plan::{describe_toml_error, file_seconds};
+use crate::vehicle::Direction;
+
+// Vehicles in a platoon unless the file says otherwise
+const DEFAULT_PLATOON_SIZE: u32 = 4;
+// Seconds between the vehicles of a platoon unless the file says otherwise
+const DEFAULT_PLATOON_GAP: f64 = 1.5;
+// Highest flow of one generator, in vehicles per hour, far beyond what a lane carries
+const MAX_FLOW: f64 = 36_000.0;
+
+/// How the time between arrivals is spread.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Headway {
+    Poisson,
+    Uniform,
+    // Platoons arrive as a Poisson process, their vehicles `gap` apart
+    Platooned { size: u32, gap: Duration },
+}
+
+impl Headway {
+    pub fn describe(self) -> String {
+        match self {
+            Headway::Poisson => "poisson".to_string(),
+            Headway::Uniform => "uniform".to_string(),
+            Headway::Platooned { size, gap } => format!("platoons of {} {:.1}s apart", size, gap.as_secs_f64()),
+        }
+    }
+}
+
+/// Share of a generator's flow released over the run, linear between points and
+/// flat beyond the first and last.
+#[derive(Clone)]
+pub struct DemandProfile {
+    pub name: String,
+    // Seconds into the run and the share of the flow then, in order of time
+    points: Vec<(f64, f64)>,
+}
+
+impl DemandProfile {
+    /// The full flow throughout.
+    pub fn flat() -> Self {
+        DemandProfile { name: "flat".to_string(), points: vec![(0.0, 1.0)] }
+    }
+
+    pub fn factor(&self, at: Duration) -> f64 {
+        let t = at.as_secs_f64();
+        let after = self.points.iter().position(|&(time, _)| time > t);
+        match after {
+            Some(0) => self.points[0].1,
+            Some(i) => {
+                let ((t0, f0), (t1, f1)) = (self.points[i - 1], self.points[i]);
+                f0 + (f1 - f0) * (t - t0) / (t1 - t0)
+            },
+            None => self.points[self.points.len() - 1].1,
+        }
+    }
+}
+
+/// Vehicles sent down one approach.
+#[derive(Clone)]
+pub struct Generator {
+    pub direction: Direction,
+    // Vehicles per hour at a profile share of one
+    pub flow: f64,
+    pub headway: Headway,
+    pub profile: DemandProfile,
+    // Vehicles expected so far, the flow integrated over the run
+    expected: f64,
+    // Value of `expected` at which the next vehicle, or platoon, arrives
+    next_arrival: Option<f64>,
+    // Vehicles of the current platoon still to come, and when the next is due
+    platoon: Option<(u32, Duration)>,
+}
+
+impl Generator {
+    pub fn new(direction: Direction, flow: f64, headway: Headway, profile: DemandProfile) -> Self {
+        Generator { direction, flow, headway, profile, expected: 0.0, next_arrival: None, platoon: None }
+    }
+
+    /// Number of vehicles arriving during the `dt` seconds from `now`.
+    pub fn arrivals(&mut self, now: Duration, dt: f64, rng: &mut impl Rng) -> u32 {
+        let mut arrivals = 0;
+        if let Some((left, due)) = self.platoon && now >= due {
+            arrivals += 1;
+            self.platoon = match self.headway {
+                Headway::Platooned { gap, .. } if left > 1 => Some((left - 1, due + gap)),
+                _ => None,
+            };
+        }
+
+        // Uniform arrivals start at a random phase, so approaches are not in step
+        let mut next = match self.next_arrival {
+            Some(next) => next,
+            None if self.headway == Headway::Uniform => rng.gen_range(0.0..1.0),
+            None => self.spacing(rng),
+        };
+        self.expected += self.flow / 3600.0 * self.profile.factor(now) * dt;
+        while self.expected >= next {
+            arrivals += 1;
+            // A platoon arriving before the last has all come joins the end of it
+            if let Headway::Platooned { size, gap } = self.headway && size > 1 {
+                self.platoon = Some(match self.platoon {
+                    Some((left, due)) => (left + size - 1, due),
+                    None => (size - 1, now + gap),
+                });
+            }
+            next += self.spacing(rng);
+        }
+        self.next_arrival = Some(next);
+        arrivals
+    }
+
+    // Vehicles expected between one arrival and the next
+    fn spacing(&self, rng: &mut impl Rng) -> f64 {
+        match self.headway {
+            Headway::Poisson => exponential(rng),
+            Headway::Uniform => 1.0,
+            Headway::Platooned { size, .. } => size as f64 * exponential(rng),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} {} veh/h, {}, {} profile",
+            self.direction.name(), self.flow, self.headway.describe(), self.profile.name
+        )
+    }
+}
+
+// Exponentially distributed, with a mean of one
+fn exponential(rng: &mut impl Rng) -> f64 {
+    -rng.gen_range(f64::EPSILON..1.0).ln()
+}
+
+/// Every generator of a run. Without any, vehicles are only spawned by hand or
+/// by a scenario.
+#[derive(Clone, Default)]
+pub struct Demand {
+    pub generators: Vec<Generator>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DemandFile {
+    #[serde(default)]
+    profiles: Vec<ProfileFile>,
+    generators: Vec<GeneratorFile>,
+}
+
+// `points` are pairs of seconds into the run and share of the flow
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfileFile {
+    name: String,
+    points: Vec<(f64, f64)>,
+}
+
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum HeadwayFile {
+    Poisson,
+    Uniform,
+    Platooned,
+}
+
+// `flow` is in vehicles per hour and `platoon_gap` in seconds
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GeneratorFile {
+    direction: Direction,
+    flow: f64,
+    headway: HeadwayFile,
+    platoon_size: Option<u32>,
+    platoon_gap: Option<f64>,
+    profile: Option<String>,
+}
+
+impl Demand {
+    pub fn is_empty(&self) -> bool {
+        self.generators.is_empty()
+    }
+
+    /// Directions of the vehicles arriving during the `dt` seconds from `now`.
+    pub fn arrivals(&mut self, now: Duration, dt: f64, rng: &mut impl Rng) -> Vec<Direction> {
+        let mut directions = Vec::new();
+        for generator in &mut self.generators {
+            let count = generator.arrivals(now, dt, rng);
+            directions.extend((0..count).map(|_| generator.direction));
+        }
+        directions
+    }
+
+    /// Loads generators from a TOML file. Errors name the file and the offending field.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("demand {}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("demand {}: {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let file: DemandFile = toml::from_str(text).map_err(|e| describe_toml_error(text, &e))?;
+
+        let mut profiles = Vec::new();
+        for (i, profile) in file.profiles.into_iter().enumerate() {
+            if let Some(j) = profiles.iter().position(|other: &DemandProfile| other.name == profile.name) {
+                return Err(format!("profiles[{}].name: duplicates profiles[{}]", i, j));
+            }
+            Self::validate_points(&profile.points).map_err(|e| format!("profiles[{}].{}", i, e))?;
+            profiles.push(DemandProfile { name: profile.name, points: profile.points });
+        }
+
+        let generators = file.generators.into_iter()
+            .enumerate()
+            .map(|(i, generator)| Self::validate_generator(generator, &profiles).map_err(|e| format!("generators[{}].{}", i, e)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Demand { generators })
+    }
+
+    fn validate_points(points: &[(f64, f64)]) -> Result<(), String> {
+        if points.is_empty() {
+            return Err("points: at least one point is required".to_string());
+        }
+        for (i, &(time, factor)) in points.iter().enumerate() {
+            file_seconds(time, false).map_err(|e| format!("points[{}]: time {}", i, e))?;
+            if i > 0 && time <= points[i - 1].0 {
+                return Err(format!("points[{}]: times must increase", i));
+            }
+            if factor.is_nan() {
+                return Err(format!("points[{}]: share must be a number, not NaN", i));
+            }
+            if !(0.0..=f64::MAX).contains(&factor) {
+                return Err(format!("points[{}]: share must be finite and not negative", i));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_generator(generator: GeneratorFile, profiles: &[DemandProfile]) -> Result<Generator, String> {
+        if !(generator.flow > 0.0 && generator.flow <= MAX_FLOW) {
+            return Err(format!("flow: must be more than 0 and at most {} vehicles per hour", MAX_FLOW));
+        }
+        let platooned = generator.headway == HeadwayFile::Platooned;
+        if !platooned && (generator.platoon_size.is_some() || generator.platoon_gap.is_some()) {
+            return Err("headway: platoon_size and platoon_gap only apply to platooned headways".to_string());
+        }
+
+        let headway = match generator.headway {
+            HeadwayFile::Poisson => Headway::Poisson,
+            HeadwayFile::Uniform => Headway::Uniform,
+            HeadwayFile::Platooned => {
+                let size = generator.platoon_size.unwrap_or(DEFAULT_PLATOON_SIZE);
+                if size == 0 {
+                    return Err("platoon_size: must be at least 1".to_string());
+                }
+                let gap = file_seconds(generator.platoon_gap.unwrap_or(DEFAULT_PLATOON_GAP), true)
+                    .map_err(|e| format!("platoon_gap: {}", e))?;
+                Headway::Platooned { size, gap }
+            },
+        };
+
+        let profile = match generator.profile {
+            Some(name) => profiles.iter()
+                .find(|profile| profile.name == name)
+                .cloned()
+                .ok_or_else(|| format!("profile: no profile named '{}'", name))?,
+            None => DemandProfile::flat(),
+        };
+        Ok(Generator::new(generator.direction, generator.flow, headway, profile))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    const DT: f64 = 1.0 / 60.0;
+
+    // Arrivals on each tick of `seconds` of a run
+    fn run(generator: &mut Generator, seconds: u32, seed: u64) -> Vec<u32> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..seconds * 60)
+            .map(|tick| generator.arrivals(Duration::from_secs_f64(tick as f64 * DT), DT, &mut rng))
+            .collect()
+    }
+
+    fn parse_error(text: &str) -> String {
+        Demand::parse(text).err().unwrap_or_default()
+    }
+
+    #[test]
+    fn uniform_arrivals_match_the_flow_evenly_spaced() {
+        let mut generator = Generator::new(Direction::East, 720.0, Headway::Uniform, DemandProfile::flat());
+        let arrivals = run(&mut generator, 600, 1);
+        // 720 veh/h for ten minutes, one every five seconds
+        assert!(arrivals.iter().sum::<u32>().abs_diff(120) <= 1);
+        let ticks: Vec<usize> = (0..arrivals.len()).filter(|&tick| arrivals[tick] > 0).collect();
+        assert!(ticks.windows(2).all(|pair| (pair[1] - pair[0]).abs_diff(300) <= 1));
+    }
+
+    #[test]
+    fn poisson_arrivals_average_the_flow() {
+        let total: u32 = (1..=20)
+            .map(|seed| {
+                let mut generator = Generator::new(Direction::North, 600.0, Headway::Poisson, DemandProfile::flat());
+                run(&mut generator, 600, seed).iter().sum::<u32>()
+            })
+            .sum();
+        // 100 expected from each run
+        assert!((1_850..2_150).contains(&total));
+    }
+
+    #[test]
+    fn platoons_arrive_in_quick_succession() {
+        let headway = Headway::Platooned { size: 5, gap: Duration::from_secs(2) };
+        let mut generator = Generator::new(Direction::West, 600.0, headway, DemandProfile::flat());
+        let arrivals = run(&mut generator, 1800, 1);
+        let total: u32 = arrivals.iter().sum();
+        assert!((240..360).contains(&total));
+        // Most vehicles follow the one before by the platoon gap
+        let ticks: Vec<usize> = (0..arrivals.len()).filter(|&tick| arrivals[tick] > 0).collect();
+        let in_step = ticks.windows(2).filter(|pair| pair[1] - pair[0] == 120).count();
+        assert!(in_step * 2 > ticks.len());
+    }
+
+    #[test]
+    fn profile_scales_the_flow() {
+        let profile = DemandProfile { name: "ramp".to_string(), points: vec![(60.0, 0.0), (120.0, 1.0)] };
+        assert_eq!(profile.factor(Duration::ZERO), 0.0);
+        assert_eq!(profile.factor(Duration::from_secs(90)), 0.5);
+        assert_eq!(profile.factor(Duration::from_secs(600)), 1.0);
+
+        let mut generator = Generator::new(Direction::South, 3600.0, Headway::Uniform, profile);
+        let arrivals = run(&mut generator, 180, 1);
+        // Nothing for the first minute, then half the second minute's flow and all the third's
+        assert_eq!(arrivals[..3600].iter().sum::<u32>(), 0);
+        assert!(arrivals[3600..7200].iter().sum::<u32>().abs_diff(30) <= 1);
+        assert!(arrivals.iter().sum::<u32>().abs_diff(90) <= 1);
+    }
+
+    #[test]
+    fn shipped_demand_parses() {
+        let demand = Demand::parse(include_str!("../demand/am_peak.toml")).expect("the shipped demand is valid");
+        assert_eq!(demand.generators.len(), 4);
+        assert_eq!(demand.generators[0].headway, Headway::Platooned { size: 5, gap: Duration::from_secs_f64(1.8) });
+        assert_eq!(demand.generators[3].profile.name, "flat");
+    }
+
+    #[test]
+    fn rejects_bad_generators_and_profiles() {
+        let generator = |fields: &str| format!("[[generators]]\ndirection = \"east\"\n{}\n", fields);
+        assert_eq!(
+            parse_error(&generator("flow = 0\nheadway = \"poisson\"")),
+            "generators[0].flow: must be more than 0 and at most 36000 vehicles per hour",
+        );
+        assert_eq!(
+            parse_error(&generator("flow = 100\nheadway = \"uniform\"\nplatoon_size = 3")),
+            "generators[0].headway: platoon_size and platoon_gap only apply to platooned headways",
+        );
+        assert_eq!(
+            parse_error(&generator("flow = 100\nheadway = \"platooned\"\nplatoon_size = 0")),
+            "generators[0].platoon_size: must be at least 1",
+        );
+        assert_eq!(
+            parse_error(&generator("flow = 100\nheadway = \"poisson\"\nprofile = \"pm\"")),
+            "generators[0].profile: no profile named 'pm'",
+        );
+
+        let profile = |points: &str| {
+            format!("[[profiles]]\nname = \"p\"\npoints = {}\n{}", points, generator("flow = 100\nheadway = \"poisson\""))
+        };
+        assert_eq!(parse_error(&profile("[]")), "profiles[0].points: at least one point is required");
+        assert_eq!(parse_error(&profile("[[0, 1], [0, 2]]")), "profiles[0].points[1]: times must increase");
+        assert_eq!(parse_error(&profile("[[0, -1]]")), "profiles[0].points[0]: share must be finite and not negative");
+    }
+}
@@ -0,0 +1,164 @@
+// detector.rs
+use serde::Deserialize;
+use crate::vehicle::{Vehicle, Direction};
+
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DetectorKind {
+    /// Presence loop at the stop line; holds a call while a vehicle waits on it.
+    StopBar,
+    /// Short loop upstream that registers vehicles on their way in.
+    Advance,
+}
+
+impl DetectorKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            DetectorKind::StopBar => "stop-bar",
+            DetectorKind::Advance => "advance",
+        }
+    }
+}
+
+/// Where a loop sits: on the approach travelling in `direction`, starting
+/// `distance` metres upstream of the stop line and extending `length` metres
+/// further upstream.
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DetectorSpec {
+    pub direction: Direction,
+    pub kind: DetectorKind,
+    pub distance: f32,
+    pub length: f32,
+}
+
+impl DetectorSpec {
+    /// One stop-bar and one advance loop on every approach.
+    pub fn default_layout() -> Vec<DetectorSpec> {
+        Direction::ALL.iter()
+            .flat_map(|&direction| [
+                DetectorSpec { direction, kind: DetectorKind::StopBar, distance: 0.0, length: 3.0 },
+                DetectorSpec { direction, kind: DetectorKind::Advance, distance: 12.0, length: 1.0 },
+            ])
+            .collect()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for (field, value) in [("distance", self.distance), ("length", self.length)] {
+            if !value.is_finite() {
+                return Err(format!("{}: must be a finite number of metres", field));
+            }
+        }
+        if self.distance < 0.0 {
+            return Err("distance: must not be negative".to_string());
+        }
+        if self.length <= 0.0 {
+            return Err("length: must be greater than zero".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A virtual loop detector and what it has reported so far.
+#[derive(Clone)]
+pub struct Detector {
+    pub spec: DetectorSpec,
+    pub occupied: bool,
+    // Number of times the loop went from empty to occupied
+    pub actuations: u64,
+    pub occupied_ticks: u64,
+    pub last_occupied: Option<u64>,
+}
+
+impl Detector {
+    pub fn new(spec: DetectorSpec) -> Self {
+        Detector {
+            spec,
+            occupied: false,
+            actuations: 0,
+            occupied_ticks: 0,
+            last_occupied: None,
+        }
+    }
+
+    pub fn update(&mut self, vehicles: &[Vehicle], tick: u64) {
+        let occupied = vehicles.iter().any(|vehicle| self.covers(vehicle));
+        if occupied && !self.occupied {
+            self.actuations += 1;
+        }
+        if occupied {
+            self.occupied_ticks += 1;
+            self.last_occupied = Some(tick);
+        }
+        self.occupied = occupied;
+    }
+
+    // A vehicle is over the loop when any part of it, measured along its
+    // approach, overlaps the loop. Vehicles that have crossed the box are ignored.
+    fn covers(&self, vehicle: &Vehicle) -> bool {
+        if vehicle.direction != self.spec.direction || vehicle.turn_executed {
+            return false;
+        }
+        let front = vehicle.distance_to_stop_line();
+        let rear = front + vehicle.length();
+        front <= self.spec.distance + self.spec.length && rear >= self.spec.distance
+    }
+
+    /// Fraction of `ticks` the loop has been occupied.
+    pub fn occupancy(&self, ticks: u64) -> f64 {
+        self.occupied_ticks as f64 / ticks.max(1) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vehicle::{test_vehicle, Turn};
+
+    fn stop_bar(direction: Direction) -> Detector {
+        Detector::new(DetectorSpec { direction, kind: DetectorKind::StopBar, distance: 0.0, length: 3.0 })
+    }
+
+    #[test]
+    fn counts_actuations_and_occupied_time() {
+        let mut detector = stop_bar(Direction::East);
+        let over = [test_vehicle(Direction::East, Turn::Straight, 1.0)];
+        let upstream = [test_vehicle(Direction::East, Turn::Straight, 10.0)];
+        for (tick, vehicles) in [&over, &over, &upstream, &over].into_iter().enumerate() {
+            detector.update(vehicles, tick as u64);
+        }
+        assert_eq!(detector.actuations, 2);
+        assert_eq!(detector.occupied_ticks, 3);
+        assert_eq!(detector.last_occupied, Some(3));
+        assert_eq!(detector.occupancy(4), 0.75);
+    }
+
+    #[test]
+    fn covers_any_part_of_a_vehicle_on_its_own_approach() {
+        let mut detector = stop_bar(Direction::East);
+        // The rear of a car whose front has just crossed the stop line
+        detector.update(&[test_vehicle(Direction::East, Turn::Straight, -1.0)], 0);
+        assert!(detector.occupied);
+
+        let mut crossed = test_vehicle(Direction::East, Turn::Straight, 1.0);
+        crossed.turn_executed = true;
+        detector.update(&[crossed, test_vehicle(Direction::West, Turn::Straight, 1.0)], 1);
+        assert!(!detector.occupied);
+        assert_eq!(detector.actuations, 1);
+    }
+
+    #[test]
+    fn rejects_bad_loop_positions() {
+        let error = |distance: f32, length: f32| {
+            DetectorSpec { direction: Direction::North, kind: DetectorKind::Advance, distance, length }
+                .validate()
+                .err()
+                .unwrap_or_default()
+        };
+        assert_eq!(error(12.0, 1.0), "");
+        assert_eq!(error(-1.0, 1.0), "distance: must not be negative");
+        assert_eq!(error(12.0, 0.0), "length: must be greater than zero");
+        assert_eq!(error(f32::INFINITY, 1.0), "distance: must be a finite number of metres");
+        assert_eq!(error(12.0, f32::NAN), "length: must be a finite number of metres");
+    }
+}
@@ -0,0 +1,164 @@
+// driver.rs
+// Behaviour of the person at the wheel. Each vehicle gets a driver of some
+// profile, whose parameters are drawn at spawn from that profile's ranges, so
+// no two drivers of a profile are quite alike.
+use std::ops::Range;
+use rand::Rng;
+use crate::weighted::Weighted;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DriverProfile {
+    Aggressive,
+    Normal,
+    Cautious,
+}
+
+/// Parameters of one driver, fixed for the vehicle's trip.
+#[derive(Clone, Copy)]
+pub struct Driver {
+    pub profile: DriverProfile,
+    // Seconds between something happening and the driver acting on it
+    pub reaction_time: f32,
+    // Shortest time, in seconds, before a vehicle on a conflicting movement
+    // reaches the box that the driver will turn left in front of
+    pub critical_gap: f32,
+    // Share of the comfortable deceleration the driver will brake at to stop for
+    // an amber light rather than go through
+    pub amber_braking: f32,
+    // Desired speed as a share of the vehicle's top speed
+    pub speed_factor: f32,
+}
+
+// Ranges each parameter is drawn from, uniformly
+struct ProfileRanges {
+    reaction_time: Range<f32>,
+    critical_gap: Range<f32>,
+    amber_braking: Range<f32>,
+    speed_factor: Range<f32>,
+}
+
+const AGGRESSIVE: ProfileRanges = ProfileRanges {
+    reaction_time: 0.5..0.9,
+    critical_gap: 3.0..4.5,
+    amber_braking: 0.7..0.9,
+    speed_factor: 1.05..1.2,
+};
+
+const NORMAL: ProfileRanges = ProfileRanges {
+    reaction_time: 0.8..1.2,
+    critical_gap: 4.0..5.5,
+    amber_braking: 0.9..1.1,
+    speed_factor: 0.95..1.05,
+};
+
+const CAUTIOUS: ProfileRanges = ProfileRanges {
+    reaction_time: 1.0..1.5,
+    critical_gap: 5.0..7.0,
+    amber_braking: 1.1..1.4,
+    speed_factor: 0.85..0.97,
+};
+
+impl DriverProfile {
+    pub const ALL: [DriverProfile; 3] = [DriverProfile::Aggressive, DriverProfile::Normal, DriverProfile::Cautious];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DriverProfile::Aggressive => "aggressive",
+            DriverProfile::Normal => "normal",
+            DriverProfile::Cautious => "cautious",
+        }
+    }
+
+    /// Draws a driver of this profile.
+    pub fn sample(self, rng: &mut impl Rng) -> Driver {
+        let ranges = match self {
+            DriverProfile::Aggressive => &AGGRESSIVE,
+            DriverProfile::Normal => &NORMAL,
+            DriverProfile::Cautious => &CAUTIOUS,
+        };
+        Driver {
+            profile: self,
+            reaction_time: rng.gen_range(ranges.reaction_time.clone()),
+            critical_gap: rng.gen_range(ranges.critical_gap.clone()),
+            amber_braking: rng.gen_range(ranges.amber_braking.clone()),
+            speed_factor: rng.gen_range(ranges.speed_factor.clone()),
+        }
+    }
+}
+
+impl std::str::FromStr for DriverProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DriverProfile::ALL.into_iter()
+            .find(|profile| profile.name() == s)
+            .ok_or_else(|| format!("unknown driver profile '{}'", s))
+    }
+}
+
+impl Default for Driver {
+    /// A driver in the middle of every normal range.
+    fn default() -> Self {
+        let middle = |range: Range<f32>| (range.start + range.end) / 2.0;
+        Driver {
+            profile: DriverProfile::Normal,
+            reaction_time: middle(NORMAL.reaction_time),
+            critical_gap: middle(NORMAL.critical_gap),
+            amber_braking: middle(NORMAL.amber_braking),
+            speed_factor: middle(NORMAL.speed_factor),
+        }
+    }
+}
+
+/// Share of each driver profile among the vehicles spawned.
+#[derive(Clone)]
+pub struct DriverMix {
+    shares: Weighted<DriverProfile>,
+}
+
+impl DriverMix {
+    /// Draws the profile of the next driver.
+    pub fn pick(&self, rng: &mut impl Rng) -> DriverProfile {
+        self.shares.pick(rng)
+    }
+
+    pub fn describe(&self) -> String {
+        self.shares.describe(DriverProfile::name)
+    }
+}
+
+impl Default for DriverMix {
+    fn default() -> Self {
+        let shares = [(DriverProfile::Aggressive, 20.0), (DriverProfile::Normal, 60.0), (DriverProfile::Cautious, 20.0)];
+        DriverMix { shares: Weighted::new(shares).expect("the default weights are valid") }
+    }
+}
+
+/// Parses a list like `aggressive=20,normal=60,cautious=20`.
+impl std::str::FromStr for DriverMix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(DriverMix { shares: Weighted::parse(s, "profile")? })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_mix_of_profiles() {
+        let mix: DriverMix = "cautious=1,aggressive=3".parse().expect("the mix is valid");
+        assert_eq!(mix.describe(), "cautious=1,aggressive=3");
+        assert_eq!(DriverMix::default().describe(), "aggressive=20,normal=60,cautious=20");
+    }
+
+    #[test]
+    fn rejects_unknown_profiles_and_bad_weights() {
+        let error = |s: &str| s.parse::<DriverMix>().err().unwrap_or_default();
+        assert_eq!(error("reckless=1"), "unknown driver profile 'reckless'");
+        assert_eq!(error("normal=-5"), "weight for normal must not be negative");
+        assert_eq!(error("normal"), "expected profile=weight, got 'normal'");
+    }
+}
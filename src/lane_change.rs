@@ -0,0 +1,205 @@
+// lane_change.rs
+// MOBIL lane changing ("Minimizing Overall Braking Induced by Lane changes"):
+// a vehicle moves to a neighbouring lane when the change is safe for it and for
+// the vehicle that would follow it there, and the acceleration it gains, less a share of what
+// it costs the vehicles behind, clears a threshold. Near the intersection a
+// vehicle in a lane that does not allow its turn must move toward one that does,
+// and takes the first safe gap whatever it gains.
+use crate::lanes::{Approach, Lane};
+use crate::vehicle::{Vehicle, VehicleKind};
+
+// Weight given to the acceleration of the old and new followers
+const POLITENESS: f32 = 0.3;
+// Acceleration gain, in metres per second squared, a discretionary change must bring
+const CHANGE_THRESHOLD: f32 = 0.2;
+// Hardest braking, in metres per second squared, a change may force on the vehicle
+// itself or its new follower
+const SAFE_DECELERATION: f32 = 3.0;
+// Smallest gap, in metres, left to the new leader and the new follower
+const MIN_GAP: f32 = 1.0;
+// Vehicles start moving into a lane for their turn this many metres upstream of the stop line
+const MANDATORY_RANGE: f32 = 30.0;
+/// Lane changes are not allowed this many metres from the stop line.
+pub const NO_CHANGE_ZONE: f32 = 2.0;
+// Added to the incentive of a change toward a lane that allows the vehicle's turn
+const MANDATORY_BIAS: f32 = 10.0;
+
+/// Picks the vehicle's lane for this tick. Within range of the stop line a
+/// vehicle in a lane that does not allow its turn moves toward one that does as
+/// soon as it safely can. Only one that reaches the stop line still in such a
+/// lane misses its route, taking the nearest turn the lane does allow.
+pub fn update_lane(vehicle: &mut Vehicle, vehicles: &[Vehicle], approach: &Approach) {
+    if vehicle.turn_executed || vehicle.changing_lanes() || vehicle.pull_over != 0.0 {
+        return;
+    }
+
+    let distance = vehicle.distance_to_stop_line();
+    if distance < NO_CHANGE_ZONE {
+        if distance >= 0.0 && !vehicle.lane.lane_use.allows(vehicle.turn) {
+            vehicle.turn = vehicle.lane.lane_use.nearest_turn(vehicle.turn);
+        }
+        return;
+    }
+
+    let current = vehicle.lane;
+    let open = || approach.neighbours(&current).filter(|lane| lane.is_open_at(distance));
+    if distance <= MANDATORY_RANGE && !current.lane_use.allows(vehicle.turn) {
+        let toward_turn = open()
+            .filter(|lane| lanes_from_turn(vehicle, approach, lane) < lanes_from_turn(vehicle, approach, &current))
+            .find(|lane| is_safe(vehicle, vehicles, lane));
+        if let Some(lane) = toward_turn {
+            vehicle.lane = *lane;
+        }
+        return;
+    }
+
+    let best = open()
+        .filter_map(|lane| incentive(vehicle, vehicles, approach, lane).map(|gain| (*lane, gain)))
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+
+    if let Some((lane, gain)) = best && gain > CHANGE_THRESHOLD {
+        vehicle.lane = lane;
+    }
+}
+
+// Whether moving into `target` leaves room to brake, both for the vehicle behind
+// its new leader and for its new follower behind it
+fn is_safe(vehicle: &Vehicle, vehicles: &[Vehicle], target: &Lane) -> bool {
+    let (new_leader, new_follower) = neighbours_in_lane(vehicle, vehicles, target);
+    let unsafe_for_vehicle = new_leader.is_some_and(|leader| {
+        leader.gap_behind(vehicle) < MIN_GAP ||
+        vehicle.acceleration_behind(Some(leader)) < -SAFE_DECELERATION
+    });
+    let unsafe_for_follower = new_follower.is_some_and(|follower| {
+        vehicle.gap_behind(follower) < MIN_GAP ||
+        follower.acceleration_behind(Some(vehicle)) < -SAFE_DECELERATION
+    });
+    !unsafe_for_vehicle && !unsafe_for_follower
+}
+
+// Incentive for moving into `target`, or `None` when the change is unsafe or
+// would take the vehicle away from the lanes for its turn near the intersection
+fn incentive(vehicle: &Vehicle, vehicles: &[Vehicle], approach: &Approach, target: &Lane) -> Option<f32> {
+    if !is_safe(vehicle, vehicles, target) {
+        return None;
+    }
+
+    let bias = turn_bias(vehicle, approach, target)?;
+    let (new_leader, new_follower) = neighbours_in_lane(vehicle, vehicles, target);
+    let (old_leader, old_follower) = neighbours_in_lane(vehicle, vehicles, &vehicle.lane);
+
+    let own_gain = vehicle.acceleration_behind(new_leader) - vehicle.acceleration_behind(old_leader);
+    let new_follower_gain = new_follower.map_or(0.0, |follower| {
+        follower.acceleration_behind(Some(vehicle)) - follower.acceleration_behind(new_leader)
+    });
+    let old_follower_gain = old_follower.map_or(0.0, |follower| {
+        follower.acceleration_behind(old_leader) - follower.acceleration_behind(Some(vehicle))
+    });
+
+    Some(own_gain + POLITENESS * (new_follower_gain + old_follower_gain) + bias)
+}
+
+// Bias toward lanes that allow the vehicle's turn. Within range of the stop line
+// a vehicle never leaves a lane for its turn, nor moves further from one.
+fn turn_bias(vehicle: &Vehicle, approach: &Approach, target: &Lane) -> Option<f32> {
+    let current = lanes_from_turn(vehicle, approach, &vehicle.lane);
+    let after = lanes_from_turn(vehicle, approach, target);
+
+    if after < current {
+        Some(MANDATORY_BIAS)
+    } else if after > current && vehicle.distance_to_stop_line() <= MANDATORY_RANGE {
+        None
+    } else {
+        Some(0.0)
+    }
+}
+
+// Number of lanes between `lane` and the nearest that allows the vehicle's turn
+fn lanes_from_turn(vehicle: &Vehicle, approach: &Approach, lane: &Lane) -> usize {
+    approach.lanes.iter()
+        .filter(|other| other.lane_use.allows(vehicle.turn))
+        .map(|other| other.index.abs_diff(lane.index))
+        .min()
+        .unwrap_or(0)
+}
+
+// Nearest vehicles ahead and behind in `lane`, counting those moving into it or
+// out of it and those whose rear is still in it as they pull away into the box
+fn neighbours_in_lane<'a>(vehicle: &Vehicle, vehicles: &'a [Vehicle], lane: &Lane) -> (Option<&'a Vehicle>, Option<&'a Vehicle>) {
+    let in_lane = vehicles.iter().filter(|other| {
+        other.direction == vehicle.direction &&
+        (other.lane == *lane || (other.lateral_center() - lane.center).abs() < other.body_width) &&
+        !(vehicle.kind == VehicleKind::Emergency && other.pull_over != 0.0)
+    });
+
+    let mut leader: Option<&Vehicle> = None;
+    let mut follower: Option<&Vehicle> = None;
+    for other in in_lane {
+        if other.distance_to_stop_line() < vehicle.distance_to_stop_line() {
+            if leader.is_none_or(|l| other.distance_to_stop_line() > l.distance_to_stop_line()) {
+                leader = Some(other);
+            }
+        } else if follower.is_none_or(|f| other.distance_to_stop_line() < f.distance_to_stop_line()) {
+            follower = Some(other);
+        }
+    }
+    (leader, follower)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lanes::RoadLayout;
+    use crate::vehicle::{test_vehicle, Direction, Turn};
+
+    // Eastbound approach whose inside lane is for left turns and through traffic
+    // and whose outside lane is for through traffic and right turns
+    fn approach() -> Approach {
+        let layout = RoadLayout::parse(
+            "[[approaches]]\ndirection = \"east\"\nlanes = [{ lane_use = \"left-through\" }, { lane_use = \"through-right\" }]"
+        ).expect("the layout is valid");
+        layout.approach(Direction::East).clone()
+    }
+
+    fn vehicle(id: u64, lane: usize, turn: Turn, distance: f32, speed: f32) -> Vehicle {
+        let mut vehicle = test_vehicle(Direction::East, turn, distance);
+        vehicle.id = id;
+        vehicle.lane = approach().lanes[lane];
+        vehicle.y = vehicle.lane.center - vehicle.height / 2.0;
+        vehicle.current_speed = speed;
+        vehicle
+    }
+
+    fn lane_after_update(mut subject: Vehicle, others: &[Vehicle]) -> usize {
+        update_lane(&mut subject, others, &approach());
+        subject.lane.index
+    }
+
+    #[test]
+    fn moves_toward_a_lane_for_its_turn_once_it_safely_can() {
+        assert_eq!(lane_after_update(vehicle(1, 0, Turn::Right, 20.0, 8.0), &[]), 1);
+        // A vehicle alongside leaves no gap
+        let alongside = vehicle(2, 1, Turn::Straight, 20.0, 8.0);
+        assert_eq!(lane_after_update(vehicle(1, 0, Turn::Right, 20.0, 8.0), &[alongside]), 0);
+        // Further out the move is discretionary, but the turn still draws it over
+        assert_eq!(lane_after_update(vehicle(1, 0, Turn::Right, 80.0, 8.0), &[]), 1);
+    }
+
+    #[test]
+    fn changes_lane_to_pass_a_stopped_leader() {
+        let stopped = vehicle(2, 0, Turn::Straight, 50.0, 0.0);
+        assert_eq!(lane_after_update(vehicle(1, 0, Turn::Straight, 60.0, 10.0), &[stopped]), 1);
+        // Not for an open road, and not out of the lane for its turn near the stop line
+        assert_eq!(lane_after_update(vehicle(1, 0, Turn::Straight, 60.0, 10.0), &[]), 0);
+        let stopped = vehicle(2, 0, Turn::Left, 10.0, 0.0);
+        assert_eq!(lane_after_update(vehicle(1, 0, Turn::Left, 20.0, 10.0), &[stopped]), 0);
+    }
+
+    #[test]
+    fn reaching_the_stop_line_in_the_wrong_lane_changes_the_turn() {
+        let mut subject = vehicle(1, 0, Turn::Right, 1.0, 5.0);
+        update_lane(&mut subject, &[], &approach());
+        assert_eq!(subject.lane.index, 0);
+        assert_eq!(subject.turn, Turn::Straight);
+    }
+}
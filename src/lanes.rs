@@ -0,0 +1,374 @@
+// lanes.rs
+use std::fs;
+use std::path::Path;
+use rand::Rng;
+use serde::Deserialize;
+use crate::signal_plan::describe_toml_error;
+use crate::simulation::{CENTER_X, CENTER_Y};
+use crate::vehicle::{Direction, Turn, LANE_WIDTH};
+
+/// Lanes that fit on each side of the centre line.
+pub const MAX_LANES: usize = 3;
+
+/// Turns a lane may be used for.
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LaneUse {
+    LeftOnly,
+    Through,
+    RightOnly,
+    LeftThrough,
+    ThroughRight,
+    All,
+}
+
+impl LaneUse {
+    pub fn name(self) -> &'static str {
+        match self {
+            LaneUse::LeftOnly => "left-only",
+            LaneUse::Through => "through",
+            LaneUse::RightOnly => "right-only",
+            LaneUse::LeftThrough => "left-through",
+            LaneUse::ThroughRight => "through-right",
+            LaneUse::All => "all",
+        }
+    }
+
+    pub fn turns(self) -> &'static [Turn] {
+        match self {
+            LaneUse::LeftOnly => &[Turn::Left],
+            LaneUse::Through => &[Turn::Straight],
+            LaneUse::RightOnly => &[Turn::Right],
+            LaneUse::LeftThrough => &[Turn::Left, Turn::Straight],
+            LaneUse::ThroughRight => &[Turn::Straight, Turn::Right],
+            LaneUse::All => &[Turn::Left, Turn::Straight, Turn::Right],
+        }
+    }
+
+    pub fn allows(self, turn: Turn) -> bool {
+        self.turns().contains(&turn)
+    }
+
+    /// The turn this lane may be used for that strays least from `turn`, as
+    /// taken by a vehicle that reaches the stop line in the wrong lane.
+    pub fn nearest_turn(self, turn: Turn) -> Turn {
+        *self.turns().iter()
+            .min_by_key(|allowed| rank(allowed).abs_diff(rank(&turn)))
+            .expect("every lane allows some turn")
+    }
+}
+
+/// One lane of an approach, positioned across the road.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Lane {
+    // Position counted from the driver's left
+    pub index: usize,
+    pub lane_use: LaneUse,
+    // Cross-road coordinate of the lane centre
+    pub center: f32,
+    // A pocket only opens this far upstream of the stop line
+    pub pocket: Option<f32>,
+}
+
+impl Lane {
+    /// Whether the lane exists at `distance` upstream of the stop line.
+    pub fn is_open_at(&self, distance: f32) -> bool {
+        self.pocket.is_none_or(|pocket| distance <= pocket)
+    }
+}
+
+/// Lanes of the approach travelling in `direction`, listed left to right as
+/// seen by the driver. Northbound and southbound traffic keeps left, so its
+/// rightmost lane runs beside the centre line; eastbound and westbound traffic
+/// keeps right, so its leftmost lane does.
+#[derive(Clone)]
+pub struct Approach {
+    pub direction: Direction,
+    pub lanes: Vec<Lane>,
+}
+
+impl Approach {
+    fn new(direction: Direction, lanes: &[LaneFile]) -> Self {
+        let count = lanes.len();
+        let lanes = lanes.iter()
+            .enumerate()
+            .map(|(index, lane)| Lane {
+                index,
+                lane_use: lane.lane_use,
+                center: lane_center(direction, if keeps_left(direction) { count - 1 - index } else { index }),
+                pocket: lane.pocket,
+            })
+            .collect();
+        Approach { direction, lanes }
+    }
+
+    /// A full-length lane for a vehicle entering the approach to make `turn`,
+    /// chosen uniformly among those allowing the turn if any do.
+    pub fn entry_lane(&self, turn: Turn, rng: &mut impl Rng) -> Lane {
+        let full_length: Vec<&Lane> = self.lanes.iter().filter(|lane| lane.pocket.is_none()).collect();
+        let for_turn: Vec<&Lane> = full_length.iter().copied().filter(|lane| lane.lane_use.allows(turn)).collect();
+        let choices = if for_turn.is_empty() { &full_length } else { &for_turn };
+        *choices[rng.gen_range(0..choices.len())]
+    }
+
+    /// Lanes beside `lane`, to its left and then to its right.
+    pub fn neighbours(&self, lane: &Lane) -> impl Iterator<Item = &Lane> {
+        self.lanes.iter().filter(move |other| other.index.abs_diff(lane.index) == 1)
+    }
+}
+
+/// Cross-road coordinate of the centre of the lane `from_centre` lanes out from
+/// the centre line, on the side of the road used by traffic heading in `direction`.
+pub fn lane_center(direction: Direction, from_centre: usize) -> f32 {
+    let offset = (from_centre as f32 + 0.5) * LANE_WIDTH;
+    match direction {
+        Direction::North => CENTER_X - offset,
+        Direction::South => CENTER_X + offset,
+        Direction::East => CENTER_Y + offset,
+        Direction::West => CENTER_Y - offset,
+    }
+}
+
+// Whether traffic heading in `direction` drives on the left of the centre line,
+// which is then on the driver's right
+fn keeps_left(direction: Direction) -> bool {
+    matches!(direction, Direction::North | Direction::South)
+}
+
+// Order of turns from the driver's left to right
+fn rank(turn: &Turn) -> u8 {
+    match turn {
+        Turn::Left => 0,
+        Turn::Straight => 1,
+        Turn::Right => 2,
+    }
+}
+
+/// Lane configuration of every approach.
+#[derive(Clone)]
+pub struct RoadLayout {
+    pub approaches: Vec<Approach>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LayoutFile {
+    approaches: Vec<ApproachFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ApproachFile {
+    direction: Direction,
+    lanes: Vec<LaneFile>,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LaneFile {
+    lane_use: LaneUse,
+    // Metres upstream of the stop line
+    pocket: Option<f32>,
+}
+
+const SINGLE_LANE: [LaneFile; 1] = [LaneFile { lane_use: LaneUse::All, pocket: None }];
+
+impl RoadLayout {
+    /// One lane for every movement on each approach.
+    pub fn single_lane() -> Self {
+        RoadLayout {
+            approaches: Direction::ALL.iter().map(|&d| Approach::new(d, &SINGLE_LANE)).collect(),
+        }
+    }
+
+    /// Loads a layout from a TOML file. Approaches not listed keep a single lane.
+    /// Errors name the file and the offending field.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("road layout {}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("road layout {}: {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let file: LayoutFile = toml::from_str(text).map_err(|e| describe_toml_error(text, &e))?;
+
+        let mut layout = Self::single_lane();
+        for (i, approach) in file.approaches.iter().enumerate() {
+            let listed_before = file.approaches[..i].iter().position(|a| a.direction == approach.direction);
+            if let Some(j) = listed_before {
+                return Err(format!("approaches[{}].direction: duplicates approaches[{}]", i, j));
+            }
+            Self::validate_lanes(&approach.lanes).map_err(|e| format!("approaches[{}].{}", i, e))?;
+
+            let index = Direction::ALL.iter().position(|&d| d == approach.direction).unwrap();
+            layout.approaches[index] = Approach::new(approach.direction, &approach.lanes);
+        }
+        Ok(layout)
+    }
+
+    fn validate_lanes(lanes: &[LaneFile]) -> Result<(), String> {
+        if lanes.is_empty() {
+            return Err("lanes: at least one lane is required".to_string());
+        }
+        if lanes.len() > MAX_LANES {
+            return Err(format!("lanes: at most {} lanes fit on each side of the road", MAX_LANES));
+        }
+
+        for turn in [Turn::Left, Turn::Straight, Turn::Right] {
+            if !lanes.iter().any(|lane| lane.lane_use.allows(turn)) {
+                return Err(format!("lanes: no lane allows {} turns", turn.name()));
+            }
+        }
+
+        // Turns must not cross inside the intersection: every turn allowed from a
+        // lane must be at least as far right as those allowed from lanes to its left
+        for (i, lane) in lanes.iter().enumerate() {
+            let lowest = lane.lane_use.turns().iter().map(rank).min().unwrap();
+            for (j, left) in lanes.iter().enumerate().take(i) {
+                if left.lane_use.turns().iter().map(rank).max().unwrap() > lowest {
+                    return Err(format!("lanes[{}].lane_use: {} crosses the turns of lanes[{}]", i, lane.lane_use.name(), j));
+                }
+            }
+
+            if let Some(pocket) = lane.pocket {
+                let at_edge = (i == 0 && lane.lane_use == LaneUse::LeftOnly) ||
+                              (i == lanes.len() - 1 && lane.lane_use == LaneUse::RightOnly);
+                if !at_edge || lanes.len() < 2 {
+                    return Err(format!(
+                        "lanes[{}].pocket: only a left-only lane on the left or a right-only lane on the right can be a pocket",
+                        i
+                    ));
+                }
+                if pocket.is_nan() || pocket <= 0.0 {
+                    return Err(format!("lanes[{}].pocket: must be greater than zero", i));
+                }
+            }
+        }
+
+        if lanes.iter().all(|lane| lane.pocket.is_some()) {
+            return Err("lanes: at least one lane must run the full length of the approach".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn approach(&self, direction: Direction) -> &Approach {
+        self.approaches.iter()
+            .find(|approach| approach.direction == direction)
+            .expect("layout covers every approach")
+    }
+}
+
+impl Default for RoadLayout {
+    fn default() -> Self {
+        Self::single_lane()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    const LAYOUT: &str = include_str!("../layouts/turn_lanes.toml");
+
+    fn error(text: &str) -> String {
+        RoadLayout::parse(text).err().unwrap_or_default()
+    }
+
+    // Layout with only the northbound approach given `lanes`
+    fn north_lanes(lanes: &str) -> String {
+        format!("[[approaches]]\ndirection = \"north\"\nlanes = [{}]", lanes)
+    }
+
+    #[test]
+    fn shipped_layout_places_lanes_from_the_centre_line() {
+        let layout = RoadLayout::parse(LAYOUT).expect("the shipped layout is valid");
+        let north = layout.approach(Direction::North);
+        let uses: Vec<_> = north.lanes.iter().map(|lane| lane.lane_use.name()).collect();
+        assert_eq!(uses, ["left-only", "through", "through-right"]);
+        // Northbound traffic keeps left, so its rightmost lane is beside the centre line
+        assert_eq!(north.lanes[2].center, CENTER_X - LANE_WIDTH / 2.0);
+        assert_eq!(north.lanes[0].center, lane_center(Direction::North, 2));
+        let east = layout.approach(Direction::East);
+        assert_eq!(east.lanes[0].center, CENTER_Y + LANE_WIDTH / 2.0);
+        assert_eq!(east.lanes[1].pocket, Some(10.0));
+    }
+
+    #[test]
+    fn approaches_left_out_keep_a_single_lane() {
+        let layout = RoadLayout::parse(&north_lanes("{ lane_use = \"left-through\" }, { lane_use = \"right-only\" }"))
+            .expect("the layout is valid");
+        assert_eq!(layout.approach(Direction::North).lanes.len(), 2);
+        for direction in [Direction::South, Direction::East, Direction::West] {
+            let lanes = &layout.approach(direction).lanes;
+            assert_eq!(lanes.len(), 1);
+            assert_eq!(lanes[0].lane_use.name(), "all");
+            assert_eq!(lanes[0].center, direction.lane_center());
+        }
+    }
+
+    #[test]
+    fn entry_lanes_are_full_length_lanes_allowing_the_turn() {
+        let layout = RoadLayout::parse(LAYOUT).expect("the shipped layout is valid");
+        let north = layout.approach(Direction::North);
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut chosen = |turn| {
+            let mut indices: Vec<_> = (0..50).map(|_| north.entry_lane(turn, &mut rng).index).collect();
+            indices.sort();
+            indices.dedup();
+            indices
+        };
+        assert_eq!(chosen(Turn::Straight), [1, 2]);
+        assert_eq!(chosen(Turn::Right), [2]);
+        // The left-turn pocket is only joined further down the approach
+        assert_eq!(chosen(Turn::Left), [1, 2]);
+    }
+
+    #[test]
+    fn lanes_know_their_neighbours_and_where_they_open() {
+        let layout = RoadLayout::parse(LAYOUT).expect("the shipped layout is valid");
+        let north = layout.approach(Direction::North);
+        let beside = |index: usize| north.neighbours(&north.lanes[index]).map(|lane| lane.index).collect::<Vec<_>>();
+        assert_eq!(beside(0), [1]);
+        assert_eq!(beside(1), [0, 2]);
+        assert!(north.lanes[0].is_open_at(12.0));
+        assert!(!north.lanes[0].is_open_at(12.5));
+        assert!(north.lanes[1].is_open_at(100.0));
+    }
+
+    #[test]
+    fn vehicles_in_the_wrong_lane_take_the_nearest_allowed_turn() {
+        assert_eq!(LaneUse::LeftOnly.nearest_turn(Turn::Right), Turn::Left);
+        assert_eq!(LaneUse::ThroughRight.nearest_turn(Turn::Left), Turn::Straight);
+        assert_eq!(LaneUse::LeftThrough.nearest_turn(Turn::Straight), Turn::Straight);
+    }
+
+    #[test]
+    fn rejects_bad_lane_layouts() {
+        let duplicated = format!("{}\n{}", north_lanes("{ lane_use = \"all\" }"), north_lanes("{ lane_use = \"all\" }"));
+        assert_eq!(error(&duplicated), "approaches[1].direction: duplicates approaches[0]");
+        assert_eq!(error(&north_lanes("")), "approaches[0].lanes: at least one lane is required");
+        assert_eq!(
+            error(&north_lanes(&["{ lane_use = \"all\" }"; 4].join(", "))),
+            "approaches[0].lanes: at most 3 lanes fit on each side of the road",
+        );
+        assert_eq!(
+            error(&north_lanes("{ lane_use = \"left-through\" }")),
+            "approaches[0].lanes: no lane allows right turns",
+        );
+        assert_eq!(
+            error(&north_lanes("{ lane_use = \"through-right\" }, { lane_use = \"left-only\" }")),
+            "approaches[0].lanes[1].lane_use: left-only crosses the turns of lanes[0]",
+        );
+        assert_eq!(
+            error(&north_lanes("{ lane_use = \"through\", pocket = 10 }, { lane_use = \"all\" }")),
+            "approaches[0].lanes[0].pocket: only a left-only lane on the left or a right-only lane on the right can be a pocket",
+        );
+        assert_eq!(
+            error(&north_lanes("{ lane_use = \"left-only\", pocket = 0 }, { lane_use = \"all\" }")),
+            "approaches[0].lanes[0].pocket: must be greater than zero",
+        );
+    }
+}
@@ -0,0 +1,24 @@
+pub mod car_following;
+pub mod clock;
+pub mod collision;
+pub mod control;
+pub mod controller;
+pub mod deadlock;
+pub mod demand;
+pub mod detector;
+pub mod driver;
+pub mod lane_change;
+pub mod lanes;
+pub mod options;
+pub mod pedestrian;
+pub mod perception;
+pub mod reservation;
+pub mod routes;
+pub mod scenario;
+pub mod signal_plan;
+pub mod simulation;
+pub mod traffic_light;
+pub mod turn_path;
+pub mod vehicle;
+pub mod vehicle_class;
+pub mod weighted;
@@ -0,0 +1,138 @@
+// options.rs
+use std::path::PathBuf;
+use std::time::Duration;
+use crate::car_following::CarFollowingModel;
+use crate::clock::DEFAULT_TICK_DURATION;
+use crate::control::IntersectionControl;
+use crate::controller::ControllerKind;
+use crate::driver::DriverMix;
+use crate::reservation::GrantPolicy;
+use crate::signal_plan::LeftTurnPhasing;
+use crate::vehicle_class::TrafficMix;
+
+// Range of tick lengths, in milliseconds, the vehicle models stay sound over
+const MIN_TICK_MS: f64 = 1.0;
+const MAX_TICK_MS: f64 = 1000.0;
+
+/// Command-line options shared by the windowed and headless front-ends.
+pub struct Options {
+    pub steps: Option<u64>,
+    pub tick_duration: Duration,
+    pub seed: Option<u64>,
+    // Only used for the built-in plan, so not allowed with a signal plan file
+    pub left_turns: LeftTurnPhasing,
+    pub signal_plan: Option<PathBuf>,
+    pub control: IntersectionControl,
+    pub controller: ControllerKind,
+    pub scenario: Option<PathBuf>,
+    pub layout: Option<PathBuf>,
+    pub od_matrix: Option<PathBuf>,
+    pub demand: Option<PathBuf>,
+    pub car_following: CarFollowingModel,
+    pub grant_policy: GrantPolicy,
+    pub traffic_mix: TrafficMix,
+    pub driver_mix: DriverMix,
+}
+
+impl Options {
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut left_turns_given = false;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--steps" => {
+                    options.steps = Some(parse_value(&arg, args.next())?);
+                }
+                "--tick-ms" => {
+                    let millis: f64 = parse_value(&arg, args.next())?;
+                    if !(MIN_TICK_MS..=MAX_TICK_MS).contains(&millis) {
+                        return Err(format!("{} must be between {} and {} milliseconds", arg, MIN_TICK_MS, MAX_TICK_MS));
+                    }
+                    options.tick_duration = Duration::from_secs_f64(millis / 1000.0);
+                }
+                "--seed" => {
+                    options.seed = Some(parse_value(&arg, args.next())?);
+                }
+                "--left-turns" => {
+                    options.left_turns = parse_value(&arg, args.next())?;
+                    left_turns_given = true;
+                }
+                "--signal-plan" => {
+                    options.signal_plan = Some(parse_value(&arg, args.next())?);
+                }
+                "--control" => {
+                    options.control = parse_value(&arg, args.next())?;
+                }
+                "--controller" => {
+                    options.controller = parse_value(&arg, args.next())?;
+                }
+                "--layout" => {
+                    options.layout = Some(parse_value(&arg, args.next())?);
+                }
+                "--od-matrix" => {
+                    options.od_matrix = Some(parse_value(&arg, args.next())?);
+                }
+                "--demand" => {
+                    options.demand = Some(parse_value(&arg, args.next())?);
+                }
+                "--scenario" => {
+                    options.scenario = Some(parse_value(&arg, args.next())?);
+                }
+                "--car-following" => {
+                    options.car_following = parse_value(&arg, args.next())?;
+                }
+                "--grants" => {
+                    options.grant_policy = parse_value(&arg, args.next())?;
+                }
+                "--mix" => {
+                    options.traffic_mix = parse_value(&arg, args.next())?;
+                }
+                "--drivers" => {
+                    options.driver_mix = parse_value(&arg, args.next())?;
+                }
+                _ => return Err(format!("unknown argument '{}'", arg)),
+            }
+        }
+
+        if left_turns_given && options.signal_plan.is_some() {
+            return Err("--left-turns only applies to the built-in plan; time left turns in the --signal-plan file instead".to_string());
+        }
+        Ok(options)
+    }
+
+    /// The seed given on the command line, or a fresh one drawn from the OS.
+    pub fn seed_or_random(&self) -> u64 {
+        self.seed.unwrap_or_else(rand::random)
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            steps: None,
+            tick_duration: DEFAULT_TICK_DURATION,
+            seed: None,
+            left_turns: LeftTurnPhasing::Permissive,
+            signal_plan: None,
+            control: IntersectionControl::default(),
+            controller: ControllerKind::Congestion,
+            scenario: None,
+            layout: None,
+            od_matrix: None,
+            demand: None,
+            car_following: CarFollowingModel::default(),
+            grant_policy: GrantPolicy::default(),
+            traffic_mix: TrafficMix::default(),
+            driver_mix: DriverMix::default(),
+        }
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String>
+where
+    T::Err: std::fmt::Display,
+{
+    let value = value.ok_or_else(|| format!("{} requires a value", flag))?;
+    value.parse().map_err(|e| format!("invalid {} '{}': {}", flag, value, e))
+}
@@ -0,0 +1,194 @@
+// pedestrian.rs
+use std::f32::consts::FRAC_PI_2;
+use rand::Rng;
+use serde::Deserialize;
+use crate::collision::Body;
+use crate::simulation::{CENTER_X, CENTER_Y};
+use crate::traffic_light::ROAD_WIDTH;
+use crate::vehicle::Direction;
+
+/// Depth of a crosswalk, measured along the road it crosses.
+pub const CROSSWALK_WIDTH: f32 = 1.4;
+/// Distance from the intersection centre to each stop line; the crosswalk lies
+/// between the stop line and the intersection box.
+pub const STOP_LINE_OFFSET: f32 = ROAD_WIDTH / 2.0 + CROSSWALK_WIDTH + 0.4;
+// Pedestrians wait this far back from the kerb
+const KERB_OFFSET: f32 = 0.8;
+// Walking speeds in metres per second
+const MIN_WALKING_SPEED: f32 = 1.2;
+const MAX_WALKING_SPEED: f32 = 1.6;
+
+/// Arm of the intersection, named by its compass side. Each leg has one crosswalk.
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Leg {
+    North,
+    South,
+    East,
+    West,
+}
+
+impl Leg {
+    pub const ALL: [Leg; 4] = [Leg::North, Leg::South, Leg::East, Leg::West];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Leg::North => "north",
+            Leg::South => "south",
+            Leg::East => "east",
+            Leg::West => "west",
+        }
+    }
+
+    /// Leg through which traffic travelling in `direction` enters the intersection.
+    pub fn entered_by(direction: Direction) -> Leg {
+        match direction {
+            Direction::North => Leg::South,
+            Direction::South => Leg::North,
+            Direction::East => Leg::West,
+            Direction::West => Leg::East,
+        }
+    }
+
+    /// Leg through which traffic travelling in `direction` leaves the intersection.
+    pub fn exited_by(direction: Direction) -> Leg {
+        match direction {
+            Direction::North => Leg::North,
+            Direction::South => Leg::South,
+            Direction::East => Leg::East,
+            Direction::West => Leg::West,
+        }
+    }
+
+    /// Centre line of the crosswalk, across the direction pedestrians walk.
+    pub fn crosswalk_center(self) -> f32 {
+        let offset = ROAD_WIDTH / 2.0 + CROSSWALK_WIDTH / 2.0;
+        match self {
+            Leg::North => CENTER_Y - offset,
+            Leg::South => CENTER_Y + offset,
+            Leg::East => CENTER_X + offset,
+            Leg::West => CENTER_X - offset,
+        }
+    }
+
+    /// Area of the road the crosswalk covers.
+    pub fn crosswalk(self) -> Body {
+        let center = self.crosswalk_center();
+        let (center, heading) = match self {
+            Leg::North | Leg::South => ((CENTER_X, center), 0.0),
+            Leg::East | Leg::West => ((center, CENTER_Y), FRAC_PI_2),
+        };
+        Body { center, heading, length: ROAD_WIDTH, width: CROSSWALK_WIDTH }
+    }
+}
+
+/// Pedestrian signal head. Pedestrians may start crossing only on `Walk`;
+/// flashing DON'T WALK gives those already crossing time to finish. Without
+/// signal control the heads are `Dark` and pedestrians cross whenever traffic
+/// leaves them room.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PedestrianSignal {
+    DontWalk,
+    Walk,
+    FlashingDontWalk,
+    Dark,
+}
+
+/// A crosswalk's signal and push-button state, owned by the signal system.
+#[derive(Clone)]
+pub struct Crosswalk {
+    pub leg: Leg,
+    pub signal: PedestrianSignal,
+    // Push button pressed and not yet served
+    pub called: bool,
+    // Tick the current signal indication began
+    pub signal_since: u64,
+    pub walks_served: u64,
+}
+
+impl Crosswalk {
+    pub fn new(leg: Leg) -> Self {
+        Crosswalk {
+            leg,
+            signal: PedestrianSignal::DontWalk,
+            called: false,
+            signal_since: 0,
+            walks_served: 0,
+        }
+    }
+}
+
+/// A pedestrian waiting at the kerb or walking across one crosswalk.
+#[derive(Clone)]
+pub struct Pedestrian {
+    pub x: f32,
+    pub y: f32,
+    pub crosswalk: Leg,
+    // +1.0 or -1.0 along the crossing
+    pub heading: f32,
+    pub speed: f32,
+    pub crossing: bool,
+    pub spawn_tick: u64,
+}
+
+impl Pedestrian {
+    /// Places a pedestrian at a random end of the crosswalk on `crosswalk`.
+    pub fn new(crosswalk: Leg, spawn_tick: u64, rng: &mut impl Rng) -> Self {
+        let heading = if rng.gen_bool(0.5) { 1.0 } else { -1.0 };
+        let start = -heading * (ROAD_WIDTH / 2.0 + KERB_OFFSET);
+        let center = crosswalk.crosswalk_center();
+        let (x, y) = match crosswalk {
+            Leg::North | Leg::South => (CENTER_X + start, center),
+            Leg::East | Leg::West => (center, CENTER_Y + start),
+        };
+
+        Pedestrian {
+            x,
+            y,
+            crosswalk,
+            heading,
+            speed: rng.gen_range(MIN_WALKING_SPEED..MAX_WALKING_SPEED),
+            crossing: false,
+            spawn_tick,
+        }
+    }
+
+    /// Starts crossing on WALK, or with the heads dark once `traffic_clear`;
+    /// once on the road the pedestrian keeps going. `dt` is the tick length in
+    /// seconds.
+    pub fn update(&mut self, signal: PedestrianSignal, traffic_clear: bool, dt: f32) {
+        let may_start = match signal {
+            PedestrianSignal::Walk => true,
+            PedestrianSignal::Dark => traffic_clear,
+            PedestrianSignal::DontWalk | PedestrianSignal::FlashingDontWalk => false,
+        };
+        if !self.crossing && may_start {
+            self.crossing = true;
+        }
+        if !self.crossing {
+            return;
+        }
+
+        match self.crosswalk {
+            Leg::North | Leg::South => self.x += self.heading * self.speed * dt,
+            Leg::East | Leg::West => self.y += self.heading * self.speed * dt,
+        }
+    }
+
+    // Offset from the centre of the road being crossed, in the walking direction
+    fn progress(&self) -> f32 {
+        match self.crosswalk {
+            Leg::North | Leg::South => (self.x - CENTER_X) * self.heading,
+            Leg::East | Leg::West => (self.y - CENTER_Y) * self.heading,
+        }
+    }
+
+    /// Whether the pedestrian is walking across the crosswalk on `leg`.
+    pub fn on_crosswalk(&self, leg: Leg) -> bool {
+        self.crosswalk == leg && self.crossing && self.progress() < ROAD_WIDTH / 2.0
+    }
+
+    pub fn has_crossed(&self) -> bool {
+        self.progress() >= ROAD_WIDTH / 2.0 + KERB_OFFSET
+    }
+}
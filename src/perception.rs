@@ -0,0 +1,148 @@
+// perception.rs
+// What drivers see. The road as it stood at the start of each step is kept for a
+// couple of seconds, and every driver acts on the view from one reaction time
+// ago rather than on the road as it is. A queue standing at a red light thus
+// sets off one driver after another once it turns green, each a reaction time
+// after the one ahead, instead of all at once. A vehicle actually nearer ahead
+// than any in view is still braked for at once, as it fills the windscreen.
+use std::collections::VecDeque;
+use crate::traffic_light::{TrafficLightState, TrafficLightSystem};
+use crate::vehicle::{Direction, Glimpse, Turn, Vehicle};
+
+// Longest reaction time remembered for, in seconds
+const MEMORY: f32 = 2.0;
+
+/// The signal heads facing each approach, as they were shown at some tick.
+#[derive(Clone, Copy)]
+pub struct SignalView {
+    // Whether drivers obey the lights at all
+    pub signalized: bool,
+    // Indexed like `Direction::ALL`
+    balls: [TrafficLightState; 4],
+    lefts: [TrafficLightState; 4],
+}
+
+impl SignalView {
+    pub fn of(lights: &TrafficLightSystem) -> Self {
+        SignalView {
+            signalized: lights.effective_control().is_signalized(),
+            balls: Direction::ALL.map(|direction| lights.signal_for(direction, Turn::Straight)),
+            lefts: Direction::ALL.map(|direction| lights.signal_for(direction, Turn::Left)),
+        }
+    }
+
+    /// Signal that traffic making `turn` from `direction` obeys.
+    pub fn signal_for(&self, direction: Direction, turn: Turn) -> TrafficLightState {
+        let i = Direction::ALL.iter().position(|&other| other == direction).unwrap_or(0);
+        match turn {
+            Turn::Left => self.lefts[i],
+            Turn::Straight | Turn::Right => self.balls[i],
+        }
+    }
+}
+
+struct View {
+    vehicles: Vec<Glimpse>,
+    signals: SignalView,
+}
+
+/// Views of the road over the last few seconds, newest first.
+#[derive(Default)]
+pub struct Perception {
+    views: VecDeque<View>,
+}
+
+impl Perception {
+    /// Remembers the road as it is now, forgetting views older than any driver
+    /// reacts to. `tick_seconds` is the length of a step.
+    pub fn record(&mut self, vehicles: &[Vehicle], lights: &TrafficLightSystem, tick_seconds: f32) {
+        // The oldest view's buffer is reused for the newest
+        let kept = (MEMORY / tick_seconds).ceil() as usize + 1;
+        let mut glimpses = if self.views.len() >= kept {
+            self.views.pop_back().map(|view| view.vehicles).unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        glimpses.clear();
+        glimpses.extend(vehicles.iter().map(Vehicle::glimpse));
+        self.views.push_front(View { vehicles: glimpses, signals: SignalView::of(lights) });
+        self.views.truncate(kept);
+    }
+
+    /// The vehicles and the lights as `vehicle`'s driver sees them, one reaction
+    /// time late, or as early as is remembered. The vehicle itself is among them.
+    /// Panics if nothing has been recorded yet.
+    pub fn seen_by(&self, vehicle: &Vehicle, tick_seconds: f32) -> (&[Glimpse], SignalView) {
+        let ticks = (vehicle.driver.reaction_time / tick_seconds).round() as usize;
+        let view = self.views.get(ticks).or(self.views.back()).expect("a view is recorded before any is seen");
+        (&view.vehicles, view.signals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traffic_light::SignalMode;
+    use crate::vehicle::test_vehicle;
+
+    const TICK: f32 = 0.5;
+
+    // Eastbound car `x` metres along the road, with a one-second reaction time
+    fn car_at(x: f32) -> Vehicle {
+        let mut vehicle = test_vehicle(Direction::East, Turn::Straight, 50.0);
+        vehicle.x = x;
+        vehicle
+    }
+
+    fn seen_x(perception: &Perception, vehicle: &Vehicle) -> f32 {
+        perception.seen_by(vehicle, TICK).0[0].center().0 - vehicle.width / 2.0
+    }
+
+    #[test]
+    fn drivers_see_the_road_one_reaction_time_late() {
+        let lights = TrafficLightSystem::new();
+        let mut perception = Perception::default();
+        perception.record(&[car_at(0.0)], &lights, TICK);
+        // Nothing older is remembered yet
+        assert_eq!(seen_x(&perception, &car_at(0.0)), 0.0);
+        for x in 1..4 {
+            perception.record(&[car_at(x as f32)], &lights, TICK);
+        }
+        assert_eq!(seen_x(&perception, &car_at(3.0)), 1.0);
+
+        let mut quick = car_at(3.0);
+        quick.driver.reaction_time = 0.5;
+        assert_eq!(seen_x(&perception, &quick), 2.0);
+    }
+
+    #[test]
+    fn views_are_kept_only_as_long_as_any_driver_reacts() {
+        let lights = TrafficLightSystem::new();
+        let mut perception = Perception::default();
+        for x in 0..20 {
+            perception.record(&[car_at(x as f32)], &lights, TICK);
+        }
+        assert_eq!(perception.views.len(), 5);
+        let mut slow = car_at(19.0);
+        slow.driver.reaction_time = 10.0;
+        assert_eq!(seen_x(&perception, &slow), 15.0);
+    }
+
+    #[test]
+    fn signals_are_seen_as_they_were_shown() {
+        let mut lights = TrafficLightSystem::new();
+        let mut perception = Perception::default();
+        perception.record(&[], &lights, TICK);
+        lights.set_mode(SignalMode::Dark, 1);
+        perception.record(&[], &lights, TICK);
+        perception.record(&[], &lights, TICK);
+
+        let (_, signals) = perception.seen_by(&car_at(0.0), TICK);
+        assert!(signals.signalized);
+        assert_eq!(signals.signal_for(Direction::East, Turn::Straight), TrafficLightState::Green);
+        assert_eq!(signals.signal_for(Direction::North, Turn::Left), TrafficLightState::Red);
+        let mut quick = car_at(0.0);
+        quick.driver.reaction_time = 0.5;
+        assert!(!perception.seen_by(&quick, TICK).1.signalized);
+    }
+}
@@ -0,0 +1,600 @@
+// reservation.rs
+// Conflict-zone reservations for the intersection box. The box is divided into
+// a grid of cells, and a vehicle coming up to it asks for every cell its body
+// will cover on its movement. It waits at the stop line until the request is
+// granted, and gives the cells back one by one as its body leaves them, so no
+// two vehicles ever hold the same cell. The one exception is vehicles following
+// each other on the same movement, whom car following keeps apart. Should the
+// vehicles ever end up waiting on each other in a cycle, the one that has waited
+// longest is let through as soon as the cells it wants are free.
+use crate::collision::{distance, Body};
+use crate::control::{self, IntersectionControl, Sign};
+use crate::deadlock::{DeadlockEvent, WaitForGraph};
+use crate::simulation::{CENTER_X, CENTER_Y};
+use crate::traffic_light::ROAD_WIDTH;
+use crate::vehicle::{Direction, StopReason, Turn, Vehicle, VehicleKind};
+
+/// Cells along each side of the box.
+pub const GRID: usize = 10;
+const CELL_SIZE: f32 = ROAD_WIDTH / GRID as f32;
+// Clearance, in metres, kept around a body when working out the cells it covers
+const CELL_MARGIN: f32 = 0.3;
+// Clearance, in metres, within which a stalled vehicle is up against another
+const STALL_CLEARANCE: f32 = 0.05;
+// Spacing of the poses sampled along a movement
+const ROUTE_STEP: f32 = 0.5;
+// A vehicle this far across from where it asked, in metres, is on another movement
+const LATERAL_TOLERANCE: f32 = 0.5;
+// A vehicle standing this close to the stop line, in metres, has stopped at it
+const STOP_LINE_TOLERANCE: f32 = 1.0;
+
+/// Order in which waiting requests are granted at a signalized intersection.
+/// Without signals, the rules of the road decide instead.
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum GrantPolicy {
+    /// Strictly in the order the requests were made.
+    FirstComeFirstServed,
+    /// Emergency vehicles first, then by the right of way of the movement, then
+    /// in order of request.
+    #[default]
+    Priority,
+}
+
+// One bit per cell, row by row from the north-west corner of the box
+type Cells = u128;
+
+struct Reservation {
+    vehicle: u64,
+    // The movement the cells were worked out for
+    direction: Direction,
+    turn: Turn,
+    lateral: f32,
+    emergency: bool,
+    // Gap, in seconds, the driver needs before turning left across traffic
+    critical_gap: f32,
+    requested_at: u64,
+    granted: bool,
+    // Body at each pose along the movement
+    route: Vec<Body>,
+    // Cells covered from each pose to the end of the movement
+    ahead: Vec<Cells>,
+    // Pose the vehicle has reached
+    progress: usize,
+}
+
+/// Hands out the cells of the intersection box to the vehicles crossing it.
+#[derive(Default)]
+pub struct ReservationManager {
+    pub policy: GrantPolicy,
+    // Right-of-way rules in force at the last update
+    control: IntersectionControl,
+    // Waiting and granted requests, in the order they were made
+    reservations: Vec<Reservation>,
+    // Requests granted over the run
+    pub grants: u64,
+    // Every deadlock found, in the order it happened
+    pub deadlocks: Vec<DeadlockEvent>,
+    // Cycle last logged without a way to break it, so it is logged once
+    stalled: Vec<u64>,
+}
+
+impl GrantPolicy {
+    pub fn name(self) -> &'static str {
+        match self {
+            GrantPolicy::FirstComeFirstServed => "fcfs",
+            GrantPolicy::Priority => "priority",
+        }
+    }
+}
+
+impl std::str::FromStr for GrantPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fcfs" => Ok(GrantPolicy::FirstComeFirstServed),
+            "priority" => Ok(GrantPolicy::Priority),
+            _ => Err("expected fcfs or priority".to_string()),
+        }
+    }
+}
+
+impl ReservationManager {
+    pub fn new(policy: GrantPolicy) -> Self {
+        ReservationManager { policy, ..Default::default() }
+    }
+
+    /// Brings the requests up to date with where the vehicles are, grants what
+    /// can be granted, and tells each vehicle whether it may cross.
+    pub fn update(&mut self, vehicles: &mut [Vehicle], control: IntersectionControl, tick: u64) {
+        self.control = control;
+        self.release(vehicles);
+        self.request(vehicles, tick);
+        self.grant(vehicles);
+        self.resolve_deadlocks(vehicles, tick);
+        for vehicle in vehicles.iter_mut() {
+            vehicle.room_before_crossing = self.room_before_crossing(vehicle);
+            vehicle.reservation_granted = self.is_granted(vehicle.id);
+        }
+    }
+
+    /// Whether a granted reservation holds the cell at `row` and `column`.
+    pub fn is_held(&self, row: usize, column: usize) -> bool {
+        let cell = 1 << (row * GRID + column);
+        self.reservations.iter().any(|reservation| reservation.granted && reservation.cells() & cell != 0)
+    }
+
+    /// Square covered by the cell at `row` and `column`, as its top-left corner and
+    /// side in metres.
+    pub fn cell_bounds(row: usize, column: usize) -> (f32, f32, f32) {
+        let (left, top) = (CENTER_X - ROAD_WIDTH / 2.0, CENTER_Y - ROAD_WIDTH / 2.0);
+        (left + column as f32 * CELL_SIZE, top + row as f32 * CELL_SIZE, CELL_SIZE)
+    }
+
+    fn has_request(&self, vehicle: u64) -> bool {
+        self.reservations.iter().any(|reservation| reservation.vehicle == vehicle)
+    }
+
+    fn is_granted(&self, vehicle: u64) -> bool {
+        self.reservations.iter().any(|reservation| reservation.vehicle == vehicle && reservation.granted)
+    }
+
+    // Follows each vehicle along its movement, giving back the cells it has left.
+    // Requests lapse once the vehicle is across, or, before it enters the box or
+    // sets off along its turn, once it stops for the light, takes another movement or is queued behind a
+    // vehicle that no longer holds a grant
+    fn release(&mut self, vehicles: &[Vehicle]) {
+        self.reservations.retain_mut(|reservation| {
+            let Some(vehicle) = vehicles.iter().find(|vehicle| vehicle.id == reservation.vehicle) else {
+                return false;
+            };
+            reservation.follow(vehicle);
+            if reservation.cells() == 0 && vehicle.path.is_none() {
+                return false;
+            }
+            vehicle.in_box() || vehicle.path.is_some() || (wants_to_cross(vehicle) && reservation.fits(vehicle))
+        });
+
+        loop {
+            let lapsed: Vec<u64> = self.reservations.iter()
+                .filter_map(|reservation| vehicles.iter().find(|vehicle| vehicle.id == reservation.vehicle))
+                .filter(|vehicle| !vehicle.in_box() && self.queued(vehicle, vehicles))
+                .map(|vehicle| vehicle.id)
+                .collect();
+            if lapsed.is_empty() {
+                break;
+            }
+            self.reservations.retain(|reservation| !lapsed.contains(&reservation.vehicle));
+        }
+    }
+
+    // Whether the vehicle is queued behind one coming up to the box that has not
+    // been granted its cells yet
+    fn queued(&self, vehicle: &Vehicle, vehicles: &[Vehicle]) -> bool {
+        vehicles.iter().any(|other| {
+            other.id != vehicle.id && other.direction == vehicle.direction && vehicle.same_lane(other) &&
+            other.gap_behind(vehicle) > 0.0 && other.needs_reservation() && !self.is_granted(other.id)
+        })
+    }
+
+    // Files requests for the vehicles coming up to the box, each once the one
+    // ahead of it in its lane holds a grant and, facing a stop sign, once it has
+    // stopped at the line. Any vehicle found in the box without one is forced
+    // a grant
+    fn request(&mut self, vehicles: &[Vehicle], tick: u64) {
+        let mut arriving: Vec<&Vehicle> = vehicles.iter()
+            .filter(|vehicle| !self.has_request(vehicle.id) && (vehicle.in_box() || wants_to_cross(vehicle)))
+            .collect();
+        arriving.sort_by(|a, b| a.distance_to_box().total_cmp(&b.distance_to_box()));
+
+        for vehicle in arriving {
+            let forced = vehicle.in_box();
+            if !forced && (self.queued(vehicle, vehicles) || !self.stopped_if_signed(vehicle)) {
+                continue;
+            }
+
+            let mut reservation = Reservation::new(vehicle, tick);
+            reservation.granted = forced;
+            self.grants += forced as u64;
+            self.reservations.push(reservation);
+        }
+    }
+
+    // Whether a vehicle facing a stop sign has come to a halt at the line.
+    // Emergency vehicles do not stop
+    fn stopped_if_signed(&self, vehicle: &Vehicle) -> bool {
+        self.control.sign_for(vehicle.direction) != Some(Sign::Stop) || vehicle.kind == VehicleKind::Emergency ||
+        (vehicle.is_standing() && vehicle.distance_to_stop_line() <= STOP_LINE_TOLERANCE)
+    }
+
+    // Grants each waiting request whose cells are free and whose way is clear,
+    // unless a request with the right of way over it wants the cells too, or the
+    // driver is turning left and does not like the gap in the traffic coming
+    fn grant(&mut self, vehicles: &[Vehicle]) {
+        for i in 0..self.reservations.len() {
+            if !self.reservations[i].granted && self.blockers(i).next().is_none() &&
+               self.in_the_way(i, vehicles).next().is_none() && !self.gap_too_short(i, vehicles) {
+                self.reservations[i].granted = true;
+                self.grants += 1;
+            }
+        }
+    }
+
+    // Whether the request at `a` has the right of way over the one at `b`. Under
+    // signals this follows the grant policy. Otherwise emergency vehicles go
+    // first, then the major road, then whoever stopped first at a stop sign,
+    // then traffic from the right. Opposing movements go by turn, then in order
+    // of request
+    fn goes_before(&self, a: usize, b: usize) -> bool {
+        let (first, second) = (&self.reservations[a], &self.reservations[b]);
+        let by_turn = (first.turn.precedence(), first.requested_at, a) < (second.turn.precedence(), second.requested_at, b);
+        let by_right = if first.direction == control::from_right(second.direction) {
+            true
+        } else if second.direction == control::from_right(first.direction) {
+            false
+        } else {
+            by_turn
+        };
+
+        match self.control {
+            IntersectionControl::Signalized => {
+                let rank = |reservation: &Reservation, i: usize| {
+                    let rank = match self.policy {
+                        GrantPolicy::FirstComeFirstServed => (false, 0),
+                        GrantPolicy::Priority => (!reservation.emergency, reservation.turn.precedence()),
+                    };
+                    (rank, reservation.requested_at, i)
+                };
+                rank(first, a) < rank(second, b)
+            },
+            _ if first.emergency != second.emergency => first.emergency,
+            IntersectionControl::TwoWayStop { .. } if self.control.is_major(first.direction) != self.control.is_major(second.direction) =>
+                self.control.is_major(first.direction),
+            IntersectionControl::TwoWayStop { .. } if self.control.is_major(first.direction) => by_turn,
+            IntersectionControl::AllWayStop | IntersectionControl::TwoWayStop { .. } => {
+                (first.requested_at, !by_right) < (second.requested_at, by_right)
+            },
+            IntersectionControl::YieldToRight => by_right,
+        }
+    }
+
+    // Whether a vehicle yet to ask for cells will reach the box on a movement
+    // crossing the left turn at `i` sooner than its driver's critical gap. Traffic
+    // that will stop first, for a light or a stop sign, leaves a gap of any length
+    fn gap_too_short(&self, i: usize, vehicles: &[Vehicle]) -> bool {
+        let reservation = &self.reservations[i];
+        if reservation.turn != Turn::Left || reservation.emergency {
+            return false;
+        }
+        vehicles.iter()
+            .filter(|other| {
+                other.direction != reservation.direction && !self.has_request(other.id) && !other.turn_executed &&
+                !other.is_standing() && other.stop_reason != StopReason::TrafficLight &&
+                self.control.sign_for(other.direction) != Some(Sign::Stop)
+            })
+            .filter(|other| (0.0..reservation.critical_gap * other.current_speed).contains(&other.distance_to_box()))
+            .any(|other| Reservation::new(other, 0).cells() & reservation.cells() != 0)
+    }
+
+    // Requests keeping the one at `i` from being granted: those holding cells it
+    // wants, and those with the right of way over it that want them too
+    fn blockers(&self, i: usize) -> impl Iterator<Item = usize> + '_ {
+        let reservation = &self.reservations[i];
+        (0..self.reservations.len()).filter(move |&j| {
+            let other = &self.reservations[j];
+            j != i && reservation.conflicts_with(other) && (other.granted || self.goes_before(j, i))
+        })
+    }
+
+    // Vehicles going other ways outside the box, coming up to it or on their way
+    // out, where the request at `i` would run into them. A long vehicle cuts
+    // across the corner of its turn, beyond the cells of the box and up to the
+    // stop lines of the other approaches
+    fn in_the_way<'a>(&'a self, i: usize, vehicles: &'a [Vehicle]) -> impl Iterator<Item = u64> + 'a {
+        let reservation = &self.reservations[i];
+        vehicles.iter()
+            .filter(move |other| other.direction != reservation.direction && !other.in_box())
+            .filter(move |other| {
+                let reach = match other.room_before_crossing {
+                    _ if other.turn_executed => 0.0,
+                    Some(room) => room.max(other.braking_distance()),
+                    None => other.distance_to_stop_line(),
+                };
+                let body = stretched(other.body(), reach.max(0.0));
+                reservation.sweep().any(|pose| pose.overlaps(&body))
+            })
+            .map(|other| other.id)
+    }
+
+    // How far a vehicle coming up to its stop line can go before it runs into
+    // where a request from another approach passes outside the box. One already
+    // in the way is let go first instead
+    fn room_before_crossing(&self, vehicle: &Vehicle) -> Option<f32> {
+        let reach = vehicle.distance_to_stop_line();
+        if vehicle.turn_executed || vehicle.in_box() || self.is_granted(vehicle.id) || reach <= 0.0 {
+            return None;
+        }
+        let body = vehicle.body();
+        let sweeps: Vec<Body> = self.reservations.iter()
+            .filter(|reservation| reservation.direction != vehicle.direction)
+            .flat_map(|reservation| reservation.sweep())
+            .collect();
+        if sweeps.iter().any(|pose| pose.overlaps(&body)) {
+            return None;
+        }
+
+        let (sin, cos) = body.heading.sin_cos();
+        let along = |(x, y): (f32, f32)| x * cos + y * sin;
+        let front = along(body.center) + body.length / 2.0;
+        let approach = stretched(body, reach);
+        sweeps.iter()
+            .filter(|pose| pose.overlaps(&approach))
+            .flat_map(|pose| pose.corners())
+            .map(|corner| (along(corner) - front).max(0.0))
+            .min_by(f32::total_cmp)
+    }
+
+    // Who waits on whom among the vehicles standing still: a vehicle waiting for
+    // its cells waits on every request blocking it and every vehicle in its way,
+    // a vehicle queued in its lane on the one ahead, and a vehicle stalled for no
+    // reason it knows of on any vehicle it is up against
+    fn wait_for_graph(&self, vehicles: &[Vehicle]) -> WaitForGraph {
+        let standing = |id: u64| vehicles.iter().any(|vehicle| vehicle.id == id && vehicle.is_standing());
+        let mut graph = WaitForGraph::default();
+
+        for (i, reservation) in self.reservations.iter().enumerate() {
+            let waiter = reservation.vehicle;
+            if reservation.granted || !standing(waiter) {
+                continue;
+            }
+            for j in self.blockers(i) {
+                graph.add(waiter, self.reservations[j].vehicle);
+            }
+            for other in self.in_the_way(i, vehicles) {
+                graph.add(waiter, other);
+            }
+        }
+
+        for vehicle in vehicles {
+            if vehicle.stop_reason == StopReason::VehicleAhead && standing(vehicle.id) &&
+               let Some(leader) = vehicle.queued_behind {
+                graph.add(vehicle.id, leader);
+            }
+            // Standing with no stop or leader to account for it, as when up against
+            // a vehicle it has run into, it waits on whatever it is touching
+            if vehicle.stop_reason == StopReason::None && vehicle.leader.is_none() && vehicle.is_standing() {
+                let reach = vehicle.body().grown(STALL_CLEARANCE);
+                for other in vehicles.iter().filter(|other| other.id != vehicle.id && other.body().overlaps(&reach)) {
+                    graph.add(vehicle.id, other.id);
+                }
+            }
+        }
+        graph
+    }
+
+    // Breaks a cycle of waiting vehicles by granting the oldest request in it
+    // that only waits on requests not yet granted. While every request in the
+    // cycle also waits on a grant, the cycle is left until the grant clears. A
+    // cycle through a vehicle already holding its grant cannot be broken this way
+    fn resolve_deadlocks(&mut self, vehicles: &[Vehicle], tick: u64) {
+        let Some(cycle) = self.wait_for_graph(vehicles).find_cycle() else {
+            self.stalled.clear();
+            return;
+        };
+
+        let in_cycle = |reservation: &Reservation| cycle.contains(&reservation.vehicle);
+        if self.reservations.iter().any(|reservation| reservation.granted && in_cycle(reservation)) {
+            if cycle != self.stalled {
+                self.deadlocks.push(DeadlockEvent { tick, vehicles: cycle.clone(), released: None });
+                self.stalled = cycle;
+            }
+            return;
+        }
+
+        let oldest = (0..self.reservations.len())
+            .filter(|&i| in_cycle(&self.reservations[i]))
+            .filter(|&i| self.blockers(i).all(|j| !self.reservations[j].granted) && self.in_the_way(i, vehicles).next().is_none())
+            .min_by_key(|&i| (self.reservations[i].requested_at, self.reservations[i].vehicle));
+        if let Some(i) = oldest {
+            self.reservations[i].granted = true;
+            self.grants += 1;
+            // A cycle already logged while it stood stalled is marked as broken
+            // rather than logged again
+            let released = Some(self.reservations[i].vehicle);
+            let logged = self.deadlocks.iter_mut().rev()
+                .find(|event| cycle == self.stalled && event.released.is_none() && event.vehicles == cycle);
+            match logged {
+                Some(event) => event.released = released,
+                None => self.deadlocks.push(DeadlockEvent { tick, vehicles: cycle, released }),
+            }
+            self.stalled.clear();
+        }
+    }
+}
+
+impl Reservation {
+    fn new(vehicle: &Vehicle, tick: u64) -> Self {
+        let poses = vehicle.route_through_box(ROUTE_STEP);
+        let mut ahead: Vec<Cells> = poses.iter().map(|pose| cells_under(&pose.grown(CELL_MARGIN))).collect();
+        for i in (1..ahead.len()).rev() {
+            ahead[i - 1] |= ahead[i];
+        }
+        Reservation {
+            vehicle: vehicle.id,
+            direction: vehicle.direction,
+            turn: vehicle.turn,
+            lateral: vehicle.lateral_center(),
+            emergency: vehicle.kind == VehicleKind::Emergency,
+            critical_gap: vehicle.driver.critical_gap,
+            requested_at: tick,
+            granted: false,
+            route: poses,
+            ahead,
+            progress: 0,
+        }
+    }
+
+    // Poses still ahead
+    fn sweep(&self) -> impl Iterator<Item = Body> + '_ {
+        self.route[self.progress..].iter().copied()
+    }
+
+    // Cells still ahead of, or under, the vehicle
+    fn cells(&self) -> Cells {
+        self.ahead.get(self.progress).copied().unwrap_or(0)
+    }
+
+    fn follow(&mut self, vehicle: &Vehicle) {
+        let center = vehicle.center();
+        while self.progress + 1 < self.route.len() &&
+              distance(self.route[self.progress + 1].center, center) <= distance(self.route[self.progress].center, center) {
+            self.progress += 1;
+        }
+    }
+
+    fn fits(&self, vehicle: &Vehicle) -> bool {
+        vehicle.direction == self.direction && vehicle.turn == self.turn &&
+        (vehicle.lateral_center() - self.lateral).abs() < LATERAL_TOLERANCE
+    }
+
+    fn same_movement(&self, other: &Reservation) -> bool {
+        self.direction == other.direction && self.turn == other.turn &&
+        (self.lateral - other.lateral).abs() < LATERAL_TOLERANCE
+    }
+
+    fn conflicts_with(&self, other: &Reservation) -> bool {
+        self.cells() & other.cells() != 0 && !self.same_movement(other)
+    }
+}
+
+// Whether a vehicle outside the box is coming up to it and free to go on, not
+// held by the light, waiting to change lanes or pulled over for an emergency
+// vehicle
+fn wants_to_cross(vehicle: &Vehicle) -> bool {
+    vehicle.needs_reservation() && !matches!(vehicle.stop_reason, StopReason::TrafficLight | StopReason::LaneChange) &&
+    !vehicle.changing_lanes() && !vehicle.yielding_to_emergency
+}
+
+// The body stretched `reach` metres forward, over the road it may yet take up
+fn stretched(body: Body, reach: f32) -> Body {
+    let (sin, cos) = body.heading.sin_cos();
+    Body {
+        center: (body.center.0 + reach / 2.0 * cos, body.center.1 + reach / 2.0 * sin),
+        length: body.length + reach,
+        ..body
+    }
+}
+
+fn cells_under(body: &Body) -> Cells {
+    let (left, top) = (CENTER_X - ROAD_WIDTH / 2.0, CENTER_Y - ROAD_WIDTH / 2.0);
+    let corners = body.corners();
+    let span = |values: [f32; 4], origin: f32| {
+        let min = values.iter().copied().fold(f32::MAX, f32::min);
+        let max = values.iter().copied().fold(f32::MIN, f32::max);
+        // Cells from the one under `min` up to the one under `max`, within the grid
+        let first = ((min - origin) / CELL_SIZE).floor().max(0.0) as usize;
+        let end = ((max - origin) / CELL_SIZE + 1.0).floor().clamp(0.0, GRID as f32) as usize;
+        first..end
+    };
+
+    let mut cells = 0;
+    for row in span(corners.map(|c| c.1), top) {
+        for column in span(corners.map(|c| c.0), left) {
+            let cell = Body {
+                center: (left + (column as f32 + 0.5) * CELL_SIZE, top + (row as f32 + 0.5) * CELL_SIZE),
+                heading: 0.0,
+                length: CELL_SIZE,
+                width: CELL_SIZE,
+            };
+            if body.overlaps(&cell) {
+                cells |= 1 << (row * GRID + column);
+            }
+        }
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vehicle::test_vehicle;
+
+    // A car standing `distance` short of its stop line
+    fn car(id: u64, direction: Direction, turn: Turn, distance: f32) -> Vehicle {
+        let mut vehicle = test_vehicle(direction, turn, distance);
+        vehicle.id = id;
+        vehicle.current_speed = 0.0;
+        vehicle
+    }
+
+    fn update(manager: &mut ReservationManager, vehicles: &mut [Vehicle], tick: u64) -> Vec<bool> {
+        manager.update(vehicles, IntersectionControl::Signalized, tick);
+        vehicles.iter().map(|vehicle| vehicle.reservation_granted).collect()
+    }
+
+    fn held_cells(manager: &ReservationManager) -> usize {
+        (0..GRID).flat_map(|row| (0..GRID).map(move |column| (row, column)))
+            .filter(|&(row, column)| manager.is_held(row, column))
+            .count()
+    }
+
+    #[test]
+    fn crossing_movements_never_hold_the_same_cells() {
+        let mut manager = ReservationManager::new(GrantPolicy::Priority);
+        let mut vehicles = [car(1, Direction::North, Turn::Straight, 0.5), car(2, Direction::East, Turn::Straight, 1.0)];
+        assert_eq!(update(&mut manager, &mut vehicles, 0), [true, false]);
+        assert_eq!(manager.grants, 1);
+        assert!(held_cells(&manager) > 0);
+
+        // Once the first has gone its cells are free for the second
+        let mut vehicles = [vehicles[1].clone()];
+        assert_eq!(update(&mut manager, &mut vehicles, 1), [true]);
+        assert_eq!(manager.grants, 2);
+    }
+
+    #[test]
+    fn cells_are_given_back_as_the_body_leaves_them() {
+        let mut manager = ReservationManager::new(GrantPolicy::Priority);
+        let mut vehicles = [car(1, Direction::North, Turn::Straight, 0.5), car(2, Direction::East, Turn::Straight, 1.0)];
+        assert_eq!(update(&mut manager, &mut vehicles, 0), [true, false]);
+        let cells_of = |manager: &ReservationManager, id: u64| manager.reservations.iter()
+            .find(|reservation| reservation.vehicle == id)
+            .map_or(0, |reservation| reservation.cells().count_ones());
+        let before = cells_of(&manager, 1);
+
+        // Still in the box, but past the eastbound lane
+        vehicles[0].y = CENTER_Y - 2.0 - vehicles[0].height;
+        assert!(vehicles[0].in_box());
+        assert_eq!(update(&mut manager, &mut vehicles, 1), [true, true]);
+        assert!(cells_of(&manager, 1) < before);
+    }
+
+    #[test]
+    fn followers_on_the_same_movement_ask_once_the_leader_holds_its_grant() {
+        let mut manager = ReservationManager::new(GrantPolicy::Priority);
+        let mut vehicles = [car(1, Direction::North, Turn::Straight, 0.5), car(2, Direction::North, Turn::Straight, 6.0)];
+        assert_eq!(update(&mut manager, &mut vehicles, 0), [true, false]);
+        assert_eq!(update(&mut manager, &mut vehicles, 1), [true, true]);
+    }
+
+    #[test]
+    fn grant_policy_decides_between_requests_made_together() {
+        let vehicles = || {
+            let mut emergency = car(2, Direction::East, Turn::Straight, 1.0);
+            emergency.kind = VehicleKind::Emergency;
+            [car(1, Direction::North, Turn::Straight, 0.5), emergency]
+        };
+        let mut first_come = ReservationManager::new(GrantPolicy::FirstComeFirstServed);
+        assert_eq!(update(&mut first_come, &mut vehicles(), 0), [true, false]);
+        let mut priority = ReservationManager::new(GrantPolicy::Priority);
+        assert_eq!(update(&mut priority, &mut vehicles(), 0), [false, true]);
+    }
+
+    #[test]
+    fn parses_grant_policies() {
+        let name = |s: &str| s.parse::<GrantPolicy>().map(GrantPolicy::name);
+        assert_eq!(name("fcfs"), Ok("fcfs"));
+        assert_eq!(name("priority"), Ok("priority"));
+        assert_eq!(name("random"), Err("expected fcfs or priority".to_string()));
+    }
+}
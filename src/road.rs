@@ -1,25 +1,129 @@
 // road.rs
 use sdl2::pixels::Color;
-use sdl2::rect::Rect;
+use sdl2::rect::{Point, Rect};
 use sdl2::render::Canvas;
 use sdl2::video::Window;
-use crate::window::{WINDOW_WIDTH, WINDOW_HEIGHT, CENTER_X, CENTER_Y};
-use crate::traffic_light::ROAD_WIDTH;
+use traffic_simulation::simulation::{CENTER_X, CENTER_Y, WORLD_WIDTH, WORLD_HEIGHT};
+use traffic_simulation::control::{IntersectionControl, Sign};
+use traffic_simulation::detector::Detector;
+use traffic_simulation::lanes::{Approach, RoadLayout};
+use traffic_simulation::pedestrian::{Leg, Pedestrian, PedestrianSignal, CROSSWALK_WIDTH, STOP_LINE_OFFSET};
+use traffic_simulation::reservation::{self, ReservationManager};
+use traffic_simulation::traffic_light::{SignalMode, TrafficLightSystem, TrafficLightState, ROAD_WIDTH};
+use traffic_simulation::vehicle::{Vehicle, VehicleKind, Direction, Turn, LANE_WIDTH};
+use crate::window::{WINDOW_WIDTH, WINDOW_HEIGHT, PIXELS_PER_METRE};
 
-pub fn draw_intersection(canvas: &mut Canvas<Window>) -> Result<(), String> {
+// Sizes below are in screen pixels; world positions, in metres, go through `px`
+const TRAFFIC_LIGHT_DISTANCE: i32 = 20; // Distance from road edge
+const LIGHT_POLE_WIDTH: u32 = 10;
+const LIGHT_POLE_HEIGHT: u32 = 30;
+const LIGHT_HEAD_WIDTH: u32 = 20;
+const LIGHT_HEAD_HEIGHT: u32 = 40;
+const LIGHT_RADIUS: i32 = 5;
+const ARROW_BOX_SIZE: u32 = 16;
+const ARROW_LENGTH: i32 = 5;
+// Lane arrows sit this far upstream of the stop line
+const LANE_ARROW_SETBACK: i32 = 30;
+const LANE_ARROW_LENGTH: i32 = 12;
+const LIGHT_BAR_DEPTH: u32 = 6;
+const ZEBRA_STRIPE: i32 = 6;
+const PEDESTRIAN_SIGNAL_SIZE: u32 = 8;
+const PEDESTRIAN_RADIUS: i32 = 3;
+// Flashing lamps and DON'T WALK toggle at this many ticks
+const FLASH_TICKS: u64 = 30;
+// Signs stand beside the kerb, this far in metres out from the road and back
+// from the stop line
+const SIGN_KERB_OFFSET: f32 = 1.5;
+const SIGN_SETBACK: f32 = 2.0;
+const SIGN_RADIUS: i32 = 9;
+
+// World-to-screen transform: metres to whole pixels
+fn px(metres: f32) -> i32 {
+    (metres * PIXELS_PER_METRE).round() as i32
+}
+
+pub fn draw_intersection(canvas: &mut Canvas<Window>, layout: &RoadLayout, detectors: &[Detector]) -> Result<(), String> {
     // Draw horizontal road
     canvas.set_draw_color(Color::RGB(50, 50, 50)); // Dark gray for road
-    canvas.fill_rect(Rect::new(0, CENTER_Y - ROAD_WIDTH as i32 / 2, WINDOW_WIDTH, ROAD_WIDTH))?;
+    canvas.fill_rect(Rect::new(0, px(CENTER_Y - ROAD_WIDTH / 2.0), WINDOW_WIDTH, px(ROAD_WIDTH) as u32))?;
     
     // Draw vertical road
-    canvas.fill_rect(Rect::new(CENTER_X - ROAD_WIDTH as i32 / 2, 0, ROAD_WIDTH, WINDOW_HEIGHT))?;
+    canvas.fill_rect(Rect::new(px(CENTER_X - ROAD_WIDTH / 2.0), 0, px(ROAD_WIDTH) as u32, WINDOW_HEIGHT))?;
     
     // Draw lane markings
     draw_road_markings(canvas)?;
+    draw_crosswalks(canvas)?;
+    for approach in &layout.approaches {
+        draw_lane_lines(canvas, approach)?;
+        draw_lane_arrows(canvas, approach)?;
+    }
+    draw_detectors(canvas, layout, detectors)?;
+    
+    Ok(())
+}
+
+// Detectors span every lane of their approach
+fn draw_detectors(canvas: &mut Canvas<Window>, layout: &RoadLayout, detectors: &[Detector]) -> Result<(), String> {
+    for detector in detectors {
+        let spec = detector.spec;
+        let centers = layout.approach(spec.direction).lanes.iter().map(|lane| lane.center);
+        let lane_edge = px(centers.clone().fold(f32::MAX, f32::min) - LANE_WIDTH / 2.0) + 2;
+        let span = (px(centers.fold(f32::MIN, f32::max) + LANE_WIDTH / 2.0) - 2 - lane_edge) as u32;
+        let length = px(spec.length) as u32;
+        let rect = match spec.direction {
+            Direction::North => Rect::new(
+                lane_edge,
+                px(CENTER_Y + STOP_LINE_OFFSET + spec.distance),
+                span,
+                length
+            ),
+            Direction::South => Rect::new(
+                lane_edge,
+                px(CENTER_Y - STOP_LINE_OFFSET - spec.distance - spec.length),
+                span,
+                length
+            ),
+            Direction::East => Rect::new(
+                px(CENTER_X - STOP_LINE_OFFSET - spec.distance - spec.length),
+                lane_edge,
+                length,
+                span
+            ),
+            Direction::West => Rect::new(
+                px(CENTER_X + STOP_LINE_OFFSET + spec.distance),
+                lane_edge,
+                length,
+                span
+            ),
+        };
+        
+        // Occupied loops are filled, idle loops drawn as an outline
+        if detector.occupied {
+            canvas.set_draw_color(Color::RGB(0, 200, 255));
+            canvas.fill_rect(rect)?;
+        } else {
+            canvas.set_draw_color(Color::RGB(0, 120, 160));
+            canvas.draw_rect(rect)?;
+        }
+    }
     
     Ok(())
 }
 
+// Cells of the box held by granted reservations, outlined
+pub fn draw_reservations(canvas: &mut Canvas<Window>, reservations: &ReservationManager) -> Result<(), String> {
+    canvas.set_draw_color(Color::RGB(200, 120, 0));
+    for row in 0..reservation::GRID {
+        for column in 0..reservation::GRID {
+            if reservations.is_held(row, column) {
+                let (left, top, size) = ReservationManager::cell_bounds(row, column);
+                canvas.draw_rect(Rect::new(px(left) + 1, px(top) + 1, px(size) as u32 - 2, px(size) as u32 - 2))?;
+            }
+        }
+    }
+    Ok(())
+}
+
 fn draw_road_markings(canvas: &mut Canvas<Window>) -> Result<(), String> {
     canvas.set_draw_color(Color::RGB(255, 255, 0)); // Yellow for road markings
     
@@ -32,7 +136,7 @@ fn draw_road_markings(canvas: &mut Canvas<Window>) -> Result<(), String> {
         let x = i * (dash_length + gap_length) as i32;
         canvas.fill_rect(Rect::new(
             x, 
-            CENTER_Y - 1, // Center line thickness of 2
+            px(CENTER_Y) - 1, // Center line thickness of 2
             dash_length, 
             2
         ))?;
@@ -44,7 +148,7 @@ fn draw_road_markings(canvas: &mut Canvas<Window>) -> Result<(), String> {
     for i in 0..total_dashes_vert {
         let y = i * (dash_length + gap_length) as i32;
         canvas.fill_rect(Rect::new(
-            CENTER_X - 1, // Center line thickness of 2
+            px(CENTER_X) - 1, // Center line thickness of 2
             y, 
             2, 
             dash_length
@@ -54,37 +158,515 @@ fn draw_road_markings(canvas: &mut Canvas<Window>) -> Result<(), String> {
     // Draw stop lines at intersection
     canvas.set_draw_color(Color::RGB(255, 255, 255)); // White for stop lines
     
+    let road_width = px(ROAD_WIDTH) as u32;
+    
     // North stop line
     canvas.fill_rect(Rect::new(
-        CENTER_X - ROAD_WIDTH as i32 / 2,
-        CENTER_Y - ROAD_WIDTH as i32 / 2 - 5,
-        ROAD_WIDTH,
+        px(CENTER_X - ROAD_WIDTH / 2.0),
+        px(CENTER_Y - STOP_LINE_OFFSET) - 3,
+        road_width,
         3
     ))?;
     
     // South stop line
     canvas.fill_rect(Rect::new(
-        CENTER_X - ROAD_WIDTH as i32 / 2,
-        CENTER_Y + ROAD_WIDTH as i32 / 2 + 2,
-        ROAD_WIDTH,
+        px(CENTER_X - ROAD_WIDTH / 2.0),
+        px(CENTER_Y + STOP_LINE_OFFSET),
+        road_width,
         3
     ))?;
     
     // East stop line
     canvas.fill_rect(Rect::new(
-        CENTER_X - ROAD_WIDTH as i32 / 2 - 5,
-        CENTER_Y - ROAD_WIDTH as i32 / 2,
+        px(CENTER_X - STOP_LINE_OFFSET) - 3,
+        px(CENTER_Y - ROAD_WIDTH / 2.0),
         3,
-        ROAD_WIDTH
+        road_width
     ))?;
     
     // West stop line
     canvas.fill_rect(Rect::new(
-        CENTER_X + ROAD_WIDTH as i32 / 2 + 2,
-        CENTER_Y - ROAD_WIDTH as i32 / 2,
+        px(CENTER_X + STOP_LINE_OFFSET),
+        px(CENTER_Y - ROAD_WIDTH / 2.0),
         3,
-        ROAD_WIDTH
+        road_width
     ))?;
     
     Ok(())
-}
\ No newline at end of file
+}
+
+// Converts a point given relative to the stop line of the approach travelling in
+// `direction` (`along` pixels upstream, `left` pixels toward the driver's left)
+// to the screen
+fn approach_point(direction: Direction, along: i32, left: i32) -> Point {
+    let (center_x, center_y, stop_line) = (px(CENTER_X), px(CENTER_Y), px(STOP_LINE_OFFSET));
+    match direction {
+        Direction::North => Point::new(center_x - left, center_y + stop_line + along),
+        Direction::South => Point::new(center_x + left, center_y - stop_line - along),
+        Direction::East => Point::new(center_x - stop_line - along, center_y - left),
+        Direction::West => Point::new(center_x + stop_line + along, center_y + left),
+    }
+}
+
+// Screen offset of a cross-road coordinate toward the driver's left, from the centre line
+fn left_of_centre(direction: Direction, coordinate: f32) -> i32 {
+    px(match direction {
+        Direction::North => CENTER_X - coordinate,
+        Direction::South => coordinate - CENTER_X,
+        Direction::East => CENTER_Y - coordinate,
+        Direction::West => coordinate - CENTER_Y,
+    })
+}
+
+// Dashed white lines between lanes; the line beside a turn pocket is solid
+// along the pocket and absent upstream of it
+fn draw_lane_lines(canvas: &mut Canvas<Window>, approach: &Approach) -> Result<(), String> {
+    canvas.set_draw_color(Color::RGB(255, 255, 255));
+    let direction = approach.direction;
+    let road_length = match direction {
+        Direction::North | Direction::South => WINDOW_HEIGHT as i32 / 2,
+        Direction::East | Direction::West => WINDOW_WIDTH as i32 / 2,
+    };
+    
+    for pair in approach.lanes.windows(2) {
+        let left = (left_of_centre(direction, pair[0].center) + left_of_centre(direction, pair[1].center)) / 2;
+        match pair[0].pocket.or(pair[1].pocket) {
+            Some(pocket) => {
+                canvas.draw_line(approach_point(direction, 0, left), approach_point(direction, px(pocket), left))?;
+            },
+            None => {
+                for start in (0..road_length).step_by(20) {
+                    canvas.draw_line(
+                        approach_point(direction, start, left),
+                        approach_point(direction, start + 10, left)
+                    )?;
+                }
+            },
+        }
+    }
+    
+    Ok(())
+}
+
+// Arrow for each turn a lane allows, painted just upstream of the stop line
+fn draw_lane_arrows(canvas: &mut Canvas<Window>, approach: &Approach) -> Result<(), String> {
+    canvas.set_draw_color(Color::RGB(255, 255, 255));
+    let direction = approach.direction;
+    
+    for lane in &approach.lanes {
+        let left = left_of_centre(direction, lane.center);
+        let tail = LANE_ARROW_SETBACK + LANE_ARROW_LENGTH;
+        let bend = LANE_ARROW_SETBACK + LANE_ARROW_LENGTH / 3;
+        let point = |along: i32, across: i32| approach_point(direction, along, left + across);
+        
+        canvas.draw_line(point(tail, 0), point(bend, 0))?;
+        for &turn in lane.lane_use.turns() {
+            let (tip, barbs) = match turn {
+                Turn::Straight => (point(LANE_ARROW_SETBACK, 0), [point(LANE_ARROW_SETBACK + 3, -3), point(LANE_ARROW_SETBACK + 3, 3)]),
+                Turn::Left => (point(bend, 6), [point(bend - 3, 3), point(bend + 3, 3)]),
+                Turn::Right => (point(bend, -6), [point(bend - 3, -3), point(bend + 3, -3)]),
+            };
+            canvas.draw_line(point(bend, 0), tip)?;
+            for barb in barbs {
+                canvas.draw_line(tip, barb)?;
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+// Zebra stripes between each stop line and the intersection box
+fn draw_crosswalks(canvas: &mut Canvas<Window>) -> Result<(), String> {
+    canvas.set_draw_color(Color::RGB(230, 230, 230));
+    
+    for leg in Leg::ALL {
+        let near_edge = px(leg.crosswalk_center() - CROSSWALK_WIDTH / 2.0);
+        let depth = px(CROSSWALK_WIDTH) as u32;
+        for offset in (0..px(ROAD_WIDTH)).step_by(2 * ZEBRA_STRIPE as usize) {
+            let stripe = match leg {
+                Leg::North | Leg::South => Rect::new(
+                    px(CENTER_X - ROAD_WIDTH / 2.0) + offset,
+                    near_edge,
+                    ZEBRA_STRIPE as u32,
+                    depth
+                ),
+                Leg::East | Leg::West => Rect::new(
+                    near_edge,
+                    px(CENTER_Y - ROAD_WIDTH / 2.0) + offset,
+                    depth,
+                    ZEBRA_STRIPE as u32
+                ),
+            };
+            canvas.fill_rect(stripe)?;
+        }
+    }
+    
+    Ok(())
+}
+
+/// Pedestrian signal heads at both ends of every crosswalk: white for WALK,
+/// orange for DON'T WALK, blinking orange for flashing DON'T WALK, and unlit
+/// without signal control.
+pub fn draw_pedestrian_signals(canvas: &mut Canvas<Window>, lights: &TrafficLightSystem, tick: u64) -> Result<(), String> {
+    let lit = |signal: PedestrianSignal| match signal {
+        PedestrianSignal::Walk => Some(Color::RGB(255, 255, 255)),
+        PedestrianSignal::DontWalk => Some(Color::RGB(255, 120, 0)),
+        PedestrianSignal::FlashingDontWalk =>
+            ((tick / FLASH_TICKS).is_multiple_of(2)).then_some(Color::RGB(255, 120, 0)),
+        PedestrianSignal::Dark => None,
+    };
+    let kerb = px(ROAD_WIDTH / 2.0) + 2;
+    let half = PEDESTRIAN_SIGNAL_SIZE as i32 / 2;
+    
+    for leg in Leg::ALL {
+        let center = px(leg.crosswalk_center());
+        for side in [-1, 1] {
+            let (x, y) = match leg {
+                Leg::North | Leg::South => (px(CENTER_X) + side * kerb + (side - 1) * half, center - half),
+                Leg::East | Leg::West => (center - half, px(CENTER_Y) + side * kerb + (side - 1) * half),
+            };
+            let rect = Rect::new(x, y, PEDESTRIAN_SIGNAL_SIZE, PEDESTRIAN_SIGNAL_SIZE);
+            canvas.set_draw_color(Color::RGB(20, 20, 20));
+            canvas.fill_rect(rect)?;
+            if let Some(color) = lit(lights.pedestrian_signal(leg)) {
+                canvas.set_draw_color(color);
+                canvas.fill_rect(Rect::new(x + 2, y + 2, PEDESTRIAN_SIGNAL_SIZE - 4, PEDESTRIAN_SIGNAL_SIZE - 4))?;
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+pub fn draw_pedestrian(canvas: &mut Canvas<Window>, pedestrian: &Pedestrian) -> Result<(), String> {
+    canvas.set_draw_color(Color::RGB(255, 220, 180));
+    draw_filled_circle(canvas, px(pedestrian.x), px(pedestrian.y), PEDESTRIAN_RADIUS)
+}
+
+/// Signal heads on every approach. Out of normal operation they flash, or stay
+/// dark if the signals have failed.
+pub fn draw_traffic_lights(canvas: &mut Canvas<Window>, lights: &TrafficLightSystem, tick: u64) -> Result<(), String> {
+    // Northbound light (facing south)
+    draw_traffic_light(
+        canvas,
+        px(CENTER_X) - LIGHT_HEAD_WIDTH as i32 / 2,
+        px(CENTER_Y - ROAD_WIDTH / 2.0) - TRAFFIC_LIGHT_DISTANCE - LIGHT_HEAD_HEIGHT as i32,
+        false, // horizontal
+        lights,
+        tick,
+        Direction::North
+    )?;
+    
+    // Southbound light (facing north)
+    draw_traffic_light(
+        canvas,
+        px(CENTER_X) - LIGHT_HEAD_WIDTH as i32 / 2,
+        px(CENTER_Y + ROAD_WIDTH / 2.0) + TRAFFIC_LIGHT_DISTANCE,
+        false, // horizontal
+        lights,
+        tick,
+        Direction::South
+    )?;
+    
+    // Eastbound light (facing west)
+    draw_traffic_light(
+        canvas,
+        px(CENTER_X + ROAD_WIDTH / 2.0) + TRAFFIC_LIGHT_DISTANCE,
+        px(CENTER_Y) - LIGHT_HEAD_WIDTH as i32 / 2,
+        true, // vertical
+        lights,
+        tick,
+        Direction::East
+    )?;
+    
+    // Westbound light (facing east)
+    draw_traffic_light(
+        canvas,
+        px(CENTER_X - ROAD_WIDTH / 2.0) - TRAFFIC_LIGHT_DISTANCE - LIGHT_HEAD_HEIGHT as i32,
+        px(CENTER_Y) - LIGHT_HEAD_WIDTH as i32 / 2,
+        true, // vertical
+        lights,
+        tick,
+        Direction::West
+    )?;
+    
+    Ok(())
+}
+
+/// Stop and yield signs at the approaches of an intersection without signals,
+/// on the kerb beside the lane they face.
+pub fn draw_signs(canvas: &mut Canvas<Window>, control: IntersectionControl) -> Result<(), String> {
+    let (kerb, back) = (ROAD_WIDTH / 2.0 + SIGN_KERB_OFFSET, STOP_LINE_OFFSET + SIGN_SETBACK);
+    for direction in Direction::ALL {
+        let Some(sign) = control.sign_for(direction) else {
+            continue;
+        };
+        let (x, y) = match direction {
+            Direction::North => (CENTER_X - kerb, CENTER_Y + back),
+            Direction::South => (CENTER_X + kerb, CENTER_Y - back),
+            Direction::East => (CENTER_X - back, CENTER_Y + kerb),
+            Direction::West => (CENTER_X + back, CENTER_Y - kerb),
+        };
+        match sign {
+            Sign::Stop => draw_stop_sign(canvas, px(x), px(y))?,
+            Sign::Yield => draw_yield_sign(canvas, px(x), px(y))?,
+        }
+    }
+    Ok(())
+}
+
+// Red disc with a white bar across it
+fn draw_stop_sign(canvas: &mut Canvas<Window>, x: i32, y: i32) -> Result<(), String> {
+    canvas.set_draw_color(Color::RGB(200, 0, 0));
+    draw_filled_circle(canvas, x, y, SIGN_RADIUS)?;
+    canvas.set_draw_color(Color::RGB(255, 255, 255));
+    canvas.fill_rect(Rect::new(x - SIGN_RADIUS / 2 - 1, y - 1, SIGN_RADIUS as u32 + 2, 3))
+}
+
+// White triangle with a red border, standing on its point
+fn draw_yield_sign(canvas: &mut Canvas<Window>, x: i32, y: i32) -> Result<(), String> {
+    let (top, bottom) = (y - SIGN_RADIUS * 2 / 3, y + SIGN_RADIUS);
+    for row in top..=bottom {
+        let half = SIGN_RADIUS * (bottom - row) / (bottom - top);
+        canvas.set_draw_color(Color::RGB(200, 0, 0));
+        canvas.draw_line(Point::new(x - half, row), Point::new(x + half, row))?;
+        if row > top + 2 && half > 3 {
+            canvas.set_draw_color(Color::RGB(255, 255, 255));
+            canvas.draw_line(Point::new(x - half + 3, row), Point::new(x + half - 3, row))?;
+        }
+    }
+    Ok(())
+}
+
+fn draw_traffic_light(
+    canvas: &mut Canvas<Window>,
+    x: i32,
+    y: i32,
+    vertical: bool,
+    lights: &TrafficLightSystem,
+    tick: u64,
+    direction: Direction
+) -> Result<(), String> {
+    let flash_on = (tick / FLASH_TICKS).is_multiple_of(2);
+    let state = match lights.mode {
+        SignalMode::Normal => Some(lights.state_for(direction)),
+        _ => lights.flashing_lamp(direction).filter(|_| flash_on),
+    };
+
+    // Draw pole
+    canvas.set_draw_color(Color::RGB(70, 70, 70));
+    let pole_rect = if vertical {
+        Rect::new(
+            x + LIGHT_HEAD_HEIGHT as i32 / 2 - LIGHT_POLE_WIDTH as i32 / 2,
+            y + LIGHT_HEAD_WIDTH as i32,
+            LIGHT_POLE_WIDTH,
+            LIGHT_POLE_HEIGHT
+        )
+    } else {
+        Rect::new(
+            x + LIGHT_HEAD_WIDTH as i32 / 2 - LIGHT_POLE_WIDTH as i32 / 2,
+            y - LIGHT_POLE_HEIGHT as i32,
+            LIGHT_POLE_WIDTH,
+            LIGHT_POLE_HEIGHT
+        )
+    };
+    canvas.fill_rect(pole_rect)?;
+    
+    // Draw light head
+    canvas.set_draw_color(Color::RGB(40, 40, 40));
+    let head_rect = Rect::new(x, y, 
+        if vertical { LIGHT_HEAD_HEIGHT } else { LIGHT_HEAD_WIDTH },
+        if vertical { LIGHT_HEAD_WIDTH } else { LIGHT_HEAD_HEIGHT }
+    );
+    canvas.fill_rect(head_rect)?;
+    canvas.set_draw_color(Color::RGB(20, 20, 20));
+    canvas.draw_rect(head_rect)?;
+    
+    // Lamps are spaced along the long side of the head: red, amber, green
+    let lamp_position = |slot: i32| if vertical {
+        // Vertical light (for east/west traffic)
+        (x + (2 * slot + 1) * LIGHT_HEAD_HEIGHT as i32 / 6, y + LIGHT_HEAD_WIDTH as i32 / 2)
+    } else {
+        // Horizontal light (for north/south traffic)
+        (x + LIGHT_HEAD_WIDTH as i32 / 2, y + (2 * slot + 1) * LIGHT_HEAD_HEIGHT as i32 / 6)
+    };
+    let lamps = [
+        (TrafficLightState::Red, Color::RGB(255, 0, 0), Color::RGB(80, 0, 0)),
+        (TrafficLightState::Amber, Color::RGB(255, 191, 0), Color::RGB(80, 60, 0)),
+        (TrafficLightState::Green, Color::RGB(0, 255, 0), Color::RGB(0, 80, 0)),
+    ];
+    
+    for (slot, (lamp_state, lit, unlit)) in lamps.into_iter().enumerate() {
+        let (lamp_x, lamp_y) = lamp_position(slot as i32);
+        canvas.set_draw_color(if state == Some(lamp_state) { lit } else { unlit });
+        draw_filled_circle(canvas, lamp_x, lamp_y, LIGHT_RADIUS)?;
+    }
+    
+    if lights.has_left_arrow(direction) {
+        // Arrow box sits beside the head, away from the pole
+        let (box_x, box_y) = if vertical {
+            (x + (LIGHT_HEAD_HEIGHT - ARROW_BOX_SIZE) as i32 / 2, y - ARROW_BOX_SIZE as i32 - 2)
+        } else {
+            (x + LIGHT_HEAD_WIDTH as i32 + 2, y + (LIGHT_HEAD_HEIGHT - ARROW_BOX_SIZE) as i32 / 2)
+        };
+        let arrow = if lights.mode == SignalMode::Normal { lights.arrow_for(direction) } else { None };
+        draw_left_arrow(canvas, box_x, box_y, direction, arrow)?;
+    }
+    
+    Ok(())
+}
+
+fn draw_left_arrow(
+    canvas: &mut Canvas<Window>,
+    x: i32,
+    y: i32,
+    direction: Direction,
+    arrow: Option<TrafficLightState>
+) -> Result<(), String> {
+    canvas.set_draw_color(Color::RGB(40, 40, 40));
+    canvas.fill_rect(Rect::new(x, y, ARROW_BOX_SIZE, ARROW_BOX_SIZE))?;
+    
+    canvas.set_draw_color(match arrow {
+        Some(TrafficLightState::Red) => Color::RGB(255, 0, 0),
+        Some(TrafficLightState::Amber) => Color::RGB(255, 191, 0),
+        Some(TrafficLightState::Green) => Color::RGB(0, 255, 0),
+        None => Color::RGB(70, 70, 70),
+    });
+    
+    // Screen direction of a left turn for traffic travelling in `direction`
+    let (dx, dy) = match direction {
+        Direction::North => (-1, 0),
+        Direction::South => (1, 0),
+        Direction::East => (0, -1),
+        Direction::West => (0, 1),
+    };
+    let center = Point::new(x + ARROW_BOX_SIZE as i32 / 2, y + ARROW_BOX_SIZE as i32 / 2);
+    let tip = center.offset(dx * ARROW_LENGTH, dy * ARROW_LENGTH);
+    let tail = center.offset(-dx * ARROW_LENGTH, -dy * ARROW_LENGTH);
+    let barb_base = center.offset(dx * (ARROW_LENGTH - 3), dy * (ARROW_LENGTH - 3));
+    
+    canvas.draw_line(tail, tip)?;
+    canvas.draw_line(tail.offset(dy, dx), tip.offset(dy, dx))?;
+    canvas.draw_line(tip, barb_base.offset(-dy * 3, -dx * 3))?;
+    canvas.draw_line(tip, barb_base.offset(dy * 3, dx * 3))?;
+    
+    Ok(())
+}
+
+fn draw_filled_circle(
+    canvas: &mut Canvas<Window>, 
+    x: i32, 
+    y: i32, 
+    radius: i32
+) -> Result<(), String> {
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            if dx*dx + dy*dy <= radius*radius {
+                canvas.draw_point((x + dx, y + dy))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn draw_vehicle(canvas: &mut Canvas<Window>, vehicle: &Vehicle) -> Result<(), String> {
+    if vehicle.x > -10.0 && vehicle.x < WORLD_WIDTH + 10.0 &&
+       vehicle.y > -10.0 && vehicle.y < WORLD_HEIGHT + 10.0 {
+        let (along, across) = (vehicle.body_length / 2.0, vehicle.body_width / 2.0);
+        let body = body_quad(vehicle, (-along, along), (-across, across));
+        match vehicle.kind {
+            VehicleKind::Emergency => draw_emergency_vehicle(canvas, vehicle, body)?,
+            kind => {
+                canvas.set_draw_color(vehicle_color(vehicle.turn));
+                fill_quad(canvas, body)?;
+                draw_class_marking(canvas, vehicle, kind)?;
+            },
+        }
+    }
+    Ok(())
+}
+
+// White body with a red and blue light bar across the middle
+fn draw_emergency_vehicle(canvas: &mut Canvas<Window>, vehicle: &Vehicle, body: [Point; 4]) -> Result<(), String> {
+    canvas.set_draw_color(Color::RGB(255, 255, 255));
+    fill_quad(canvas, body)?;
+
+    let bar = LIGHT_BAR_DEPTH as f32 / PIXELS_PER_METRE / 2.0;
+    let across = vehicle.body_width / 2.0;
+    canvas.set_draw_color(Color::RGB(255, 0, 0));
+    fill_quad(canvas, body_quad(vehicle, (-bar, bar), (-across, 0.0)))?;
+    canvas.set_draw_color(Color::RGB(0, 0, 255));
+    fill_quad(canvas, body_quad(vehicle, (-bar, bar), (0.0, across)))?;
+    Ok(())
+}
+
+// Marks that tell the kinds apart on top of the turn colour: a dark cab at the
+// front of a truck, a pale strip of windows along a bus and a rider on a
+// motorcycle or bicycle
+fn draw_class_marking(canvas: &mut Canvas<Window>, vehicle: &Vehicle, kind: VehicleKind) -> Result<(), String> {
+    let (along, across) = (vehicle.body_length / 2.0, vehicle.body_width / 2.0);
+    match kind {
+        VehicleKind::Truck => {
+            canvas.set_draw_color(Color::RGB(40, 40, 40));
+            fill_quad(canvas, body_quad(vehicle, (along - 2.0, along), (-across, across)))?;
+        },
+        VehicleKind::Bus => {
+            canvas.set_draw_color(Color::RGB(220, 230, 255));
+            fill_quad(canvas, body_quad(vehicle, (-along + 0.5, along - 0.5), (-across / 3.0, across / 3.0)))?;
+        },
+        VehicleKind::Motorcycle | VehicleKind::Bicycle => {
+            let (x, y) = vehicle.body_point(0.0, 0.0);
+            canvas.set_draw_color(Color::RGB(0, 0, 0));
+            draw_filled_circle(canvas, px(x), px(y), 2)?;
+        },
+        VehicleKind::Car | VehicleKind::Emergency => {},
+    }
+    Ok(())
+}
+
+// Screen corners of the part of the body between `along` and `across`, which
+// are in metres from its centre toward the front and the right
+fn body_quad(vehicle: &Vehicle, along: (f32, f32), across: (f32, f32)) -> [Point; 4] {
+    [(along.0, across.0), (along.1, across.0), (along.1, across.1), (along.0, across.1)]
+        .map(|(a, c)| {
+            let (x, y) = vehicle.body_point(a, c);
+            Point::new(px(x), px(y))
+        })
+}
+
+// Fills a convex quadrilateral one row of pixels at a time
+fn fill_quad(canvas: &mut Canvas<Window>, corners: [Point; 4]) -> Result<(), String> {
+    let top = corners.iter().map(|p| p.y()).min().unwrap_or(0);
+    let bottom = corners.iter().map(|p| p.y()).max().unwrap_or(0);
+    for y in top..=bottom {
+        let mut left = i32::MAX;
+        let mut right = i32::MIN;
+        for i in 0..corners.len() {
+            let (a, b) = (corners[i], corners[(i + 1) % corners.len()]);
+            if y < a.y().min(b.y()) || y > a.y().max(b.y()) {
+                continue;
+            }
+            let xs = if a.y() == b.y() {
+                (a.x(), b.x())
+            } else {
+                let x = a.x() + ((y - a.y()) as f32 * (b.x() - a.x()) as f32 / (b.y() - a.y()) as f32).round() as i32;
+                (x, x)
+            };
+            left = left.min(xs.0.min(xs.1));
+            right = right.max(xs.0.max(xs.1));
+        }
+        if left <= right {
+            canvas.draw_line(Point::new(left, y), Point::new(right, y))?;
+        }
+    }
+    Ok(())
+}
+
+fn vehicle_color(turn: Turn) -> Color {
+    match turn {
+        Turn::Left => Color::RGB(255, 0, 0),
+        Turn::Right => Color::RGB(0, 255, 0),
+        Turn::Straight => Color::RGB(0, 0, 255),
+    }
+}
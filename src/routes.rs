@@ -0,0 +1,250 @@
+// routes.rs
+// Where vehicles are going. Every vehicle is given a route when it spawns, from
+// the leg it enters by to the leg it leaves by, drawn from the turning shares of
+// an origin-destination matrix, and keeps it for the whole trip.
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use rand::Rng;
+use serde::Deserialize;
+use crate::clock::SimClock;
+use crate::pedestrian::Leg;
+use crate::signal_plan::describe_toml_error;
+use crate::vehicle::{Direction, Turn};
+use crate::weighted::Weighted;
+
+/// Legs a vehicle enters and leaves the intersection by.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Route {
+    pub origin: Leg,
+    pub destination: Leg,
+}
+
+impl Route {
+    /// Route of traffic travelling in `direction` that makes `turn`.
+    pub fn of(direction: Direction, turn: Turn) -> Self {
+        Route {
+            origin: Leg::entered_by(direction),
+            destination: Leg::exited_by(direction.turned(turn)),
+        }
+    }
+
+    /// Direction of travel on entering the intersection.
+    pub fn direction(self) -> Direction {
+        Direction::ALL.into_iter()
+            .find(|&direction| Leg::entered_by(direction) == self.origin)
+            .expect("every leg is entered by one direction")
+    }
+
+    /// Turn that takes traffic from the origin to the destination.
+    pub fn turn(self) -> Turn {
+        [Turn::Left, Turn::Straight, Turn::Right].into_iter()
+            .find(|&turn| Route::of(self.direction(), turn) == self)
+            .expect("routes never lead back to their origin")
+    }
+
+    pub fn describe(self) -> String {
+        format!("{}->{}", self.origin.name(), self.destination.name())
+    }
+}
+
+/// Share of the vehicles entering by each leg that are bound for each other leg.
+#[derive(Clone)]
+pub struct OdMatrix {
+    // Destinations of the vehicles entering by each leg, indexed like `Leg::ALL`
+    shares: [Weighted<Leg>; 4],
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OdFile {
+    origins: Vec<OriginFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OriginFile {
+    leg: Leg,
+    #[serde(default)]
+    north: f32,
+    #[serde(default)]
+    south: f32,
+    #[serde(default)]
+    east: f32,
+    #[serde(default)]
+    west: f32,
+}
+
+impl OriginFile {
+    // Shares indexed like `Leg::ALL`
+    fn shares(&self) -> [f32; 4] {
+        [self.north, self.south, self.east, self.west]
+    }
+}
+
+impl OdMatrix {
+    /// Draws the route of a vehicle entering the intersection travelling in `direction`.
+    pub fn pick(&self, direction: Direction, rng: &mut impl Rng) -> Route {
+        let origin = Leg::entered_by(direction);
+        Route { origin, destination: self.shares[index(origin)].pick(rng) }
+    }
+
+    /// Shares of the vehicles entering by `origin`, like `north=60,east=20,west=20`.
+    pub fn describe(&self, origin: Leg) -> String {
+        self.shares[index(origin)].describe(Leg::name)
+    }
+
+    /// Loads a matrix from a TOML file. Origins not listed keep equal shares.
+    /// Errors name the file and the offending field.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("od matrix {}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("od matrix {}: {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let file: OdFile = toml::from_str(text).map_err(|e| describe_toml_error(text, &e))?;
+
+        let mut matrix = Self::default();
+        for (i, origin) in file.origins.iter().enumerate() {
+            if let Some(j) = file.origins[..i].iter().position(|other| other.leg == origin.leg) {
+                return Err(format!("origins[{}].leg: duplicates origins[{}]", i, j));
+            }
+            let shares = origin.shares();
+            for (leg, weight) in Leg::ALL.into_iter().zip(shares) {
+                if !weight.is_finite() {
+                    return Err(format!("origins[{}].{}: must be a finite number", i, leg.name()));
+                }
+                if weight < 0.0 {
+                    return Err(format!("origins[{}].{}: must not be negative", i, leg.name()));
+                }
+                if leg == origin.leg && weight > 0.0 {
+                    return Err(format!("origins[{}].{}: vehicles cannot leave by the leg they entered", i, leg.name()));
+                }
+            }
+            matrix.shares[index(origin.leg)] = Weighted::new(Leg::ALL.into_iter().zip(shares))
+                .map_err(|e| format!("origins[{}]: {}", i, e))?;
+        }
+        Ok(matrix)
+    }
+}
+
+impl Default for OdMatrix {
+    /// Traffic from every leg split evenly between the other three.
+    fn default() -> Self {
+        OdMatrix {
+            shares: Leg::ALL.map(|origin| {
+                Weighted::new(Leg::ALL.into_iter().filter(|&destination| destination != origin).map(|destination| (destination, 1.0)))
+                    .expect("equal positive weights are valid")
+            }),
+        }
+    }
+}
+
+/// Totals for the vehicles given one route.
+#[derive(Clone, Copy, Default)]
+pub struct RouteTotals {
+    pub spawned: u64,
+    pub exited: u64,
+    // Sum of the ticks each vehicle that has exited took from spawn to exit
+    pub travel_ticks: u64,
+    // Sum over ticks of the number of these vehicles standing still
+    pub stopped_ticks: u64,
+    // Vehicles that reached the stop line in a lane not allowing their turn, and
+    // so left by another leg
+    pub missed: u64,
+}
+
+impl RouteTotals {
+    /// Mean time from spawn to exit of the vehicles that have exited.
+    pub fn average_travel_time(&self, clock: &SimClock) -> Duration {
+        clock.ticks_to_duration(self.travel_ticks).checked_div(self.exited.max(1) as u32).unwrap_or_default()
+    }
+
+    /// Mean time each vehicle spawned has spent standing still so far.
+    pub fn average_stopped_time(&self, clock: &SimClock) -> Duration {
+        clock.ticks_to_duration(self.stopped_ticks).checked_div(self.spawned.max(1) as u32).unwrap_or_default()
+    }
+}
+
+/// Totals kept for every route over a run.
+#[derive(Clone, Copy, Default)]
+pub struct RouteStats {
+    // Indexed like `Leg::ALL`, by origin and then by destination
+    totals: [[RouteTotals; 4]; 4],
+}
+
+impl RouteStats {
+    pub fn get(&self, route: Route) -> &RouteTotals {
+        &self.totals[index(route.origin)][index(route.destination)]
+    }
+
+    pub fn get_mut(&mut self, route: Route) -> &mut RouteTotals {
+        &mut self.totals[index(route.origin)][index(route.destination)]
+    }
+
+    /// Routes that any vehicle has been given, with their totals.
+    pub fn used(&self) -> impl Iterator<Item = (Route, &RouteTotals)> {
+        Leg::ALL.into_iter()
+            .flat_map(|origin| Leg::ALL.map(|destination| Route { origin, destination }))
+            .map(|route| (route, self.get(route)))
+            .filter(|(_, totals)| totals.spawned > 0)
+    }
+}
+
+fn index(leg: Leg) -> usize {
+    Leg::ALL.iter().position(|&other| other == leg).expect("every leg is listed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    fn parse_error(text: &str) -> String {
+        OdMatrix::parse(text).err().unwrap_or_default()
+    }
+
+    #[test]
+    fn origins_left_out_split_evenly() {
+        let matrix = OdMatrix::parse("[[origins]]\nleg = \"east\"\nwest = 80\nnorth = 20\n").expect("the matrix is valid");
+        assert_eq!(matrix.describe(Leg::East), "north=20,west=80");
+        assert_eq!(matrix.describe(Leg::North), "south=1,east=1,west=1");
+    }
+
+    #[test]
+    fn shipped_matrix_parses() {
+        let matrix = OdMatrix::parse(include_str!("../routes/major_road.toml")).expect("the shipped matrix is valid");
+        assert_eq!(matrix.describe(Leg::West), "north=10,south=10,east=80");
+    }
+
+    #[test]
+    fn rejects_bad_origins() {
+        assert_eq!(
+            parse_error("[[origins]]\nleg = \"east\"\neast = 1\nwest = 1\n"),
+            "origins[0].east: vehicles cannot leave by the leg they entered",
+        );
+        assert_eq!(parse_error("[[origins]]\nleg = \"east\"\nwest = -1\n"), "origins[0].west: must not be negative");
+        assert_eq!(parse_error("[[origins]]\nleg = \"east\"\nwest = inf\n"), "origins[0].west: must be a finite number");
+        assert_eq!(parse_error("[[origins]]\nleg = \"east\"\nnorth = nan\n"), "origins[0].north: must be a finite number");
+        assert_eq!(parse_error("[[origins]]\nleg = \"east\"\n"), "origins[0]: at least one weight must be positive");
+        assert_eq!(
+            parse_error("[[origins]]\nleg = \"east\"\nwest = 1\n[[origins]]\nleg = \"east\"\nnorth = 1\n"),
+            "origins[1].leg: duplicates origins[0]",
+        );
+        assert!(parse_error("[[origins]]\nleg = \"east\"\nwset = 1\n").contains("unknown field `wset`"));
+    }
+
+    #[test]
+    fn picks_only_listed_destinations() {
+        let matrix = OdMatrix::parse("[[origins]]\nleg = \"south\"\nnorth = 1\n").expect("the matrix is valid");
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..100 {
+            let route = matrix.pick(Direction::North, &mut rng);
+            assert_eq!(route, Route { origin: Leg::South, destination: Leg::North });
+            assert_eq!(route.turn(), Turn::Straight);
+        }
+    }
+}
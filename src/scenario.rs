@@ -0,0 +1,219 @@
+// scenario.rs
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use serde::Deserialize;
+use crate::pedestrian::Leg;
+use crate::signal_plan::{describe_toml_error, file_seconds};
+use crate::traffic_light::SignalMode;
+use crate::vehicle::{Direction, VehicleKind};
+
+/// Something a scenario script does to the running simulation.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ScenarioAction {
+    /// Spawns a vehicle at the entry of an approach, regardless of the spawn cooldown,
+    /// to appear once the entry is clear.
+    Spawn {
+        direction: Direction,
+        kind: VehicleKind,
+    },
+    /// Places a pedestrian at a random end of the crosswalk on the given leg.
+    SpawnPedestrian {
+        crosswalk: Leg,
+    },
+    /// Switches the signals to flashing, dark or back to normal operation.
+    SignalMode {
+        mode: SignalMode,
+    },
+}
+
+#[derive(Clone, Copy)]
+pub struct ScenarioEvent {
+    // Simulated time since the start of the run
+    pub at: Duration,
+    pub action: ScenarioAction,
+}
+
+/// Timed events replayed against a run, in order of their time.
+#[derive(Clone, Default)]
+pub struct Scenario {
+    pub events: Vec<ScenarioEvent>,
+    next_event: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioFile {
+    events: Vec<EventFile>,
+}
+
+// `at` is given in seconds. Each action takes only its own fields, checked once
+// the action is known.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EventFile {
+    at: f64,
+    action: ActionFile,
+    direction: Option<Direction>,
+    kind: Option<VehicleKind>,
+    crosswalk: Option<Leg>,
+    mode: Option<SignalMode>,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum ActionFile {
+    Spawn,
+    SpawnPedestrian,
+    SignalMode,
+}
+
+impl ActionFile {
+    fn name(self) -> &'static str {
+        match self {
+            ActionFile::Spawn => "spawn",
+            ActionFile::SpawnPedestrian => "spawn-pedestrian",
+            ActionFile::SignalMode => "signal-mode",
+        }
+    }
+}
+
+impl Scenario {
+    /// Loads a scenario from a TOML file. Errors name the file and the offending field.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("scenario {}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("scenario {}: {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let file: ScenarioFile = toml::from_str(text).map_err(|e| describe_toml_error(text, &e))?;
+
+        let mut events = file.events.into_iter()
+            .enumerate()
+            .map(|(i, event)| -> Result<ScenarioEvent, String> {
+                let at = file_seconds(event.at, false).map_err(|e| format!("events[{}].at: {}", i, e))?;
+                let action = Self::validate_action(&event).map_err(|e| format!("events[{}].{}", i, e))?;
+                Ok(ScenarioEvent { at, action })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        // Stable, so events given for the same time keep their order
+        events.sort_by_key(|event| event.at);
+
+        Ok(Scenario { events, next_event: 0 })
+    }
+
+    fn validate_action(event: &EventFile) -> Result<ScenarioAction, String> {
+        let given = [
+            ("direction", event.direction.is_some()),
+            ("kind", event.kind.is_some()),
+            ("crosswalk", event.crosswalk.is_some()),
+            ("mode", event.mode.is_some()),
+        ];
+        let takes: &[&str] = match event.action {
+            ActionFile::Spawn => &["direction", "kind"],
+            ActionFile::SpawnPedestrian => &["crosswalk"],
+            ActionFile::SignalMode => &["mode"],
+        };
+        if let Some(&(field, _)) = given.iter().find(|&&(field, set)| set && !takes.contains(&field)) {
+            return Err(format!("{}: does not apply to the {} action", field, event.action.name()));
+        }
+        let missing = |field: &str| format!("{}: required by the {} action", field, event.action.name());
+
+        Ok(match event.action {
+            ActionFile::Spawn => ScenarioAction::Spawn {
+                direction: event.direction.ok_or_else(|| missing("direction"))?,
+                kind: event.kind.unwrap_or_default(),
+            },
+            ActionFile::SpawnPedestrian => ScenarioAction::SpawnPedestrian {
+                crosswalk: event.crosswalk.ok_or_else(|| missing("crosswalk"))?,
+            },
+            ActionFile::SignalMode => ScenarioAction::SignalMode {
+                mode: event.mode.ok_or_else(|| missing("mode"))?,
+            },
+        })
+    }
+
+    /// Actions due by `now` that have not been returned before.
+    pub fn due(&mut self, now: Duration) -> Vec<ScenarioAction> {
+        let remaining = &self.events[self.next_event..];
+        let count = remaining.iter().take_while(|event| event.at <= now).count();
+        self.next_event += count;
+        remaining[..count].iter().map(|event| event.action).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(fields: &str) -> String {
+        format!("[[events]]\n{}\n", fields)
+    }
+
+    fn parse_error(text: &str) -> String {
+        Scenario::parse(text).err().unwrap_or_default()
+    }
+
+    #[test]
+    fn shipped_scenarios_parse() {
+        let emergency = Scenario::parse(include_str!("../scenarios/emergency.toml")).expect("the scenario is valid");
+        assert!(emergency.events.iter()
+            .all(|event| matches!(event.action, ScenarioAction::Spawn { kind: VehicleKind::Emergency, .. })));
+        let outage = Scenario::parse(include_str!("../scenarios/signal_outage.toml")).expect("the scenario is valid");
+        assert_eq!(outage.events.len(), 4);
+    }
+
+    #[test]
+    fn events_are_due_in_order_of_time() {
+        let text = [
+            event("at = 5.0\naction = \"signal-mode\"\nmode = \"dark\""),
+            event("at = 1.0\naction = \"spawn\"\ndirection = \"west\""),
+            event("at = 5.0\naction = \"spawn-pedestrian\"\ncrosswalk = \"north\""),
+        ].concat();
+        let mut scenario = Scenario::parse(&text).expect("the scenario is valid");
+        assert!(scenario.due(Duration::from_millis(500)).is_empty());
+        assert_eq!(
+            scenario.due(Duration::from_secs(1)),
+            [ScenarioAction::Spawn { direction: Direction::West, kind: VehicleKind::Car }],
+        );
+        // Events at the same time keep the order they were given in
+        assert_eq!(scenario.due(Duration::from_secs(10)), [
+            ScenarioAction::SignalMode { mode: SignalMode::Dark },
+            ScenarioAction::SpawnPedestrian { crosswalk: Leg::North },
+        ]);
+        assert!(scenario.due(Duration::from_secs(20)).is_empty());
+    }
+
+    #[test]
+    fn rejects_fields_that_do_not_belong_to_the_action() {
+        assert!(parse_error(&event("at = 1.0\naction = \"spawn\"\ndirection = \"west\"\nknd = \"emergency\""))
+            .starts_with("line 5, column 1: unknown field `knd`"));
+        assert_eq!(
+            parse_error(&event("at = 1.0\naction = \"signal-mode\"\nmode = \"dark\"\ndirection = \"west\"")),
+            "events[0].direction: does not apply to the signal-mode action",
+        );
+        assert_eq!(
+            parse_error(&event("at = 1.0\naction = \"spawn-pedestrian\"\ncrosswalk = \"east\"\nkind = \"bus\"")),
+            "events[0].kind: does not apply to the spawn-pedestrian action",
+        );
+    }
+
+    #[test]
+    fn rejects_missing_fields_and_bad_times() {
+        assert_eq!(
+            parse_error(&event("at = 1.0\naction = \"spawn\"\nkind = \"bus\"")),
+            "events[0].direction: required by the spawn action",
+        );
+        assert_eq!(
+            parse_error(&event("at = 1.0\naction = \"signal-mode\"")),
+            "events[0].mode: required by the signal-mode action",
+        );
+        assert_eq!(
+            parse_error(&event("at = -1.0\naction = \"signal-mode\"\nmode = \"dark\"")),
+            "events[0].at: must not be negative",
+        );
+        assert!(parse_error(&event("at = 1.0\naction = \"explode\"")).contains("unknown variant `explode`"));
+    }
+}
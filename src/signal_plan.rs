@@ -0,0 +1,488 @@
+// signal_plan.rs
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use serde::Deserialize;
+use crate::detector::DetectorSpec;
+use crate::pedestrian::Leg;
+use crate::traffic_light::Axis;
+use crate::vehicle::{Direction, Turn};
+
+/// How left turns are timed in the built-in two-phase plan. `Leading` runs the
+/// protected arrow before the through green, `Lagging` after it; either way lefts
+/// may still turn permissively during the through green.
+#[derive(Clone, Copy, PartialEq)]
+pub enum LeftTurnPhasing {
+    Permissive,
+    Leading,
+    Lagging,
+}
+
+impl std::str::FromStr for LeftTurnPhasing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "permissive" => Ok(LeftTurnPhasing::Permissive),
+            "leading" => Ok(LeftTurnPhasing::Leading),
+            "lagging" => Ok(LeftTurnPhasing::Lagging),
+            _ => Err("expected permissive, leading or lagging".to_string()),
+        }
+    }
+}
+
+/// A movement released during a phase. A `permissive` movement may proceed but
+/// must yield to conflicting traffic; only left turns are usually permissive.
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PhaseMovement {
+    pub direction: Direction,
+    pub turn: Turn,
+    #[serde(default)]
+    pub permissive: bool,
+}
+
+#[derive(Clone)]
+pub struct Phase {
+    pub name: String,
+    pub movements: Vec<PhaseMovement>,
+    pub green: Duration,
+    pub min_green: Duration,
+    pub max_green: Duration,
+    pub amber: Duration,
+    pub all_red: Duration,
+    // Gap after the last detection that ends an actuated green
+    pub passage: Duration,
+}
+
+/// Queue lengths at which `controller::CongestionController` cuts green short or
+/// stretches it.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AdaptiveThresholds {
+    pub congestion_threshold: u32,
+    pub light_traffic_threshold: u32,
+}
+
+impl Default for AdaptiveThresholds {
+    fn default() -> Self {
+        AdaptiveThresholds {
+            congestion_threshold: 4,
+            light_traffic_threshold: 2,
+        }
+    }
+}
+
+/// How long WALK is shown once a pedestrian call is served, and the flashing
+/// DON'T WALK clearance that follows it before the phase may end.
+#[derive(Clone, Copy)]
+pub struct PedestrianTiming {
+    pub walk: Duration,
+    pub clearance: Duration,
+}
+
+impl Default for PedestrianTiming {
+    fn default() -> Self {
+        PedestrianTiming {
+            walk: Duration::from_secs(4),
+            clearance: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Ordered list of phases the signal cycles through, and the loop detectors
+/// that feed its controller.
+#[derive(Clone)]
+pub struct SignalPlan {
+    pub phases: Vec<Phase>,
+    pub adaptive: AdaptiveThresholds,
+    pub detectors: Vec<DetectorSpec>,
+    pub pedestrians: PedestrianTiming,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PlanFile {
+    #[serde(default)]
+    adaptive: AdaptiveThresholds,
+    phases: Vec<PhaseFile>,
+    detectors: Option<Vec<DetectorSpec>>,
+    pedestrians: Option<PedestrianFile>,
+}
+
+// Times are given in seconds
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PedestrianFile {
+    walk: f64,
+    clearance: f64,
+}
+
+// Times are given in seconds
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PhaseFile {
+    name: String,
+    movements: Vec<PhaseMovement>,
+    green: f64,
+    min_green: f64,
+    max_green: f64,
+    amber: f64,
+    all_red: f64,
+    #[serde(default = "default_passage")]
+    passage: f64,
+}
+
+fn default_passage() -> f64 {
+    2.0
+}
+
+impl Phase {
+    pub fn movement(&self, direction: Direction, turn: Turn) -> Option<&PhaseMovement> {
+        self.movements.iter().find(|m| m.direction == direction && m.turn == turn)
+    }
+
+    pub fn serves(&self, direction: Direction, turn: Turn) -> bool {
+        self.movement(direction, turn).is_some()
+    }
+
+    /// Whether the circular ball is green for `direction`. The ball is shared by
+    /// through, right-turn and permissive left-turn movements.
+    pub fn ball_green(&self, direction: Direction) -> bool {
+        self.movements.iter()
+            .any(|m| m.direction == direction && (m.turn != Turn::Left || m.permissive))
+    }
+
+    pub fn protected_left(&self, direction: Direction) -> bool {
+        self.movement(direction, Turn::Left).is_some_and(|m| !m.permissive)
+    }
+
+    pub fn permissive_left(&self, direction: Direction) -> bool {
+        self.movement(direction, Turn::Left).is_some_and(|m| m.permissive)
+    }
+}
+
+impl SignalPlan {
+    /// Two-phase plan alternating the east-west and north-south axes, with optional
+    /// protected left-turn phases. East-west is served first.
+    pub fn two_phase(left_turns: LeftTurnPhasing) -> Self {
+        let mut phases = Vec::new();
+        for axis in [Axis::EastWest, Axis::NorthSouth] {
+            let through = Self::through_phase(axis, left_turns);
+            match left_turns {
+                LeftTurnPhasing::Permissive => phases.push(through),
+                LeftTurnPhasing::Leading => {
+                    phases.push(Self::left_arrow_phase(axis, Duration::ZERO));
+                    phases.push(Phase { all_red: Duration::from_secs(1), ..through });
+                },
+                LeftTurnPhasing::Lagging => {
+                    phases.push(Phase { all_red: Duration::ZERO, ..through });
+                    phases.push(Self::left_arrow_phase(axis, Duration::from_secs(1)));
+                },
+            }
+        }
+
+        SignalPlan {
+            phases,
+            adaptive: AdaptiveThresholds::default(),
+            detectors: DetectorSpec::default_layout(),
+            pedestrians: PedestrianTiming::default(),
+        }
+    }
+
+    fn through_phase(axis: Axis, left_turns: LeftTurnPhasing) -> Phase {
+        let mut movements = Vec::new();
+        for direction in axis.directions() {
+            for turn in [Turn::Straight, Turn::Right, Turn::Left] {
+                movements.push(PhaseMovement { direction, turn, permissive: turn == Turn::Left });
+            }
+        }
+        let name = match axis {
+            Axis::NorthSouth => "north-south",
+            Axis::EastWest => "east-west",
+        };
+
+        Phase {
+            name: name.to_string(),
+            movements,
+            green: Duration::from_secs(5),
+            min_green: Duration::from_secs(3),
+            max_green: Duration::from_secs(10),
+            amber: Duration::from_secs(3),
+            all_red: if left_turns == LeftTurnPhasing::Permissive { Duration::from_secs(1) } else { Duration::ZERO },
+            passage: Duration::from_secs(2),
+        }
+    }
+
+    fn left_arrow_phase(axis: Axis, all_red: Duration) -> Phase {
+        let movements = axis.directions()
+            .map(|direction| PhaseMovement { direction, turn: Turn::Left, permissive: false })
+            .to_vec();
+        let name = match axis {
+            Axis::NorthSouth => "north-south left arrows",
+            Axis::EastWest => "east-west left arrows",
+        };
+
+        Phase {
+            name: name.to_string(),
+            movements,
+            green: Duration::from_secs(4),
+            min_green: Duration::from_secs(4),
+            max_green: Duration::from_secs(4),
+            amber: Duration::from_secs(3),
+            all_red,
+            passage: Duration::from_secs(2),
+        }
+    }
+
+    /// Loads a plan from a TOML file. Errors name the file and the offending field.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("signal plan {}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("signal plan {}: {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let file: PlanFile = toml::from_str(text).map_err(|e| describe_toml_error(text, &e))?;
+
+        if file.phases.is_empty() {
+            return Err("phases: at least one phase is required".to_string());
+        }
+        if file.adaptive.light_traffic_threshold > file.adaptive.congestion_threshold {
+            return Err("adaptive.light_traffic_threshold: must not exceed congestion_threshold".to_string());
+        }
+
+        let phases = file.phases.into_iter()
+            .enumerate()
+            .map(|(i, phase)| Self::validate_phase(phase).map_err(|e| format!("phases[{}].{}", i, e)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for direction in Direction::ALL {
+            for turn in [Turn::Left, Turn::Straight, Turn::Right] {
+                if !phases.iter().any(|phase| phase.serves(direction, turn)) {
+                    return Err(format!(
+                        "phases: {} {} is never served",
+                        direction.name(), turn.name()
+                    ));
+                }
+            }
+        }
+
+        let detectors = file.detectors.unwrap_or_else(DetectorSpec::default_layout);
+        for (i, detector) in detectors.iter().enumerate() {
+            detector.validate().map_err(|e| format!("detectors[{}].{}", i, e))?;
+        }
+
+        let pedestrians = match file.pedestrians {
+            Some(timing) => Self::validate_pedestrians(timing).map_err(|e| format!("pedestrians.{}", e))?,
+            None => PedestrianTiming::default(),
+        };
+
+        let plan = SignalPlan { phases, adaptive: file.adaptive, detectors, pedestrians };
+        for leg in Leg::ALL {
+            if !(0..plan.phases.len()).any(|i| plan.phase_serves_crosswalk(i, leg)) {
+                return Err(format!("phases: the {} crosswalk is never served", leg.name()));
+            }
+        }
+        Ok(plan)
+    }
+
+    fn validate_pedestrians(timing: PedestrianFile) -> Result<PedestrianTiming, String> {
+        Ok(PedestrianTiming {
+            walk: file_seconds(timing.walk, true).map_err(|e| format!("walk: {}", e))?,
+            clearance: file_seconds(timing.clearance, false).map_err(|e| format!("clearance: {}", e))?,
+        })
+    }
+
+    fn validate_phase(phase: PhaseFile) -> Result<Phase, String> {
+        let seconds = |field: &str, value: f64| file_seconds(value, false).map_err(|e| format!("{}: {}", field, e));
+        let green = seconds("green", phase.green)?;
+        let min_green = seconds("min_green", phase.min_green)?;
+        let max_green = seconds("max_green", phase.max_green)?;
+        let amber = seconds("amber", phase.amber)?;
+        let all_red = seconds("all_red", phase.all_red)?;
+        let passage = seconds("passage", phase.passage)?;
+
+        if min_green.is_zero() {
+            return Err("min_green: must be greater than zero".to_string());
+        }
+        if min_green > max_green {
+            return Err("min_green: must not exceed max_green".to_string());
+        }
+        if green < min_green || green > max_green {
+            return Err("green: must lie between min_green and max_green".to_string());
+        }
+        if phase.movements.is_empty() {
+            return Err("movements: at least one movement is required".to_string());
+        }
+
+        for (i, movement) in phase.movements.iter().enumerate() {
+            for (j, other) in phase.movements.iter().enumerate().take(i) {
+                if movement.direction == other.direction && movement.turn == other.turn {
+                    return Err(format!("movements[{}]: duplicates movements[{}]", i, j));
+                }
+                if movements_conflict(movement, other) {
+                    return Err(format!("movements[{}]: conflicts with movements[{}]", i, j));
+                }
+            }
+        }
+
+        Ok(Phase {
+            name: phase.name,
+            movements: phase.movements,
+            green,
+            min_green,
+            max_green,
+            amber,
+            all_red,
+            passage,
+        })
+    }
+
+    /// Whether `phase` releases any movement from the approach travelling in `direction`.
+    pub fn phase_serves_approach(&self, phase: usize, direction: Direction) -> bool {
+        self.phases[phase].movements.iter().any(|m| m.direction == direction)
+    }
+
+    /// Whether pedestrians may cross the `leg` crosswalk during `phase`: nothing
+    /// released in it enters from that leg or leaves through it unopposed, so the
+    /// only traffic crossing the crosswalk is turning traffic that yields.
+    pub fn phase_serves_crosswalk(&self, phase: usize, leg: Leg) -> bool {
+        self.phases[phase].movements.iter().all(|m| {
+            Leg::entered_by(m.direction) != leg &&
+            (Leg::exited_by(m.direction.turned(m.turn)) != leg || m.turn == Turn::Right || m.permissive)
+        })
+    }
+
+    /// Whether any phase gives `direction` a protected left arrow.
+    pub fn has_protected_left(&self, direction: Direction) -> bool {
+        self.phases.iter().any(|phase| phase.protected_left(direction))
+    }
+}
+
+/// Longest time, in seconds, a duration read from a file may span: one day.
+pub(crate) const MAX_FILE_SECONDS: f64 = 86_400.0;
+
+/// A number of seconds read from a file as a duration, or what is wrong with it.
+/// Zero is only allowed unless `positive`.
+pub(crate) fn file_seconds(value: f64, positive: bool) -> Result<Duration, String> {
+    if value.is_nan() {
+        Err("must be a number of seconds, not NaN".to_string())
+    } else if value < 0.0 {
+        Err("must not be negative".to_string())
+    } else if positive && value == 0.0 {
+        Err("must be greater than zero".to_string())
+    } else if value > MAX_FILE_SECONDS {
+        Err(format!("must not exceed {} seconds", MAX_FILE_SECONDS))
+    } else {
+        Ok(Duration::from_secs_f64(value))
+    }
+}
+
+// Single-line form of a TOML error: its position followed by the message.
+pub(crate) fn describe_toml_error(text: &str, error: &toml::de::Error) -> String {
+    match error.span() {
+        Some(span) => {
+            let before = &text[..span.start];
+            let line = before.matches('\n').count() + 1;
+            let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+            format!("line {}, column {}: {}", line, column, error.message().trim())
+        },
+        None => error.message().trim().to_string(),
+    }
+}
+
+// Two movements released together conflict when their paths cross and neither
+// is a permissive left that yields.
+fn movements_conflict(a: &PhaseMovement, b: &PhaseMovement) -> bool {
+    if a.permissive || b.permissive {
+        return false;
+    }
+    if Axis::of(a.direction) != Axis::of(b.direction) {
+        return !(a.turn == Turn::Right && b.turn == Turn::Right);
+    }
+    if a.direction == b.direction {
+        return false;
+    }
+    // Opposing approaches: a protected left crosses the opposing through and right turns
+    (a.turn == Turn::Left) != (b.turn == Turn::Left)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAN: &str = include_str!("../plans/leading_left.toml");
+
+    // Error from the shipped plan with the first `from` replaced by `to`
+    fn error_with(from: &str, to: &str) -> String {
+        assert!(PLAN.contains(from), "the plan has no '{}'", from);
+        SignalPlan::parse(&PLAN.replacen(from, to, 1)).err().unwrap_or_default()
+    }
+
+    #[test]
+    fn shipped_plan_parses() {
+        let plan = SignalPlan::parse(PLAN).expect("the shipped plan is valid");
+        assert_eq!(plan.phases.len(), 4);
+        assert_eq!(plan.detectors.len(), 8);
+        assert!(plan.has_protected_left(Direction::East));
+        assert_eq!(plan.pedestrians.walk, Duration::from_secs(4));
+    }
+
+    #[test]
+    fn rejects_bad_phase_times() {
+        assert_eq!(
+            error_with("min_green = 3\nmax_green = 6", "min_green = 7\nmax_green = 6"),
+            "phases[0].min_green: must not exceed max_green",
+        );
+        assert_eq!(
+            error_with("green = 4\n", "green = 9\n"),
+            "phases[0].green: must lie between min_green and max_green",
+        );
+        assert_eq!(error_with("min_green = 3\n", "min_green = 0\n"), "phases[0].min_green: must be greater than zero");
+        assert_eq!(error_with("amber = 3\n", "amber = -3\n"), "phases[0].amber: must not be negative");
+        assert_eq!(error_with("amber = 3\n", "amber = nan\n"), "phases[0].amber: must be a number of seconds, not NaN");
+    }
+
+    #[test]
+    fn rejects_bad_movements() {
+        // Adds a movement to the left arrows of the first phase
+        let adding = |movement: &str| {
+            error_with("turn = \"left\" },\n]", &format!("turn = \"left\" }},\n    {},\n]", movement))
+        };
+        assert_eq!(adding("{ direction = \"east\", turn = \"left\" }"), "phases[0].movements[2]: duplicates movements[0]");
+        assert_eq!(
+            adding("{ direction = \"north\", turn = \"straight\" }"),
+            "phases[0].movements[2]: conflicts with movements[0]",
+        );
+        let left_arrows = "movements = [\n    { direction = \"east\", turn = \"left\" },\n    \
+            { direction = \"west\", turn = \"left\" },\n]";
+        assert_eq!(error_with(left_arrows, "movements = []"), "phases[0].movements: at least one movement is required");
+    }
+
+    #[test]
+    fn rejects_plans_that_leave_a_movement_unserved() {
+        let without_north_south = PLAN.split("[[phases]]\nname = \"north-south\"\n").next().unwrap_or_default();
+        assert_eq!(
+            SignalPlan::parse(without_north_south).err().unwrap_or_default(),
+            "phases: north straight is never served",
+        );
+        assert_eq!(
+            SignalPlan::parse("phases = []").err().unwrap_or_default(),
+            "phases: at least one phase is required",
+        );
+    }
+
+    #[test]
+    fn rejects_bad_sections() {
+        assert_eq!(
+            error_with("light_traffic_threshold = 2", "light_traffic_threshold = 5"),
+            "adaptive.light_traffic_threshold: must not exceed congestion_threshold",
+        );
+        assert_eq!(error_with("walk = 4", "walk = 0"), "pedestrians.walk: must be greater than zero");
+        assert!(error_with("clearance = 10", "clearance = 10\nflash = 2")
+            .starts_with("line 12, column 1: unknown field `flash`"));
+        assert!(error_with("kind = \"stop-bar\"", "kind = \"loop\"").contains("unknown variant `loop`"));
+    }
+}
@@ -0,0 +1,431 @@
+// simulation.rs
+use std::time::Duration;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use crate::car_following::CarFollowingModel;
+use crate::clock::SimClock;
+use crate::collision::{self, CollisionEvent};
+use crate::demand::Demand;
+use crate::driver::DriverMix;
+use crate::lane_change;
+use crate::lanes::RoadLayout;
+use crate::options::Options;
+use crate::pedestrian::{Leg, Pedestrian};
+use crate::perception::{Perception, SignalView};
+use crate::reservation::ReservationManager;
+use crate::routes::{OdMatrix, RouteStats};
+use crate::scenario::{Scenario, ScenarioAction};
+use crate::signal_plan::SignalPlan;
+use crate::traffic_light::{SignalMode, TrafficLightSystem};
+use crate::vehicle::{Vehicle, Direction, Glimpse, VehicleKind};
+use crate::vehicle_class::TrafficMix;
+
+// Positions are in metres, with y increasing southward
+pub const WORLD_WIDTH: f32 = 80.0;
+pub const WORLD_HEIGHT: f32 = 60.0;
+pub const CENTER_X: f32 = WORLD_WIDTH / 2.0;
+pub const CENTER_Y: f32 = WORLD_HEIGHT / 2.0;
+
+// Vehicles further than this outside the world are removed
+const DESPAWN_MARGIN: f32 = 10.0;
+// Two vehicles in contact stay one collision until they are this far apart, in metres
+const CONTACT_CLEARANCE: f32 = 0.5;
+
+/// The single random source behind every random decision in a run.
+pub type SimRng = StdRng;
+
+pub struct Simulation {
+    pub vehicles: Vec<Vehicle>,
+    pub pedestrians: Vec<Pedestrian>,
+    pub traffic_light_system: TrafficLightSystem,
+    pub reservations: ReservationManager,
+    pub layout: RoadLayout,
+    pub car_following: CarFollowingModel,
+    // Kinds of vehicle spawned at random
+    pub traffic_mix: TrafficMix,
+    // Profiles of the drivers at the wheel
+    pub driver_mix: DriverMix,
+    // Where the vehicles entering by each leg are bound
+    pub od_matrix: OdMatrix,
+    // Generators spawning vehicles automatically
+    pub demand: Demand,
+    // Vehicles spawned but held, in the order they were spawned, until the entry
+    // to their approach is clear
+    pub entry_queue: Vec<Vehicle>,
+    pub clock: SimClock,
+    pub seed: u64,
+    pub rng: SimRng,
+    pub last_spawn_tick: Option<u64>,
+    pub spawn_cooldown: Duration,
+    pub last_pedestrian_spawn_tick: Option<u64>,
+    pub pedestrian_spawn_cooldown: Duration,
+    pub stats: RunStats,
+    pub scenario: Scenario,
+    // Every contact between two vehicles, in the order it happened
+    pub collisions: Vec<CollisionEvent>,
+    // Pairs of vehicle ids, lowest first, whose contact has not yet cleared
+    contacts: Vec<(u64, u64)>,
+    // The road as drivers remember it
+    perception: Perception,
+}
+
+/// Totals accumulated over a run, for comparing strategies on identical traffic.
+#[derive(Clone, Copy, Default)]
+pub struct RunStats {
+    pub vehicles_spawned: u64,
+    // Trucks and buses among the vehicles spawned
+    pub heavy_vehicles_spawned: u64,
+    // Sum of the ticks each vehicle spawned was held in the entry queue
+    pub entry_wait_ticks: u64,
+    pub vehicles_exited: u64,
+    // Sum over ticks of the number of vehicles standing still
+    pub stopped_vehicle_ticks: u64,
+    pub pedestrians_spawned: u64,
+    pub pedestrians_crossed: u64,
+    // Sum over ticks of the number of pedestrians waiting at the kerb
+    pub pedestrian_wait_ticks: u64,
+    // The vehicle totals broken down by route
+    pub routes: RouteStats,
+}
+
+impl Simulation {
+    pub fn new(seed: u64) -> Self {
+        Self::with_clock(SimClock::default(), seed)
+    }
+
+    /// Creates a simulation whose random decisions are fully determined by `seed`.
+    pub fn with_clock(clock: SimClock, seed: u64) -> Self {
+        Simulation {
+            vehicles: Vec::new(),
+            pedestrians: Vec::new(),
+            traffic_light_system: TrafficLightSystem::new(),
+            reservations: ReservationManager::default(),
+            layout: RoadLayout::single_lane(),
+            car_following: CarFollowingModel::default(),
+            traffic_mix: TrafficMix::default(),
+            driver_mix: DriverMix::default(),
+            od_matrix: OdMatrix::default(),
+            demand: Demand::default(),
+            entry_queue: Vec::new(),
+            clock,
+            seed,
+            rng: SimRng::seed_from_u64(seed),
+            last_spawn_tick: None,
+            spawn_cooldown: Duration::from_secs(1),
+            last_pedestrian_spawn_tick: None,
+            pedestrian_spawn_cooldown: Duration::from_secs(3),
+            stats: RunStats::default(),
+            scenario: Scenario::default(),
+            collisions: Vec::new(),
+            contacts: Vec::new(),
+            perception: Perception::default(),
+        }
+    }
+
+    /// Builds a simulation from command-line options, loading the signal plan,
+    /// road layout and scenario if given.
+    pub fn from_options(options: &Options) -> Result<Self, String> {
+        let mut simulation = Self::with_clock(SimClock::new(options.tick_duration), options.seed_or_random());
+        let plan = match &options.signal_plan {
+            Some(path) => SignalPlan::load(path)?,
+            None => SignalPlan::two_phase(options.left_turns),
+        };
+        simulation.traffic_light_system.set_plan(plan, options.controller.build());
+        simulation.traffic_light_system.control = options.control;
+        simulation.car_following = options.car_following;
+        simulation.traffic_mix = options.traffic_mix.clone();
+        simulation.driver_mix = options.driver_mix.clone();
+        simulation.reservations = ReservationManager::new(options.grant_policy);
+        if let Some(path) = &options.layout {
+            simulation.layout = RoadLayout::load(path)?;
+        }
+        if let Some(path) = &options.od_matrix {
+            simulation.od_matrix = OdMatrix::load(path)?;
+        }
+        if let Some(path) = &options.demand {
+            simulation.demand = Demand::load(path)?;
+        }
+        if let Some(path) = &options.scenario {
+            simulation.scenario = Scenario::load(path)?;
+        }
+        Ok(simulation)
+    }
+
+    /// Spawns a vehicle heading in `direction`, unless the spawn cooldown is still
+    /// running. It waits in the entry queue until there is room for it on the road.
+    pub fn spawn(&mut self, direction: Direction, kind: VehicleKind) -> bool {
+        let cooling_down = self.last_spawn_tick
+            .is_some_and(|tick| self.clock.elapsed_since(tick) <= self.spawn_cooldown);
+        if cooling_down {
+            return false;
+        }
+
+        self.add_vehicle(direction, kind);
+        self.last_spawn_tick = Some(self.clock.tick);
+        true
+    }
+
+    pub fn spawn_random(&mut self, kind: VehicleKind) -> bool {
+        let direction = match self.rng.gen_range(0..4) {
+            0 => Direction::North,
+            1 => Direction::South,
+            2 => Direction::East,
+            _ => Direction::West,
+        };
+        self.spawn(direction, kind)
+    }
+
+    /// Spawns a vehicle of a kind drawn from the traffic mix on a random approach.
+    pub fn spawn_from_mix(&mut self) -> bool {
+        let kind = self.traffic_mix.pick(&mut self.rng);
+        self.spawn_random(kind)
+    }
+
+    // Queues a vehicle at the entry of its approach, to appear once there is room
+    fn add_vehicle(&mut self, direction: Direction, kind: VehicleKind) {
+        let route = self.od_matrix.pick(direction, &mut self.rng);
+        let driver = self.driver_mix.pick(&mut self.rng).sample(&mut self.rng);
+        let mut new_vehicle = Vehicle::new(
+            route,
+            kind,
+            driver,
+            self.layout.approach(direction),
+            self.clock.tick,
+            &mut self.rng
+        );
+        new_vehicle.car_following = self.car_following;
+        self.entry_queue.push(new_vehicle);
+    }
+
+    // Moves queued vehicles onto the road, each once its body, with its minimum
+    // gap around it, is clear of every vehicle, it could brake behind the last
+    // vehicle in its lane, and no vehicle queued before it for the same approach
+    // is still held
+    fn release_entries(&mut self) {
+        let mut held: Vec<Direction> = Vec::new();
+        let mut i = 0;
+        while i < self.entry_queue.len() {
+            let waiting = &self.entry_queue[i];
+            let blocked = held.contains(&waiting.direction) || self.vehicles.iter().any(|other| {
+                waiting.body().grown(waiting.minimum_gap).overlaps(&other.body()) ||
+                other.direction == waiting.direction && waiting.same_lane(other) &&
+                other.gap_behind(waiting) < waiting.safe_gap_behind(other)
+            });
+            if blocked {
+                held.push(waiting.direction);
+                i += 1;
+                continue;
+            }
+
+            let mut new_vehicle = self.entry_queue.remove(i);
+            self.stats.entry_wait_ticks += self.clock.tick - new_vehicle.spawn_tick;
+            new_vehicle.id = self.stats.vehicles_spawned;
+            new_vehicle.spawn_tick = self.clock.tick;
+            let on_road: Vec<Glimpse> = self.vehicles.iter().map(Vehicle::glimpse).collect();
+            new_vehicle.check_vehicles_ahead(&on_road, &SignalView::of(&self.traffic_light_system));
+            self.stats.vehicles_spawned += 1;
+            self.stats.heavy_vehicles_spawned += new_vehicle.kind.is_heavy() as u64;
+            self.stats.routes.get_mut(new_vehicle.route).spawned += 1;
+            self.vehicles.push(new_vehicle);
+        }
+    }
+
+    // Queues the vehicles the generators send this tick
+    fn generate_demand(&mut self) {
+        let dt = self.clock.tick_duration.as_secs_f64();
+        for direction in self.demand.arrivals(self.clock.now(), dt, &mut self.rng) {
+            let kind = self.traffic_mix.pick(&mut self.rng);
+            self.add_vehicle(direction, kind);
+        }
+    }
+
+    // Logs a collision, unless the two vehicles were already in contact
+    fn record_collision(&mut self, event: CollisionEvent) {
+        if !self.in_contact(event.vehicle, event.other) {
+            self.contacts.push((event.vehicle.min(event.other), event.vehicle.max(event.other)));
+            self.collisions.push(event);
+        }
+    }
+
+    fn in_contact(&self, a: u64, b: u64) -> bool {
+        self.contacts.contains(&(a.min(b), a.max(b)))
+    }
+
+    // Forgets contacts between vehicles that have since moved apart or left
+    fn clear_contacts(&mut self) {
+        let vehicles = &self.vehicles;
+        let body = |id: u64| vehicles.iter().find(|vehicle| vehicle.id == id).map(Vehicle::body);
+        self.contacts.retain(|&(a, b)| match (body(a), body(b)) {
+            (Some(a), Some(b)) => a.grown(CONTACT_CLEARANCE).overlaps(&b),
+            _ => false,
+        });
+    }
+
+    /// Places a pedestrian at the crosswalk on `leg`, unless the pedestrian spawn
+    /// cooldown is still running.
+    pub fn spawn_pedestrian(&mut self, leg: Leg) -> bool {
+        let cooling_down = self.last_pedestrian_spawn_tick
+            .is_some_and(|tick| self.clock.elapsed_since(tick) <= self.pedestrian_spawn_cooldown);
+        if cooling_down {
+            return false;
+        }
+
+        self.add_pedestrian(leg);
+        self.last_pedestrian_spawn_tick = Some(self.clock.tick);
+        true
+    }
+
+    pub fn spawn_random_pedestrian(&mut self) -> bool {
+        let leg = Leg::ALL[self.rng.gen_range(0..Leg::ALL.len())];
+        self.spawn_pedestrian(leg)
+    }
+
+    fn add_pedestrian(&mut self, leg: Leg) {
+        self.pedestrians.push(Pedestrian::new(leg, self.clock.tick, &mut self.rng));
+        self.stats.pedestrians_spawned += 1;
+    }
+
+    /// Switches the signals to `mode`, from the next step on.
+    pub fn set_signal_mode(&mut self, mode: SignalMode) {
+        self.traffic_light_system.set_mode(mode, self.clock.tick);
+    }
+
+    fn run_scenario(&mut self) {
+        for action in self.scenario.due(self.clock.now()) {
+            match action {
+                ScenarioAction::Spawn { direction, kind } => self.add_vehicle(direction, kind),
+                ScenarioAction::SpawnPedestrian { crosswalk } => self.add_pedestrian(crosswalk),
+                ScenarioAction::SignalMode { mode } => self.set_signal_mode(mode),
+            }
+        }
+    }
+
+    // Waiting pedestrians keep the push button pressed; those across are removed
+    fn update_pedestrians(&mut self) {
+        let dt = self.clock.tick_duration.as_secs_f32();
+        for pedestrian in &mut self.pedestrians {
+            if !pedestrian.crossing {
+                self.traffic_light_system.press_button(pedestrian.crosswalk);
+                self.stats.pedestrian_wait_ticks += 1;
+            }
+            let traffic_clear = !self.vehicles.iter().any(|vehicle| vehicle.bears_down_on(pedestrian.crosswalk));
+            pedestrian.update(self.traffic_light_system.pedestrian_signal(pedestrian.crosswalk), traffic_clear, dt);
+        }
+
+        let before = self.pedestrians.len();
+        self.pedestrians.retain(|pedestrian| !pedestrian.has_crossed());
+        self.stats.pedestrians_crossed += (before - self.pedestrians.len()) as u64;
+    }
+
+    /// Advances the traffic lights and every vehicle by one tick.
+    pub fn step(&mut self) {
+        self.run_scenario();
+        self.generate_demand();
+        self.release_entries();
+        self.traffic_light_system.update(&self.clock, &self.vehicles);
+        self.update_pedestrians();
+        self.reservations.update(&mut self.vehicles, self.traffic_light_system.effective_control(), self.clock.tick);
+
+        let dt = self.clock.tick_duration.as_secs_f32();
+        self.perception.record(&self.vehicles, &self.traffic_light_system, dt);
+        // Every vehicle as it is now, kept up to date as each one moves
+        let mut around: Vec<Glimpse> = self.vehicles.iter().map(Vehicle::glimpse).collect();
+        let mut i = 0;
+        while i < self.vehicles.len() {
+            // Create a temporary copy of other vehicles for collision checking
+            let other_vehicles: Vec<Vehicle> = self.vehicles.iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, vehicle)| vehicle.clone())
+                .collect();
+
+            // Store the previous placement
+            let previous = self.vehicles[i].placement();
+            let before = self.vehicles[i].body();
+
+            // Pick a lane, then check for collisions and update vehicle. The
+            // driver reacts to the road as it was a reaction time ago
+            let approach = self.layout.approach(self.vehicles[i].direction);
+            lane_change::update_lane(&mut self.vehicles[i], &other_vehicles, approach);
+            let (seen, signals) = self.perception.seen_by(&self.vehicles[i], dt);
+            self.vehicles[i].check_vehicles_ahead(seen, &signals);
+            self.vehicles[i].update(
+                seen,
+                &signals,
+                &around,
+                &self.pedestrians,
+                dt,
+                &mut self.rng
+            );
+
+            // Sweep the move against every other vehicle and cut it short at the first
+            // contact, which is logged. Vehicles already overlapping, or already in
+            // contact, are free to part rather than pinned against each other
+            let after = self.vehicles[i].body();
+            let id = self.vehicles[i].id;
+            let contact = other_vehicles.iter()
+                .filter(|other| !before.overlaps(&other.body()) && !self.in_contact(id, other.id))
+                .filter_map(|other| collision::sweep(&before, &after, &other.body()).map(|clear| (clear, other.id)))
+                .min_by(|a, b| a.0.total_cmp(&b.0));
+            if let Some((clear, other)) = contact {
+                let speed = self.vehicles[i].current_speed;
+                self.vehicles[i].restore(previous);
+                self.vehicles[i].advance(speed * dt * clear);
+                self.vehicles[i].current_speed = 0.0;
+                self.record_collision(CollisionEvent {
+                    tick: self.clock.tick,
+                    vehicle: self.vehicles[i].id,
+                    other,
+                    position: self.vehicles[i].center(),
+                    speed,
+                });
+            }
+
+            around[i] = self.vehicles[i].glimpse();
+            let route = self.vehicles[i].route;
+            if self.vehicles[i].current_speed == 0.0 {
+                self.stats.stopped_vehicle_ticks += 1;
+                self.stats.routes.get_mut(route).stopped_ticks += 1;
+            }
+
+            // Remove vehicles that have left the world
+            if self.is_outside_world(&self.vehicles[i]) {
+                let vehicle = self.vehicles.remove(i);
+                around.remove(i);
+                self.stats.vehicles_exited += 1;
+                let totals = self.stats.routes.get_mut(route);
+                totals.exited += 1;
+                totals.travel_ticks += self.clock.tick - vehicle.spawn_tick;
+                totals.missed += (vehicle.turn != route.turn()) as u64;
+            } else {
+                i += 1;
+            }
+        }
+
+        self.clear_contacts();
+        self.clock.advance();
+    }
+
+    /// Mean time each vehicle that has entered the road was held in the entry queue.
+    pub fn average_entry_wait(&self) -> Duration {
+        let total = self.clock.ticks_to_duration(self.stats.entry_wait_ticks);
+        total.checked_div(self.stats.vehicles_spawned.max(1) as u32).unwrap_or_default()
+    }
+
+    /// Mean time each spawned vehicle has spent standing still so far.
+    pub fn average_stopped_time(&self) -> Duration {
+        let total = self.clock.ticks_to_duration(self.stats.stopped_vehicle_ticks);
+        total.checked_div(self.stats.vehicles_spawned.max(1) as u32).unwrap_or_default()
+    }
+
+    /// Mean time each pedestrian has spent waiting at the kerb so far.
+    pub fn average_pedestrian_wait(&self) -> Duration {
+        let total = self.clock.ticks_to_duration(self.stats.pedestrian_wait_ticks);
+        total.checked_div(self.stats.pedestrians_spawned.max(1) as u32).unwrap_or_default()
+    }
+
+    fn is_outside_world(&self, vehicle: &Vehicle) -> bool {
+        vehicle.x < -DESPAWN_MARGIN || vehicle.x > WORLD_WIDTH + DESPAWN_MARGIN ||
+        vehicle.y < -DESPAWN_MARGIN || vehicle.y > WORLD_HEIGHT + DESPAWN_MARGIN
+    }
+}
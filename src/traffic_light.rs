@@ -1,239 +1,749 @@
-use sdl2::pixels::Color;
-use sdl2::render::Canvas;
-use sdl2::video::Window;
-use std::time::Instant;
-use sdl2::rect::Rect;
-use crate::vehicle::StopReason;
-use crate::window::{CENTER_X, CENTER_Y};
-use crate::vehicle::{Vehicle, Direction};
-
-pub const ROAD_WIDTH: u32 = 100;
-const TRAFFIC_LIGHT_DISTANCE: i32 = 20; // Distance from road edge
-const LIGHT_POLE_WIDTH: u32 = 10;
-const LIGHT_POLE_HEIGHT: u32 = 30;
-const LIGHT_HEAD_WIDTH: u32 = 20;
-const LIGHT_HEAD_HEIGHT: u32 = 40;
-const LIGHT_RADIUS: i32 = 6;
+use std::time::Duration;
+use serde::Deserialize;
+use crate::clock::SimClock;
+use crate::control::IntersectionControl;
+use crate::controller::{ControllerKind, Observation, SignalController};
+use crate::detector::Detector;
+use crate::pedestrian::{Crosswalk, Leg, PedestrianSignal};
+use crate::signal_plan::{SignalPlan, LeftTurnPhasing};
+use crate::vehicle::{StopReason, VehicleKind};
+use crate::vehicle::{Vehicle, Direction, Turn};
 
-#[derive(Clone, Copy, PartialEq)]
+// Room for three lanes on each side of the centre line
+pub const ROAD_WIDTH: f32 = 15.0;
+// Distance upstream of the stop line at which an emergency vehicle requests preemption
+const PREEMPTION_RANGE: f32 = 25.0;
+// Longest a preemption holds the signals before normal timing resumes, should
+// its emergency vehicle be held up
+const MAX_PREEMPTION: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum TrafficLightState {
     Red,
+    Amber,
+    Green,
+}
+
+/// Pair of opposing approaches.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Axis {
+    NorthSouth,
+    EastWest,
+}
+
+impl Axis {
+    pub fn of(direction: Direction) -> Self {
+        match direction {
+            Direction::North | Direction::South => Axis::NorthSouth,
+            Direction::East | Direction::West => Axis::EastWest,
+        }
+    }
+
+    pub fn other(self) -> Self {
+        match self {
+            Axis::NorthSouth => Axis::EastWest,
+            Axis::EastWest => Axis::NorthSouth,
+        }
+    }
+
+    pub fn directions(self) -> [Direction; 2] {
+        match self {
+            Axis::NorthSouth => [Direction::North, Direction::South],
+            Axis::EastWest => [Direction::East, Direction::West],
+        }
+    }
+}
+
+/// How the signal heads are operating.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Deserialize)]
+#[serde(try_from = "String")]
+pub enum SignalMode {
+    /// Cycling through the signal plan.
+    #[default]
+    Normal,
+    /// Red flashing on every approach, obeyed as an all-way stop.
+    FlashingRed,
+    /// Amber flashing on the `major` road and red on the other, obeyed as a
+    /// two-way stop.
+    FlashingAmber { major: Axis },
+    /// Failed, with every head dark, obeyed as an all-way stop.
+    Dark,
+}
+
+/// Interval within the current phase. Movements that do not continue into the
+/// next phase show amber, then red for the `AllRed` clearance interval before
+/// the next phase is released.
+#[derive(Clone, Copy, PartialEq)]
+pub enum SignalInterval {
     Green,
+    Amber,
+    AllRed,
+}
+
+/// An emergency vehicle's request for green on its movement.
+#[derive(Clone, Copy)]
+pub struct Preemption {
+    pub vehicle: u64,
+    pub direction: Direction,
+    pub turn: Turn,
+    pub requested_at: u64,
+    // Set once the movement shows green
+    pub served: bool,
+}
+
+#[derive(Clone, Copy)]
+pub enum SignalEvent {
+    PreemptionRequested { direction: Direction, turn: Turn },
+    PreemptionServed { direction: Direction },
+    // The emergency vehicle has cleared and the controller resumes normal timing
+    PreemptionEnded { direction: Direction },
+    // The emergency vehicle took too long to clear and its request is dropped
+    PreemptionExpired { direction: Direction },
+    ModeChanged { mode: SignalMode },
+}
+
+#[derive(Clone, Copy)]
+pub struct SignalLogEntry {
+    pub tick: u64,
+    pub event: SignalEvent,
 }
 
 pub struct TrafficLightSystem {
+    // Vehicles ignore the lights unless the intersection is signalized
+    pub control: IntersectionControl,
+    pub mode: SignalMode,
+    pub plan: SignalPlan,
+    pub controller: Box<dyn SignalController>,
+    pub phase_index: usize,
+    // Phase released after the current one clears
+    pub target_phase: usize,
+    pub interval: SignalInterval,
     pub north_state: TrafficLightState,
     pub south_state: TrafficLightState,
     pub east_state: TrafficLightState,
     pub west_state: TrafficLightState,
-    pub last_change: Instant,
-    pub change_interval: u64,
-    pub min_interval: u64,
-    pub max_interval: u64,
-    pub north_south_congestion: u32,
-    pub east_west_congestion: u32,
+    // Left-turn arrows; `None` means the arrow is dark and lefts follow the ball
+    pub north_arrow: Option<TrafficLightState>,
+    pub south_arrow: Option<TrafficLightState>,
+    pub east_arrow: Option<TrafficLightState>,
+    pub west_arrow: Option<TrafficLightState>,
+    pub last_change: u64,
+    // Vehicles held at the light, per phase that would serve them
+    pub phase_demand: Vec<u32>,
+    pub detectors: Vec<Detector>,
+    pub crosswalks: Vec<Crosswalk>,
+    pub preemption: Option<Preemption>,
+    // Emergency vehicles whose preemption expired, which are not served again
+    pub expired_preemptions: Vec<u64>,
+    pub events: Vec<SignalLogEntry>,
 }
 
 impl TrafficLightSystem {
     pub fn new() -> Self {
-        TrafficLightSystem {
+        Self::with_plan(
+            SignalPlan::two_phase(LeftTurnPhasing::Permissive),
+            ControllerKind::Congestion.build()
+        )
+    }
+
+    pub fn with_plan(plan: SignalPlan, controller: Box<dyn SignalController>) -> Self {
+        let mut system = TrafficLightSystem {
+            phase_demand: vec![0; plan.phases.len()],
+            detectors: plan.detectors.iter().copied().map(Detector::new).collect(),
+            crosswalks: Leg::ALL.into_iter().map(Crosswalk::new).collect(),
+            target_phase: 1 % plan.phases.len(),
+            control: IntersectionControl::Signalized,
+            mode: SignalMode::Normal,
+            plan,
+            controller,
+            phase_index: 0,
+            interval: SignalInterval::Green,
             north_state: TrafficLightState::Red,
             south_state: TrafficLightState::Red,
-            east_state: TrafficLightState::Green,
-            west_state: TrafficLightState::Green,
-            last_change: Instant::now(),
-            change_interval: 5,
-            min_interval: 3,
-            max_interval: 10,
-            north_south_congestion: 0,
-            east_west_congestion: 0,
-        }
+            east_state: TrafficLightState::Red,
+            west_state: TrafficLightState::Red,
+            north_arrow: None,
+            south_arrow: None,
+            east_arrow: None,
+            west_arrow: None,
+            last_change: 0,
+            preemption: None,
+            expired_preemptions: Vec::new(),
+            events: Vec::new(),
+        };
+        system.apply_interval();
+        system
     }
 
-    pub fn update_congestion(&mut self, vehicles: &[Vehicle]) {
-        self.north_south_congestion = 0;
-        self.east_west_congestion = 0;
-        
+    fn update_congestion(&mut self, vehicles: &[Vehicle]) {
+        self.phase_demand.iter_mut().for_each(|demand| *demand = 0);
+
         for vehicle in vehicles {
             if vehicle.stopped && vehicle.stop_reason == StopReason::TrafficLight {
-                match vehicle.direction {
-                    Direction::North | Direction::South => self.north_south_congestion += 1,
-                    Direction::East | Direction::West => self.east_west_congestion += 1,
+                for (i, phase) in self.plan.phases.iter().enumerate() {
+                    if phase.serves(vehicle.direction, vehicle.turn) {
+                        self.phase_demand[i] += 1;
+                    }
                 }
             }
         }
-        self.adapt_timing();
     }
 
-    fn adapt_timing(&mut self) {
-        const CONGESTION_THRESHOLD: u32 = 4;
-        
-        if self.north_state == TrafficLightState::Green {
-            self.change_interval = if self.east_west_congestion >= CONGESTION_THRESHOLD {
-                self.min_interval
-            } else {
-                5
-            };
-        } else {
-            self.change_interval = if self.north_south_congestion >= CONGESTION_THRESHOLD {
-                self.min_interval
+    // Tracks the emergency vehicle being served. One request is held until its
+    // vehicle has crossed the intersection, or for at most `MAX_PREEMPTION`; the
+    // nearest waiting one comes next.
+    fn update_preemption(&mut self, clock: &SimClock, vehicles: &[Vehicle]) {
+        self.expired_preemptions.retain(|&id| vehicles.iter().any(|v| v.id == id));
+        let requesting = |direction: Option<Direction>, expired: &[u64]| vehicles.iter()
+            .filter(|v| v.kind == VehicleKind::Emergency && !v.turn_executed && !expired.contains(&v.id))
+            .filter(|v| direction.is_none_or(|direction| v.direction == direction))
+            .filter(|v| (-ROAD_WIDTH..=PREEMPTION_RANGE).contains(&v.distance_to_stop_line()))
+            .min_by(|a, b| a.distance_to_stop_line().total_cmp(&b.distance_to_stop_line()))
+            .map(|v| (v.id, v.direction, v.turn));
+
+        if let Some(active) = self.preemption {
+            let event = if clock.elapsed_since(active.requested_at) >= MAX_PREEMPTION {
+                self.expired_preemptions.push(active.vehicle);
+                SignalEvent::PreemptionExpired { direction: active.direction }
+            } else if requesting(Some(active.direction), &self.expired_preemptions).is_some() {
+                return;
             } else {
-                5
+                SignalEvent::PreemptionEnded { direction: active.direction }
             };
+            self.preemption = None;
+            self.events.push(SignalLogEntry { tick: clock.tick, event });
+        }
+
+        if let Some((vehicle, direction, turn)) = requesting(None, &self.expired_preemptions) {
+            self.preemption = Some(Preemption { vehicle, direction, turn, requested_at: clock.tick, served: false });
+            self.events.push(SignalLogEntry {
+                tick: clock.tick,
+                event: SignalEvent::PreemptionRequested { direction, turn },
+            });
+        }
+    }
+
+    // First phase in cycle order, starting with the current one, that serves the
+    // preempting movement
+    fn preemption_phase(&self, preemption: Preemption) -> usize {
+        let count = self.plan.phases.len();
+        (0..count)
+            .map(|offset| (self.phase_index + offset) % count)
+            .find(|&i| self.plan.phases[i].serves(preemption.direction, preemption.turn))
+            .unwrap_or(self.phase_index)
+    }
+
+    pub fn update(&mut self, clock: &SimClock, vehicles: &[Vehicle]) {
+        self.update_congestion(vehicles);
+        for detector in &mut self.detectors {
+            detector.update(vehicles, clock.tick);
+        }
+        // Out of normal operation the phases stand still and the pedestrian heads
+        // go dark
+        if self.mode != SignalMode::Normal {
+            self.update_crosswalks(clock, true);
+            return;
         }
-        
-        if self.north_south_congestion >= CONGESTION_THRESHOLD && 
-           self.east_west_congestion >= CONGESTION_THRESHOLD {
-            self.change_interval = 5;
+        self.update_preemption(clock, vehicles);
+
+        let phase = &self.plan.phases[self.phase_index];
+        let elapsed = clock.elapsed_since(self.last_change);
+        let pedestrians_crossing = self.crosswalks.iter()
+            .any(|crosswalk| matches!(crosswalk.signal, PedestrianSignal::Walk | PedestrianSignal::FlashingDontWalk));
+        let mut ending_green = false;
+        let next_interval = match self.interval {
+            // Preemption holds a green that serves the emergency vehicle and ends
+            // any other green at once, skipping minimum green but not clearance
+            SignalInterval::Green if self.preemption.is_some() => {
+                let target = self.preemption_phase(self.preemption.unwrap());
+                ending_green = target != self.phase_index;
+                (ending_green && !pedestrians_crossing).then(|| {
+                    self.target_phase = target;
+                    SignalInterval::Amber
+                })
+            },
+            // The controller sees every green tick but can only end the green
+            // once minimum green has run and pedestrians have cleared
+            SignalInterval::Green => {
+                let observation = Observation {
+                    plan: &self.plan,
+                    current_phase: self.phase_index,
+                    clock,
+                    green_elapsed: elapsed,
+                    phase_demand: &self.phase_demand,
+                    detectors: &self.detectors,
+                    crosswalks: &self.crosswalks,
+                    vehicles,
+                };
+                let desired = self.controller.desired_phase(&observation);
+                ending_green = desired != self.phase_index && elapsed >= phase.min_green;
+                (ending_green && !pedestrians_crossing).then(|| {
+                    self.target_phase = desired;
+                    SignalInterval::Amber
+                })
+            },
+            SignalInterval::Amber if elapsed >= phase.amber => Some(SignalInterval::AllRed),
+            SignalInterval::AllRed if elapsed >= phase.all_red => {
+                if let Some(preemption) = self.preemption {
+                    self.target_phase = self.preemption_phase(preemption);
+                }
+                self.phase_index = self.target_phase;
+                Some(SignalInterval::Green)
+            },
+            _ => None,
+        };
+
+        if let Some(interval) = next_interval {
+            self.interval = interval;
+            self.last_change = clock.tick;
+            self.apply_interval();
         }
-        
-        if self.north_south_congestion < 2 && self.east_west_congestion < 2 {
-            self.change_interval = self.max_interval;
+        self.update_crosswalks(clock, ending_green);
+
+        if let Some(preemption) = &mut self.preemption {
+            let phase = &self.plan.phases[self.phase_index];
+            if !preemption.served && self.interval == SignalInterval::Green &&
+               phase.serves(preemption.direction, preemption.turn) {
+                preemption.served = true;
+                self.events.push(SignalLogEntry {
+                    tick: clock.tick,
+                    event: SignalEvent::PreemptionServed { direction: preemption.direction },
+                });
+            }
         }
     }
-    
-    pub fn update(&mut self) {
-        if self.last_change.elapsed().as_secs() >= self.change_interval {
-            if self.north_state == TrafficLightState::Red {
-                self.north_state = TrafficLightState::Green;
-                self.south_state = TrafficLightState::Green;
-                self.east_state = TrafficLightState::Red;
-                self.west_state = TrafficLightState::Red;
-            } else {
-                self.north_state = TrafficLightState::Red;
-                self.south_state = TrafficLightState::Red;
-                self.east_state = TrafficLightState::Green;
-                self.west_state = TrafficLightState::Green;
+
+    // Serves push-button calls on crosswalks the current green allows, unless the
+    // green is already due to end, and times WALK then flashing DON'T WALK.
+    // Preemption cuts WALK short but never the clearance.
+    fn update_crosswalks(&mut self, clock: &SimClock, ending_green: bool) {
+        // Without signal control the heads are blank, and light again at DON'T
+        // WALK once it is back
+        if !self.effective_control().is_signalized() {
+            for crosswalk in &mut self.crosswalks {
+                if crosswalk.signal != PedestrianSignal::Dark {
+                    crosswalk.signal = PedestrianSignal::Dark;
+                    crosswalk.signal_since = clock.tick;
+                }
+            }
+            return;
+        }
+        let timing = self.plan.pedestrians;
+        let may_start = self.interval == SignalInterval::Green && !ending_green && self.preemption.is_none();
+        for crosswalk in &mut self.crosswalks {
+            let elapsed = clock.elapsed_since(crosswalk.signal_since);
+            let next = match crosswalk.signal {
+                PedestrianSignal::DontWalk if crosswalk.called && may_start &&
+                    self.plan.phase_serves_crosswalk(self.phase_index, crosswalk.leg) => {
+                    crosswalk.called = false;
+                    crosswalk.walks_served += 1;
+                    Some(PedestrianSignal::Walk)
+                },
+                PedestrianSignal::Walk if elapsed >= timing.walk || self.preemption.is_some() =>
+                    Some(PedestrianSignal::FlashingDontWalk),
+                PedestrianSignal::FlashingDontWalk if elapsed >= timing.clearance =>
+                    Some(PedestrianSignal::DontWalk),
+                PedestrianSignal::Dark => Some(PedestrianSignal::DontWalk),
+                _ => None,
+            };
+            if let Some(signal) = next {
+                crosswalk.signal = signal;
+                crosswalk.signal_since = clock.tick;
+            }
+        }
+    }
+
+    /// Registers a push-button press at the crosswalk on `leg`.
+    pub fn press_button(&mut self, leg: Leg) {
+        if let Some(crosswalk) = self.crosswalks.iter_mut().find(|c| c.leg == leg) {
+            crosswalk.called = true;
+        }
+    }
+
+    pub fn pedestrian_signal(&self, leg: Leg) -> PedestrianSignal {
+        self.crosswalks.iter()
+            .find(|c| c.leg == leg)
+            .map_or(PedestrianSignal::DontWalk, |c| c.signal)
+    }
+
+    fn apply_interval(&mut self) {
+        for direction in Direction::ALL {
+            let (ball, arrow) = self.signals_for(direction);
+            match direction {
+                Direction::North => (self.north_state, self.north_arrow) = (ball, arrow),
+                Direction::South => (self.south_state, self.south_arrow) = (ball, arrow),
+                Direction::East => (self.east_state, self.east_arrow) = (ball, arrow),
+                Direction::West => (self.west_state, self.west_arrow) = (ball, arrow),
             }
-            self.last_change = Instant::now();
-        }
-    }
-    
-    pub fn draw(&self, canvas: &mut Canvas<Window>) -> Result<(), String> {
-        // Northbound light (facing south)
-        self.draw_traffic_light(
-            canvas,
-            CENTER_X - LIGHT_HEAD_WIDTH as i32 / 2,
-            CENTER_Y - ROAD_WIDTH as i32 / 2 - TRAFFIC_LIGHT_DISTANCE - LIGHT_HEAD_HEIGHT as i32,
-            false, // horizontal
-            self.north_state
-        )?;
-        
-        // Southbound light (facing north)
-        self.draw_traffic_light(
-            canvas,
-            CENTER_X - LIGHT_HEAD_WIDTH as i32 / 2,
-            CENTER_Y + ROAD_WIDTH as i32 / 2 + TRAFFIC_LIGHT_DISTANCE,
-            false, // horizontal
-            self.south_state
-        )?;
-        
-        // Eastbound light (facing west)
-        self.draw_traffic_light(
-            canvas,
-            CENTER_X + ROAD_WIDTH as i32 / 2 + TRAFFIC_LIGHT_DISTANCE,
-            CENTER_Y - LIGHT_HEAD_WIDTH as i32 / 2,
-            true, // vertical
-            self.east_state
-        )?;
-        
-        // Westbound light (facing east)
-        self.draw_traffic_light(
-            canvas,
-            CENTER_X - ROAD_WIDTH as i32 / 2 - TRAFFIC_LIGHT_DISTANCE - LIGHT_HEAD_HEIGHT as i32,
-            CENTER_Y - LIGHT_HEAD_WIDTH as i32 / 2,
-            true, // vertical
-            self.west_state
-        )?;
-        
-        Ok(())
-    }
-    
-    fn draw_traffic_light(
-        &self,
-        canvas: &mut Canvas<Window>,
-        x: i32,
-        y: i32,
-        vertical: bool,
-        state: TrafficLightState
-    ) -> Result<(), String> {
-        // Draw pole
-        canvas.set_draw_color(Color::RGB(70, 70, 70));
-        let pole_rect = if vertical {
-            Rect::new(
-                x + LIGHT_HEAD_HEIGHT as i32 / 2 - LIGHT_POLE_WIDTH as i32 / 2,
-                y + LIGHT_HEAD_WIDTH as i32,
-                LIGHT_POLE_WIDTH,
-                LIGHT_POLE_HEIGHT
-            )
+        }
+    }
+
+    // Movements that continue into the next phase stay green through the amber
+    // and all-red intervals; the rest clear. A phase restarting after the signals
+    // were out of normal operation continues nothing.
+    fn signals_for(&self, direction: Direction) -> (TrafficLightState, Option<TrafficLightState>) {
+        let current = &self.plan.phases[self.phase_index];
+        let next = &self.plan.phases[self.target_phase];
+        let clearing = self.interval != SignalInterval::Green;
+        let restarting = self.target_phase == self.phase_index;
+
+        let (green_now, green_next) = (current.ball_green(direction), next.ball_green(direction) && !restarting);
+        let ball = if green_now && (!clearing || green_next) {
+            TrafficLightState::Green
+        } else if green_now && self.interval == SignalInterval::Amber {
+            TrafficLightState::Amber
         } else {
-            Rect::new(
-                x + LIGHT_HEAD_WIDTH as i32 / 2 - LIGHT_POLE_WIDTH as i32 / 2,
-                y - LIGHT_POLE_HEIGHT as i32,
-                LIGHT_POLE_WIDTH,
-                LIGHT_POLE_HEIGHT
-            )
+            TrafficLightState::Red
         };
-        canvas.fill_rect(pole_rect)?;
-        
-        // Draw light head
-        canvas.set_draw_color(Color::RGB(40, 40, 40));
-        let head_rect = Rect::new(x, y, 
-            if vertical { LIGHT_HEAD_HEIGHT } else { LIGHT_HEAD_WIDTH },
-            if vertical { LIGHT_HEAD_WIDTH } else { LIGHT_HEAD_HEIGHT }
-        );
-        canvas.fill_rect(head_rect)?;
-        canvas.set_draw_color(Color::RGB(20, 20, 20));
-        canvas.draw_rect(head_rect)?;
-        
-        // Calculate light positions
-        let (red_pos, green_pos) = if vertical {
-            // Vertical light (for east/west traffic)
-            (
-                (x + LIGHT_HEAD_HEIGHT as i32 / 2, y + LIGHT_HEAD_WIDTH as i32 / 3),
-                (x + LIGHT_HEAD_HEIGHT as i32 / 2, y + 2 * LIGHT_HEAD_WIDTH as i32 / 3)
-            )
+
+        if !self.plan.has_protected_left(direction) {
+            return (ball, None);
+        }
+        let (arrow_now, arrow_next) = (current.protected_left(direction), next.protected_left(direction) && !restarting);
+        let arrow = if arrow_now && (!clearing || arrow_next) {
+            Some(TrafficLightState::Green)
+        } else if arrow_now && self.interval == SignalInterval::Amber {
+            Some(TrafficLightState::Amber)
+        } else if current.permissive_left(direction) && ball != TrafficLightState::Red {
+            None
         } else {
-            // Horizontal light (for north/south traffic)
-            (
-                (x + LIGHT_HEAD_WIDTH as i32 / 3, y + LIGHT_HEAD_HEIGHT as i32 / 2),
-                (x + 2 * LIGHT_HEAD_WIDTH as i32 / 3, y + LIGHT_HEAD_HEIGHT as i32 / 2)
-            )
+            Some(TrafficLightState::Red)
         };
-        
-        // Draw red light
-        canvas.set_draw_color(if state == TrafficLightState::Red {
-            Color::RGB(255, 0, 0)
-        } else {
-            Color::RGB(80, 0, 0)
-        });
-        self.draw_filled_circle(canvas, red_pos.0, red_pos.1, LIGHT_RADIUS)?;
-        
-        // Draw green light
-        canvas.set_draw_color(if state == TrafficLightState::Green {
-            Color::RGB(0, 255, 0)
-        } else {
-            Color::RGB(0, 80, 0)
-        });
-        self.draw_filled_circle(canvas, green_pos.0, green_pos.1, LIGHT_RADIUS)?;
-        
-        Ok(())
-    }
-    
-    fn draw_filled_circle(
-        &self, 
-        canvas: &mut Canvas<Window>, 
-        x: i32, 
-        y: i32, 
-        radius: i32
-    ) -> Result<(), String> {
-        for dy in -radius..=radius {
-            for dx in -radius..=radius {
-                if dx*dx + dy*dy <= radius*radius {
-                    canvas.draw_point((x + dx, y + dy))?;
-                }
-            }
+        (ball, arrow)
+    }
+
+    /// Switches the heads to `mode`. Back in normal operation, the current phase
+    /// starts again after an all-red clearance.
+    pub fn set_mode(&mut self, mode: SignalMode, tick: u64) {
+        if mode == self.mode {
+            return;
+        }
+        self.mode = mode;
+        self.events.push(SignalLogEntry { tick, event: SignalEvent::ModeChanged { mode } });
+        if mode == SignalMode::Normal {
+            self.interval = SignalInterval::AllRed;
+            self.target_phase = self.phase_index;
+            self.last_change = tick;
+            self.apply_interval();
         }
-        Ok(())
     }
-}
\ No newline at end of file
+
+    /// Rules drivers follow: a signal out of normal operation is obeyed as the
+    /// stop signs it stands for.
+    pub fn effective_control(&self) -> IntersectionControl {
+        match (self.control, self.mode) {
+            (IntersectionControl::Signalized, SignalMode::FlashingRed | SignalMode::Dark) => IntersectionControl::AllWayStop,
+            (IntersectionControl::Signalized, SignalMode::FlashingAmber { major }) => IntersectionControl::TwoWayStop { major },
+            (control, _) => control,
+        }
+    }
+
+    /// Lamp flashing for traffic travelling in `direction`, if the heads are
+    /// flashing.
+    pub fn flashing_lamp(&self, direction: Direction) -> Option<TrafficLightState> {
+        match self.mode {
+            SignalMode::FlashingRed => Some(TrafficLightState::Red),
+            SignalMode::FlashingAmber { major } if Axis::of(direction) == major => Some(TrafficLightState::Amber),
+            SignalMode::FlashingAmber { .. } => Some(TrafficLightState::Red),
+            SignalMode::Normal | SignalMode::Dark => None,
+        }
+    }
+
+    /// Replaces the signal plan and controller and restarts from the first phase.
+    pub fn set_plan(&mut self, plan: SignalPlan, controller: Box<dyn SignalController>) {
+        let control = self.control;
+        *self = Self::with_plan(plan, controller);
+        self.control = control;
+    }
+
+    /// Signal shown to traffic travelling in `direction`.
+    pub fn state_for(&self, direction: Direction) -> TrafficLightState {
+        match direction {
+            Direction::North => self.north_state,
+            Direction::South => self.south_state,
+            Direction::East => self.east_state,
+            Direction::West => self.west_state,
+        }
+    }
+
+    pub fn arrow_for(&self, direction: Direction) -> Option<TrafficLightState> {
+        match direction {
+            Direction::North => self.north_arrow,
+            Direction::South => self.south_arrow,
+            Direction::East => self.east_arrow,
+            Direction::West => self.west_arrow,
+        }
+    }
+
+    /// Whether the approach in `direction` has a left-turn arrow head at all.
+    pub fn has_left_arrow(&self, direction: Direction) -> bool {
+        self.plan.has_protected_left(direction)
+    }
+
+    /// Signal governing a movement: a lit arrow takes precedence for left turns,
+    /// everything else follows the ball.
+    pub fn signal_for(&self, direction: Direction, turn: Turn) -> TrafficLightState {
+        match (turn, self.arrow_for(direction)) {
+            (Turn::Left, Some(arrow)) => arrow,
+            _ => self.state_for(direction),
+        }
+    }
+}
+
+impl SignalEvent {
+    pub fn describe(&self) -> String {
+        match self {
+            SignalEvent::PreemptionRequested { direction, turn } =>
+                format!("preemption requested by emergency vehicle {} {}", direction.name(), turn.name()),
+            SignalEvent::PreemptionServed { direction } =>
+                format!("preemption green for {}", direction.name()),
+            SignalEvent::PreemptionEnded { direction } =>
+                format!("preemption for {} ended, resuming normal timing", direction.name()),
+            SignalEvent::PreemptionExpired { direction } =>
+                format!("preemption for {} held too long, resuming normal timing", direction.name()),
+            SignalEvent::ModeChanged { mode } =>
+                format!("signals switched to {}", mode.name()),
+        }
+    }
+}
+
+impl SignalMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            SignalMode::Normal => "normal",
+            SignalMode::FlashingRed => "flashing-red",
+            SignalMode::FlashingAmber { major: Axis::EastWest } => "flashing-amber",
+            SignalMode::FlashingAmber { major: Axis::NorthSouth } => "flashing-amber-ns",
+            SignalMode::Dark => "dark",
+        }
+    }
+
+    /// The mode after this one when stepping through them from the keyboard.
+    pub fn next(self) -> Self {
+        match self {
+            SignalMode::Normal => SignalMode::FlashingRed,
+            SignalMode::FlashingRed => SignalMode::FlashingAmber { major: Axis::EastWest },
+            SignalMode::FlashingAmber { .. } => SignalMode::Dark,
+            SignalMode::Dark => SignalMode::Normal,
+        }
+    }
+}
+
+impl std::str::FromStr for SignalMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" => Ok(SignalMode::Normal),
+            "flashing-red" => Ok(SignalMode::FlashingRed),
+            // Amber flashes on the east-west road unless told otherwise
+            "flashing-amber" => Ok(SignalMode::FlashingAmber { major: Axis::EastWest }),
+            "flashing-amber-ns" => Ok(SignalMode::FlashingAmber { major: Axis::NorthSouth }),
+            "dark" => Ok(SignalMode::Dark),
+            _ => Err("expected normal, flashing-red, flashing-amber, flashing-amber-ns or dark".to_string()),
+        }
+    }
+}
+
+impl TryFrom<String> for SignalMode {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl Default for TrafficLightSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vehicle::test_vehicle;
+
+    // Permissive two-phase plan on fixed time: east-west green first
+    fn system() -> TrafficLightSystem {
+        TrafficLightSystem::with_plan(SignalPlan::two_phase(LeftTurnPhasing::Permissive), ControllerKind::FixedTime.build())
+    }
+
+    fn emergency(id: u64, direction: Direction, distance: f32) -> Vehicle {
+        let mut vehicle = test_vehicle(direction, Turn::Straight, distance);
+        vehicle.id = id;
+        vehicle.kind = VehicleKind::Emergency;
+        vehicle
+    }
+
+    // Steps the signals on one-second ticks until `tick`
+    fn run_until(system: &mut TrafficLightSystem, clock: &mut SimClock, vehicles: &[Vehicle], tick: u64) {
+        while clock.tick < tick {
+            clock.advance();
+            system.update(clock, vehicles);
+        }
+    }
+
+    fn events(system: &TrafficLightSystem) -> Vec<(u64, String)> {
+        system.events.iter().map(|entry| (entry.tick, entry.event.describe())).collect()
+    }
+
+    #[test]
+    fn preemption_cuts_a_conflicting_green_short_and_holds_its_own() {
+        let mut system = system();
+        let mut clock = SimClock::new(Duration::from_secs(1));
+        let vehicles = [emergency(7, Direction::North, 20.0)];
+        run_until(&mut system, &mut clock, &vehicles, 1);
+        assert_eq!(system.state_for(Direction::East), TrafficLightState::Amber);
+        run_until(&mut system, &mut clock, &vehicles, 4);
+        assert_eq!(system.state_for(Direction::East), TrafficLightState::Red);
+        assert_eq!(system.state_for(Direction::North), TrafficLightState::Red);
+        run_until(&mut system, &mut clock, &vehicles, 5);
+        assert_eq!(system.state_for(Direction::North), TrafficLightState::Green);
+
+        // Held well past the planned green while the vehicle is still on its way
+        run_until(&mut system, &mut clock, &vehicles, 25);
+        assert_eq!(system.phase_index, 1);
+        run_until(&mut system, &mut clock, &[], 26);
+        assert!(system.preemption.is_none());
+        assert_eq!(system.state_for(Direction::North), TrafficLightState::Amber);
+        assert_eq!(events(&system), [
+            (1, "preemption requested by emergency vehicle north straight".to_string()),
+            (5, "preemption green for north".to_string()),
+            (26, "preemption for north ended, resuming normal timing".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn preemption_is_dropped_when_held_too_long() {
+        let mut system = system();
+        let mut clock = SimClock::new(Duration::from_secs(1));
+        let vehicles = [emergency(7, Direction::East, 20.0)];
+        run_until(&mut system, &mut clock, &vehicles, 40);
+        assert!(system.preemption.is_none());
+        assert_eq!(system.expired_preemptions, [7]);
+        assert_eq!(events(&system), [
+            (1, "preemption requested by emergency vehicle east straight".to_string()),
+            (1, "preemption green for east".to_string()),
+            (31, "preemption for east held too long, resuming normal timing".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn nearest_emergency_vehicle_is_served_first() {
+        let mut system = system();
+        let mut clock = SimClock::new(Duration::from_secs(1));
+        let vehicles = [emergency(3, Direction::East, 20.0), emergency(4, Direction::South, 5.0)];
+        run_until(&mut system, &mut clock, &vehicles, 1);
+        assert_eq!(system.preemption.map(|p| p.vehicle), Some(4));
+    }
+
+    #[test]
+    fn emergency_vehicles_request_only_within_range() {
+        let mut system = system();
+        let mut clock = SimClock::new(Duration::from_secs(1));
+        run_until(&mut system, &mut clock, &[emergency(3, Direction::North, 30.0)], 1);
+        assert!(system.preemption.is_none());
+    }
+
+    #[test]
+    fn push_button_brings_walk_then_clearance_before_green_ends() {
+        let mut system = system();
+        let mut clock = SimClock::new(Duration::from_secs(1));
+        system.press_button(Leg::North);
+        system.press_button(Leg::East);
+        run_until(&mut system, &mut clock, &[], 1);
+        assert_eq!(system.pedestrian_signal(Leg::North), PedestrianSignal::Walk);
+        // East-west traffic enters across the east crosswalk, so it waits for north-south
+        assert_eq!(system.pedestrian_signal(Leg::East), PedestrianSignal::DontWalk);
+        run_until(&mut system, &mut clock, &[], 5);
+        assert_eq!(system.pedestrian_signal(Leg::North), PedestrianSignal::FlashingDontWalk);
+
+        // The planned five seconds of green stretch until the clearance has run
+        run_until(&mut system, &mut clock, &[], 15);
+        assert_eq!(system.pedestrian_signal(Leg::North), PedestrianSignal::DontWalk);
+        assert_eq!(system.state_for(Direction::East), TrafficLightState::Green);
+        run_until(&mut system, &mut clock, &[], 16);
+        assert_eq!(system.state_for(Direction::East), TrafficLightState::Amber);
+        run_until(&mut system, &mut clock, &[], 20);
+        assert_eq!(system.state_for(Direction::North), TrafficLightState::Green);
+        assert_eq!(system.pedestrian_signal(Leg::East), PedestrianSignal::Walk);
+        assert_eq!(system.crosswalks.iter().map(|c| c.walks_served).sum::<u64>(), 2);
+    }
+
+    #[test]
+    fn preemption_cuts_walk_short_but_not_the_clearance() {
+        let mut system = system();
+        let mut clock = SimClock::new(Duration::from_secs(1));
+        system.press_button(Leg::North);
+        run_until(&mut system, &mut clock, &[], 1);
+        let vehicles = [emergency(7, Direction::North, 20.0)];
+        run_until(&mut system, &mut clock, &vehicles, 2);
+        assert_eq!(system.pedestrian_signal(Leg::North), PedestrianSignal::FlashingDontWalk);
+        assert_eq!(system.state_for(Direction::East), TrafficLightState::Green);
+        run_until(&mut system, &mut clock, &vehicles, 12);
+        assert_eq!(system.state_for(Direction::East), TrafficLightState::Green);
+        run_until(&mut system, &mut clock, &vehicles, 13);
+        assert_eq!(system.state_for(Direction::East), TrafficLightState::Amber);
+    }
+
+    #[test]
+    fn flashing_and_dark_signals_are_obeyed_as_stop_signs() {
+        let mut system = system();
+        let expected = [
+            (SignalMode::FlashingRed, IntersectionControl::AllWayStop, Some(TrafficLightState::Red), Some(TrafficLightState::Red)),
+            (
+                SignalMode::FlashingAmber { major: Axis::EastWest },
+                IntersectionControl::TwoWayStop { major: Axis::EastWest },
+                Some(TrafficLightState::Amber),
+                Some(TrafficLightState::Red),
+            ),
+            (SignalMode::Dark, IntersectionControl::AllWayStop, None, None),
+            (SignalMode::Normal, IntersectionControl::Signalized, None, None),
+        ];
+        for (mode, control, east, north) in expected {
+            system.set_mode(mode, 0);
+            assert_eq!(system.effective_control(), control, "{}", mode.name());
+            assert_eq!(system.flashing_lamp(Direction::East), east, "{}", mode.name());
+            assert_eq!(system.flashing_lamp(Direction::North), north, "{}", mode.name());
+        }
+    }
+
+    #[test]
+    fn phases_stand_still_out_of_normal_operation_and_resume_after_all_red() {
+        let mut system = system();
+        let mut clock = SimClock::new(Duration::from_secs(1));
+        system.press_button(Leg::North);
+        system.set_mode(SignalMode::Dark, 0);
+        run_until(&mut system, &mut clock, &[], 30);
+        assert_eq!(system.phase_index, 0);
+        assert_eq!(system.pedestrian_signal(Leg::North), PedestrianSignal::Dark);
+
+        system.set_mode(SignalMode::Normal, 30);
+        assert_eq!(system.state_for(Direction::East), TrafficLightState::Red);
+        run_until(&mut system, &mut clock, &[], 31);
+        assert_eq!(system.state_for(Direction::East), TrafficLightState::Green);
+        assert_eq!(system.pedestrian_signal(Leg::North), PedestrianSignal::DontWalk);
+        // The call made before the outage is still served
+        run_until(&mut system, &mut clock, &[], 32);
+        assert_eq!(system.pedestrian_signal(Leg::North), PedestrianSignal::Walk);
+        assert_eq!(events(&system), [
+            (0, "signals switched to dark".to_string()),
+            (30, "signals switched to normal".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn parses_signal_modes() {
+        let name = |s: &str| s.parse::<SignalMode>().map(SignalMode::name);
+        for mode in ["normal", "flashing-red", "flashing-amber", "flashing-amber-ns", "dark"] {
+            assert_eq!(name(mode), Ok(mode));
+        }
+        assert_eq!(
+            name("off"),
+            Err("expected normal, flashing-red, flashing-amber, flashing-amber-ns or dark".to_string()),
+        );
+        assert_eq!(SignalMode::Dark.next(), SignalMode::Normal);
+        assert_eq!(SignalMode::FlashingRed.next(), SignalMode::FlashingAmber { major: Axis::EastWest });
+    }
+}
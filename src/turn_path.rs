@@ -0,0 +1,145 @@
+// turn_path.rs
+// Paths followed by turning vehicles through the intersection: a quadratic
+// Bézier curve from the approach lane to the exit lane, with its corner where
+// the two lane centre lines cross. Positions along the curve are looked up by
+// distance travelled, so vehicles move along it at their own speed. The front
+// of the vehicle leads along the curve and the rear trails a body length behind
+// on it, so the rear of a long vehicle tracks inside the turn rather than
+// swinging out into the lane beside it.
+use crate::collision::distance;
+
+// Points sampled along the curve for the arc-length table
+const SAMPLES: usize = 32;
+// Refinements of where the rear sits behind the front on the curve, and how
+// close, in metres, to a body length behind it is close enough
+const REAR_STEPS: usize = 3;
+const REAR_TOLERANCE: f32 = 0.01;
+// Sideways acceleration, in metres per second squared, drivers accept in a turn
+const CORNERING_ACCELERATION: f32 = 2.0;
+
+#[derive(Clone, Copy)]
+pub struct TurnPath {
+    start: (f32, f32),
+    corner: (f32, f32),
+    end: (f32, f32),
+    // Length of the body following the curve
+    body_length: f32,
+    // Distance along the curve at each sample, in metres
+    arc_lengths: [f32; SAMPLES + 1],
+    // Fastest speed, in metres per second, at which the tightest bend can be taken
+    pub safe_speed: f32,
+    // Distance the front has covered along the curve so far
+    pub travelled: f32,
+}
+
+impl TurnPath {
+    /// Curve from `start` to `end`, bent toward `corner`, for a body `body_length`
+    /// long. Points are in metres.
+    pub fn new(start: (f32, f32), corner: (f32, f32), end: (f32, f32), body_length: f32) -> Self {
+        let mut path = TurnPath {
+            start,
+            corner,
+            end,
+            body_length,
+            arc_lengths: [0.0; SAMPLES + 1],
+            safe_speed: 0.0,
+            travelled: 0.0,
+        };
+
+        let mut previous = start;
+        let mut tightest_radius = f32::MAX;
+        for i in 1..=SAMPLES {
+            let t = i as f32 / SAMPLES as f32;
+            let point = path.point(t);
+            path.arc_lengths[i] = path.arc_lengths[i - 1] + distance(previous, point);
+            tightest_radius = tightest_radius.min(path.radius(t));
+            previous = point;
+        }
+        path.safe_speed = (CORNERING_ACCELERATION * tightest_radius).sqrt();
+        path
+    }
+
+    pub fn length(&self) -> f32 {
+        self.arc_lengths[SAMPLES]
+    }
+
+    /// Distance the front has left to the end of the curve.
+    pub fn remaining(&self) -> f32 {
+        (self.length() - self.travelled).max(0.0)
+    }
+
+    /// Whether the rear has left the curve too.
+    pub fn is_complete(&self) -> bool {
+        self.travelled >= self.length() + self.body_length
+    }
+
+    /// Centre of the body, and its heading in radians, with the front at the
+    /// distance travelled so far and the rear on the curve a body length behind it.
+    pub fn pose(&self) -> ((f32, f32), f32) {
+        let front = self.point_along(self.travelled);
+        // The body cuts straight across the bend, so the rear sits a little
+        // further back along the curve than the body is long
+        let mut back = self.body_length;
+        let mut rear = self.point_along(self.travelled - back);
+        for _ in 0..REAR_STEPS {
+            let short = self.body_length - distance(front, rear);
+            if short < REAR_TOLERANCE {
+                break;
+            }
+            back += short;
+            rear = self.point_along(self.travelled - back);
+        }
+        (((front.0 + rear.0) / 2.0, (front.1 + rear.1) / 2.0), (front.1 - rear.1).atan2(front.0 - rear.0))
+    }
+
+    // Point `distance` along the curve, carried on in a straight line before its
+    // start and beyond its end
+    fn point_along(&self, distance: f32) -> (f32, f32) {
+        let (from, t, beyond) = if distance < 0.0 {
+            (self.start, 0.0, distance)
+        } else if distance > self.length() {
+            (self.end, 1.0, distance - self.length())
+        } else {
+            return self.point(self.parameter_at(distance));
+        };
+        let (dx, dy) = self.tangent(t);
+        let norm = (dx * dx + dy * dy).sqrt();
+        (from.0 + beyond * dx / norm, from.1 + beyond * dy / norm)
+    }
+
+    // Curve parameter at `distance` along the curve, interpolated between samples
+    fn parameter_at(&self, distance: f32) -> f32 {
+        let i = self.arc_lengths.partition_point(|&length| length < distance).clamp(1, SAMPLES);
+        let (before, after) = (self.arc_lengths[i - 1], self.arc_lengths[i]);
+        let within = if after > before { (distance - before) / (after - before) } else { 0.0 };
+        (i as f32 - 1.0 + within) / SAMPLES as f32
+    }
+
+    fn point(&self, t: f32) -> (f32, f32) {
+        let u = 1.0 - t;
+        (
+            u * u * self.start.0 + 2.0 * u * t * self.corner.0 + t * t * self.end.0,
+            u * u * self.start.1 + 2.0 * u * t * self.corner.1 + t * t * self.end.1,
+        )
+    }
+
+    fn tangent(&self, t: f32) -> (f32, f32) {
+        let u = 1.0 - t;
+        (
+            2.0 * u * (self.corner.0 - self.start.0) + 2.0 * t * (self.end.0 - self.corner.0),
+            2.0 * u * (self.corner.1 - self.start.1) + 2.0 * t * (self.end.1 - self.corner.1),
+        )
+    }
+
+    // Radius of curvature at `t`
+    fn radius(&self, t: f32) -> f32 {
+        let (dx, dy) = self.tangent(t);
+        let ddx = 2.0 * (self.start.0 - 2.0 * self.corner.0 + self.end.0);
+        let ddy = 2.0 * (self.start.1 - 2.0 * self.corner.1 + self.end.1);
+        let cross = (dx * ddy - dy * ddx).abs();
+        if cross == 0.0 {
+            return f32::MAX;
+        }
+        (dx * dx + dy * dy).powf(1.5) / cross
+    }
+}
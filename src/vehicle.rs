@@ -1,23 +1,55 @@
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
-use sdl2::render::Canvas;
-use sdl2::video::Window;
+use std::f32::consts::{FRAC_PI_2, PI, SQRT_2};
 use rand::Rng;
-use crate::window::{WINDOW_WIDTH, WINDOW_HEIGHT, CENTER_X, CENTER_Y};
-use crate::traffic_light::{TrafficLightSystem, TrafficLightState, ROAD_WIDTH};
+use serde::Deserialize;
+use crate::car_following::{CarFollowingModel, Leader, KRAUSS_DAWDLE};
+use crate::collision::{distance, Body};
+use crate::driver::Driver;
+use crate::lane_change::NO_CHANGE_ZONE;
+use crate::lanes::{Approach, Lane};
+use crate::pedestrian::{Leg, Pedestrian, STOP_LINE_OFFSET};
+use crate::simulation::{WORLD_WIDTH, WORLD_HEIGHT, CENTER_X, CENTER_Y};
+use crate::perception::SignalView;
+use crate::routes::Route;
+use crate::traffic_light::{TrafficLightState, ROAD_WIDTH};
+use crate::turn_path::TurnPath;
 
-pub const LANE_WIDTH: u32 = ROAD_WIDTH / 2;
-const INTERSECTION_MARGIN: i32 = 15;
-const SAFETY_DISTANCE: i32 = 45;
-const TURN_EXECUTION_ZONE: i32 = 5;
+/// Width of one lane, in metres.
+pub const LANE_WIDTH: f32 = 2.5;
+const INTERSECTION_MARGIN: f32 = 1.5;
+const TURN_EXECUTION_ZONE: f32 = 0.5;
+// Vehicles this far upstream of the road edge count as approaching the intersection
+const APPROACH_DISTANCE: f32 = 8.0;
+// Ordinary vehicles give way to an emergency vehicle this close behind them
+const EMERGENCY_YIELD_DISTANCE: f32 = 20.0;
+// How far a yielding vehicle pulls toward the lane edge, a full lane so that the
+// widest vehicle clears an emergency vehicle passing in its lane
+const PULL_OVER_OFFSET: f32 = LANE_WIDTH;
+// Room a yielding vehicle leaves beside a vehicle alongside it on the kerb side
+const PULL_OVER_CLEARANCE: f32 = 0.3;
+// Speed at which vehicles enter the world, in metres per second, unless their
+// class is slower
+const ENTRY_SPEED: f32 = 6.0;
+// A braking vehicle slower than this, in metres per second, comes to a standstill
+const STANDSTILL_SPEED: f32 = 0.1;
+// Drivers react to a light that requires them to stop this far beyond their braking distance
+const SIGNAL_LOOKAHEAD: f32 = 3.0;
+// Sideways speed when moving across to a lane or the kerb, in metres per second
+const LATERAL_SPEED: f32 = 1.5;
+// A vehicle this close to its lane centre counts as in its lane
+const LATERAL_TOLERANCE: f32 = 0.01;
+// Steps, in metres, in which a long vehicle's turn is moved out from the centre line
+const SWING_STEP: f32 = 0.25;
+// Spacing of the poses checked along a turn
+const SWING_SAMPLE: f32 = 0.5;
 
-// Lane center positions
-const NORTHBOUND_LANE_CENTER: i32 = CENTER_X - LANE_WIDTH as i32 / 4;
-const SOUTHBOUND_LANE_CENTER: i32 = CENTER_X + LANE_WIDTH as i32 / 4;
-const EASTBOUND_LANE_CENTER: i32 = CENTER_Y + LANE_WIDTH as i32 / 4;
-const WESTBOUND_LANE_CENTER: i32 = CENTER_Y - LANE_WIDTH as i32 / 4;
+// Centres of the lanes beside the centre line, which turning traffic exits into
+const NORTHBOUND_LANE_CENTER: f32 = CENTER_X - LANE_WIDTH / 2.0;
+const SOUTHBOUND_LANE_CENTER: f32 = CENTER_X + LANE_WIDTH / 2.0;
+const EASTBOUND_LANE_CENTER: f32 = CENTER_Y + LANE_WIDTH / 2.0;
+const WESTBOUND_LANE_CENTER: f32 = CENTER_Y - LANE_WIDTH / 2.0;
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Direction {
     North,
     South,
@@ -25,595 +57,1125 @@ pub enum Direction {
     West,
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Turn {
     Left,
     Right,
     Straight,
 }
 
+impl Direction {
+    pub const ALL: [Direction; 4] = [Direction::North, Direction::South, Direction::East, Direction::West];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Direction::North => "north",
+            Direction::South => "south",
+            Direction::East => "east",
+            Direction::West => "west",
+        }
+    }
+
+    /// Cross-road coordinate of the centre of the lane beside the centre line
+    /// travelling in this direction.
+    pub fn lane_center(self) -> f32 {
+        match self {
+            Direction::North => NORTHBOUND_LANE_CENTER,
+            Direction::South => SOUTHBOUND_LANE_CENTER,
+            Direction::East => EASTBOUND_LANE_CENTER,
+            Direction::West => WESTBOUND_LANE_CENTER,
+        }
+    }
+
+    /// Unit vector across the road from the centre line toward the lane.
+    fn away_from_centre_line(self) -> (f32, f32) {
+        match self {
+            Direction::North | Direction::South => ((self.lane_center() - CENTER_X).signum(), 0.0),
+            Direction::East | Direction::West => (0.0, (self.lane_center() - CENTER_Y).signum()),
+        }
+    }
+
+    /// Direction of travel after making `turn` at the intersection.
+    pub fn turned(self, turn: Turn) -> Direction {
+        match (self, turn) {
+            (_, Turn::Straight) => self,
+            (Direction::North, Turn::Left) | (Direction::South, Turn::Right) => Direction::West,
+            (Direction::North, Turn::Right) | (Direction::South, Turn::Left) => Direction::East,
+            (Direction::East, Turn::Left) | (Direction::West, Turn::Right) => Direction::North,
+            (Direction::East, Turn::Right) | (Direction::West, Turn::Left) => Direction::South,
+        }
+    }
+
+    /// Heading of travel in radians, measured from east toward south.
+    pub fn heading(self) -> f32 {
+        match self {
+            Direction::East => 0.0,
+            Direction::South => FRAC_PI_2,
+            Direction::West => PI,
+            Direction::North => -FRAC_PI_2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VehicleKind {
+    #[default]
+    Car,
+    Truck,
+    Bus,
+    Motorcycle,
+    Bicycle,
+    /// Preempts the signal and is given way to by ordinary traffic.
+    Emergency,
+}
+
+impl VehicleKind {
+    pub const ALL: [VehicleKind; 6] = [
+        VehicleKind::Car,
+        VehicleKind::Truck,
+        VehicleKind::Bus,
+        VehicleKind::Motorcycle,
+        VehicleKind::Bicycle,
+        VehicleKind::Emergency,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            VehicleKind::Car => "car",
+            VehicleKind::Truck => "truck",
+            VehicleKind::Bus => "bus",
+            VehicleKind::Motorcycle => "motorcycle",
+            VehicleKind::Bicycle => "bicycle",
+            VehicleKind::Emergency => "emergency",
+        }
+    }
+}
+
+impl std::str::FromStr for VehicleKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        VehicleKind::ALL.into_iter()
+            .find(|kind| kind.name() == s)
+            .ok_or_else(|| format!("unknown vehicle kind '{}'", s))
+    }
+}
+
 #[derive(Clone, Copy, PartialEq)]
 pub enum StopReason {
     None,
     TrafficLight,
     VehicleAhead,
     IntersectionConflict,
+    EmergencyVehicle,
+    Pedestrian,
+    // Waiting for a gap to move into a lane that allows the turn
+    LaneChange,
 }
 
+/// A vehicle on the road. Positions and sizes are in metres, speeds in metres
+/// per second and accelerations in metres per second squared.
 #[derive(Clone)]
 pub struct Vehicle {
-    pub x: i32,
-    pub y: i32,
+    // Assigned by the simulation at spawn, unique within a run
+    pub id: u64,
+    // Top-left corner of the footprint, the axis-aligned box around the body
+    pub x: f32,
+    pub y: f32,
+    pub kind: VehicleKind,
     pub direction: Direction,
     pub turn: Turn,
-    pub width: u32,
-    pub height: u32,
-    pub color: Color,
+    // Legs the vehicle set out to enter and leave by, fixed at spawn
+    pub route: Route,
+    pub lane: Lane,
+    // Extent of the footprint along x and y
+    pub width: f32,
+    pub height: f32,
+    pub body_length: f32,
+    pub body_width: f32,
+    // Direction the body faces, in radians from east toward south
+    pub heading: f32,
+    // Curve followed through the intersection while turning
+    pub path: Option<TurnPath>,
+    // Halting, or halted, for `stop_reason`
     pub stopped: bool,
     pub stop_reason: StopReason,
-    pub max_speed: i32,
-    pub current_speed: i32,
-    pub acceleration: i32,
-    pub deceleration: i32,
-    pub following_distance: i32,
-    pub arrival_time: Option<u64>,
-    pub global_tick: u32,
+    // Room left to halt in while `stopped`
+    pub stop_distance: f32,
+    pub car_following: CarFollowingModel,
+    // Nearest vehicle ahead in the lane
+    pub leader: Option<Leader>,
+    // Id of the vehicle this one is queued behind while stopped for it
+    pub queued_behind: Option<u64>,
+    pub driver: Driver,
+    // Desired speed on an open road, the class's top speed as the driver sees fit
+    pub max_speed: f32,
+    pub current_speed: f32,
+    pub acceleration: f32,
+    pub comfortable_deceleration: f32,
+    pub max_deceleration: f32,
+    pub time_headway: f32,
+    pub minimum_gap: f32,
+    // Tightest radius the vehicle can turn on
+    pub turn_radius: f32,
+    // Set by the reservation manager once the cells the vehicle crosses are its own
+    pub reservation_granted: bool,
+    // Set by the reservation manager to how far the vehicle can go before running
+    // into the way of a movement from another approach that swings out of the box
+    pub room_before_crossing: Option<f32>,
+    pub spawn_tick: u64,
     pub has_turned: bool,
     pub turn_executed: bool,
-    pub target_x: i32,
-    pub target_y: i32,
+    pub target_x: f32,
+    pub target_y: f32,
+    pub yielding_to_emergency: bool,
+    // Current lateral offset toward the lane edge while yielding
+    pub pull_over: f32,
 }
 
-impl Vehicle {
-    pub fn new(direction: Direction) -> Self {
-        let (x, y, width, height, target_x, target_y) = match direction {
-            Direction::North => (
-                NORTHBOUND_LANE_CENTER - 10,
-                WINDOW_HEIGHT as i32 + 50,
-                20,
-                40,
-                NORTHBOUND_LANE_CENTER - 10,
-                i32::MAX,
-            ),
-            Direction::South => (
-                SOUTHBOUND_LANE_CENTER - 10,
-                -50,
-                20,
-                40,
-                SOUTHBOUND_LANE_CENTER - 10,
-                i32::MAX,
-            ),
-            Direction::East => (
-                -50,
-                EASTBOUND_LANE_CENTER - 10,
-                40,
-                20,
-                i32::MAX,
-                EASTBOUND_LANE_CENTER - 10,
-            ),
-            Direction::West => (
-                WINDOW_WIDTH as i32 + 50,
-                WESTBOUND_LANE_CENTER - 10,
-                40,
-                20,
-                i32::MAX,
-                WESTBOUND_LANE_CENTER - 10,
-            ),
+/// What other drivers take in of a vehicle: where it is and how fast it is
+/// going, the lane it is in and the turn it is making.
+#[derive(Clone, Copy)]
+pub struct Glimpse {
+    id: u64,
+    kind: VehicleKind,
+    direction: Direction,
+    turn: Turn,
+    // Centre of the lane the vehicle keeps to or is moving into
+    lane_center: f32,
+    // Footprint, as on the vehicle
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    heading: f32,
+    body_length: f32,
+    body_width: f32,
+    current_speed: f32,
+    pull_over: f32,
+    turn_executed: bool,
+    // Distance travelled along the turn path while on it
+    turn_travelled: Option<f32>,
+}
+
+impl Glimpse {
+    pub fn center(&self) -> (f32, f32) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+
+    /// Extent of the vehicle along its direction of travel.
+    pub fn length(&self) -> f32 {
+        match self.direction {
+            Direction::North | Direction::South => self.height,
+            Direction::East | Direction::West => self.width,
+        }
+    }
+
+    /// Cross-road coordinate of the vehicle's centre.
+    pub fn lateral_center(&self) -> f32 {
+        match self.direction {
+            Direction::North | Direction::South => self.x + self.width / 2.0,
+            Direction::East | Direction::West => self.y + self.height / 2.0,
+        }
+    }
+
+    /// Whether the two vehicles overlap across the road, so one would run into
+    /// the other. A vehicle part-way through a lane change blocks both lanes.
+    pub fn same_lane(&self, other: &Glimpse) -> bool {
+        let overlap = match self.direction {
+            Direction::North | Direction::South => (self.width + other.width) / 2.0,
+            Direction::East | Direction::West => (self.height + other.height) / 2.0,
         };
+        (self.lateral_center() - other.lateral_center()).abs() < overlap
+    }
+
+    /// Extent of the vehicle across its direction of travel.
+    pub fn breadth(&self) -> f32 {
+        match self.direction {
+            Direction::North | Direction::South => self.width,
+            Direction::East | Direction::West => self.height,
+        }
+    }
+
+    // Whether a vehicle centred in the lane at `center` would overlap this one
+    // across the road, whether or not it has pulled over
+    fn lane_holds(&self, center: f32) -> bool {
+        (self.lateral_center() - center).abs() < (self.breadth() + LANE_WIDTH) / 2.0
+    }
+
+    // Whether `other` makes the same turn from the same lane
+    fn same_turn(&self, other: &Glimpse) -> bool {
+        self.turn == other.turn && self.turn != Turn::Straight &&
+        (self.lane_center - other.lane_center).abs() < LATERAL_TOLERANCE
+    }
+
+    // Leg the vehicle is on, or turning onto while on its turn path
+    fn leg(&self) -> Direction {
+        match self.turn_travelled {
+            Some(_) => self.direction.turned(self.turn),
+            None => self.direction,
+        }
+    }
+
+    fn body(&self) -> Body {
+        Body { center: self.center(), heading: self.heading, length: self.body_length, width: self.body_width }
+    }
+
+    // Gap from the front of `other` to the nearest part of this vehicle ahead of
+    // it, when `other` comes from another approach onto the leg this one is on
+    // or turning onto, while one of the two is still part-way round its turn.
+    // A turning body is still sliding across the leg, so anything within a lane
+    // of the one behind counts as ahead of it
+    fn merge_gap_behind(&self, other: &Glimpse) -> Option<f32> {
+        let leg = self.leg();
+        if other.leg() != leg || (self.turn_travelled.is_none() && other.turn_travelled.is_none()) {
+            return None;
+        }
+        let (sin, cos) = leg.heading().sin_cos();
+        let behind = Body { width: other.body_width + LANE_WIDTH, ..other.body() };
+        behind.gap_ahead(&self.body(), (cos, sin))
+    }
+
+    /// Gap from the front of `other` to the rear of this vehicle, when `other` follows it.
+    pub fn gap_behind(&self, other: &Glimpse) -> f32 {
+        match self.direction {
+            Direction::North => other.y - self.y - self.height,
+            Direction::South => self.y - other.y - other.height,
+            Direction::East => self.x - other.x - other.width,
+            Direction::West => other.x - self.x - self.width,
+        }
+    }
+}
 
-        let turn = Turn::random();
-        let color = match turn {
-            Turn::Left => Color::RGB(255, 0, 0),
-            Turn::Right => Color::RGB(0, 255, 0),
-            Turn::Straight => Color::RGB(0, 0, 255),
+/// Where a vehicle is and which way it faces.
+#[derive(Clone, Copy)]
+pub struct Placement {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    heading: f32,
+    direction: Direction,
+    path: Option<TurnPath>,
+    has_turned: bool,
+}
+
+impl Vehicle {
+    /// Creates a vehicle following `route` in one of the full-length lanes at the
+    /// entry of `approach`.
+    pub fn new(route: Route, kind: VehicleKind, driver: Driver, approach: &Approach, spawn_tick: u64, rng: &mut impl Rng) -> Self {
+        let direction = route.direction();
+        let turn = route.turn();
+        let lane = approach.entry_lane(turn, rng);
+        let class = kind.class();
+        let entry = lane.center - class.width / 2.0;
+
+        let (x, y, width, height, target_x, target_y) = match direction {
+            Direction::North => (entry, WORLD_HEIGHT + 5.0, class.width, class.length, entry, f32::MAX),
+            Direction::South => (entry, -5.0, class.width, class.length, entry, f32::MAX),
+            Direction::East => (-5.0, entry, class.length, class.width, f32::MAX, entry),
+            Direction::West => (WORLD_WIDTH + 5.0, entry, class.length, class.width, f32::MAX, entry),
         };
 
         Vehicle {
+            id: 0,
             x,
             y,
+            kind,
             direction,
             turn,
+            route,
+            lane,
             width,
             height,
-            color,
+            body_length: class.length,
+            body_width: class.width,
+            heading: direction.heading(),
+            path: None,
             stopped: false,
             stop_reason: StopReason::None,
-            max_speed: 3,
-            current_speed: 1,
-            acceleration: 1,
-            deceleration: 2,
-            following_distance: 50,
-            arrival_time: None,
-            global_tick: 0,
+            stop_distance: f32::MAX,
+            car_following: CarFollowingModel::default(),
+            leader: None,
+            queued_behind: None,
+            driver,
+            max_speed: class.max_speed * driver.speed_factor,
+            current_speed: ENTRY_SPEED.min(class.max_speed * driver.speed_factor),
+            acceleration: class.acceleration,
+            comfortable_deceleration: class.comfortable_deceleration,
+            max_deceleration: class.max_deceleration,
+            time_headway: class.time_headway,
+            minimum_gap: class.minimum_gap,
+            turn_radius: class.turn_radius,
+            reservation_granted: false,
+            room_before_crossing: None,
+            spawn_tick,
             has_turned: false,
             turn_executed: false,
             target_x,
             target_y,
+            yielding_to_emergency: false,
+            pull_over: 0.0,
         }
     }
 
+    /// Re-evaluates every reason to stop from scratch, from the vehicles and
+    /// lights as the driver sees them.
     pub fn check_vehicles_ahead(
         &mut self,
-        vehicles: &[Vehicle],
-        current_index: usize,
-        traffic_system: &TrafficLightSystem
+        vehicles: &[Glimpse],
+        signals: &SignalView
     ) {
-        if self.stop_reason == StopReason::VehicleAhead || 
-           self.stop_reason == StopReason::IntersectionConflict {
-            self.stopped = false;
-            self.stop_reason = StopReason::None;
-        }
+        self.stopped = false;
+        self.stop_reason = StopReason::None;
+        self.stop_distance = f32::MAX;
+        self.leader = None;
+        self.queued_behind = None;
 
-        self.check_same_direction_vehicles(vehicles, current_index);
-        self.check_intersection_conflicts(vehicles, current_index);
-        self.check_traffic_light(traffic_system);
+        self.check_same_direction_vehicles(vehicles);
+        self.check_lane_for_turn();
+        self.check_intersection_conflicts();
+        self.check_traffic_light(signals);
     }
 
-    fn check_same_direction_vehicles(&mut self, vehicles: &[Vehicle], current_index: usize) {
-        let mut closest_distance = i32::MAX;
-        let mut closest_vehicle_stopped = false;
+    // Waits short of the stop line in a lane that does not allow the turn, for a
+    // gap to move into one that does, rather than miss the route
+    fn check_lane_for_turn(&mut self) {
+        let distance = self.distance_to_stop_line() - NO_CHANGE_ZONE;
+        let in_range = distance < self.braking_distance() + SIGNAL_LOOKAHEAD;
+        if !self.turn_executed && !self.lane.lane_use.allows(self.turn) && in_range && distance >= 0.0 {
+            self.hold(StopReason::LaneChange, distance);
+        }
+    }
 
-        for (i, other) in vehicles.iter().enumerate() {
-            if i == current_index || other.direction != self.direction {
-                continue;
-            }
+    // Brakes to a halt `distance` ahead for `reason`, unless already halting sooner
+    fn hold(&mut self, reason: StopReason, distance: f32) {
+        if !self.stopped || distance <= self.stop_distance {
+            self.stopped = true;
+            self.stop_reason = reason;
+            self.stop_distance = distance;
+        }
+    }
 
-            let same_lane = match self.direction {
-                Direction::North | Direction::South => 
-                    (self.x - other.x).abs() < LANE_WIDTH as i32 / 2,
-                Direction::East | Direction::West => 
-                    (self.y - other.y).abs() < LANE_WIDTH as i32 / 2,
-            };
+    // Follows the nearest vehicle ahead in `vehicles`, unless a nearer leader is
+    // already being followed
+    fn check_same_direction_vehicles(&mut self, vehicles: &[Glimpse]) {
+        let me = self.glimpse();
+        let mut leader_id = self.queued_behind;
 
-            if !same_lane {
+        for other in vehicles {
+            if other.id == self.id {
                 continue;
             }
 
-            let distance = match self.direction {
-                Direction::North => self.y - other.y - other.height as i32,
-                Direction::South => other.y - self.y - self.height as i32,
-                Direction::East => other.x - self.x - self.width as i32,
-                Direction::West => self.x - other.x - other.width as i32,
+            // A vehicle moving across follows those in the lane it is moving into
+            // as well as those it overlaps. An emergency vehicle passes those
+            // pulled over out of its way; anyone else queues behind them as if
+            // they were still in lane, and behind any they overlap
+            let in_lane = if self.kind == VehicleKind::Emergency || other.pull_over == 0.0 {
+                me.same_lane(other) || other.lane_holds(self.lane.center)
+            } else {
+                me.same_lane(other) || me.lane_holds(other.lane_center)
             };
 
-            if distance > 0 && distance < closest_distance {
-                closest_distance = distance;
-                closest_vehicle_stopped = other.stopped;
+            // A vehicle ahead on the same turn is followed along the curve, or in a
+            // straight line where that is nearer, as a long body cuts the corner
+            let gap = match other.turn_travelled {
+                // Traffic from another approach is followed where the two merge
+                // onto the same leg
+                _ if other.direction != self.direction => match other.merge_gap_behind(&me) {
+                    Some(gap) => gap,
+                    None => continue,
+                },
+                Some(travelled) if me.same_turn(other) =>
+                    (travelled - other.body_length - self.turn_progress())
+                        .min(distance(self.center(), other.center()) - (self.body_length + other.body_length) / 2.0),
+                _ if in_lane => other.gap_behind(&me),
+                _ => continue,
+            };
+            if gap > 0.0 && self.leader.is_none_or(|leader| gap < leader.gap) {
+                self.leader = Some(Leader { gap, speed: other.current_speed });
+                leader_id = Some(other.id);
             }
         }
 
-        if closest_distance < self.following_distance {
-            if closest_vehicle_stopped || closest_distance < 20 {
-                self.current_speed = 0;
-                self.stopped = true;
-                self.stop_reason = StopReason::VehicleAhead;
-                
-                if closest_distance < 10 {
-                    match self.direction {
-                        Direction::North => self.y += 1,
-                        Direction::South => self.y -= 1,
-                        Direction::East => self.x -= 1,
-                        Direction::West => self.x += 1,
-                    }
-                }
-            } else {
-                let desired_speed = (closest_distance as f32 * 0.8).min(self.max_speed as f32) as i32;
-                self.current_speed = desired_speed.max(1);
-            }
-        } else {
-            self.current_speed = (self.current_speed + self.acceleration).min(self.max_speed);
+        // Queueing behind a vehicle standing close ahead
+        if let Some(leader) = self.leader && leader.speed == 0.0 && leader.gap < self.following_distance() {
+            self.hold(StopReason::VehicleAhead, leader.room(self).max(0.0));
+            self.queued_behind = leader_id;
         }
     }
 
-    fn check_intersection_conflicts(&mut self, vehicles: &[Vehicle], current_index: usize) {
-        if self.stop_reason == StopReason::TrafficLight || !self.approaching_intersection() {
-            if !self.approaching_intersection() {
-                self.arrival_time = None;
-            }
-            return;
+    // Gap at which a vehicle standing ahead starts to hold this one up
+    fn following_distance(&self) -> f32 {
+        self.minimum_gap + self.max_speed * self.time_headway
+    }
+
+    /// Acceleration the car-following model gives behind `leader`, or on an open
+    /// road without one.
+    pub fn acceleration_behind(&self, leader: Option<&Vehicle>) -> f32 {
+        let leader = leader.map(|leader| Leader { gap: leader.gap_behind(self), speed: leader.current_speed });
+        self.car_following.acceleration(self, leader)
+    }
+
+    // Waits at the stop line, or where the vehicle is once past it, until the
+    // reservation manager grants it the cells it will cross, and short of where
+    // another vehicle's granted movement passes
+    fn check_intersection_conflicts(&mut self) {
+        if self.stop_reason != StopReason::TrafficLight && self.needs_reservation() && !self.reservation_granted {
+            self.hold(StopReason::IntersectionConflict, self.distance_to_stop_line().max(0.0));
         }
-        
-        if self.arrival_time.is_none() {
-            self.arrival_time = Some(self.global_tick as u64);
+        if let Some(room) = self.room_before_crossing {
+            self.hold(StopReason::IntersectionConflict, room);
         }
-        
-        let mut should_stop = false;
-        
-        for (i, other) in vehicles.iter().enumerate() {
-            if i == current_index || !other.approaching_intersection() && !other.in_intersection_area() {
-                continue;
-            }
-            
-            if other.stop_reason == StopReason::TrafficLight {
-                continue;
-            }
-            
-            if self.will_collide(other) {
-                match (self.arrival_time, other.arrival_time) {
-                    (Some(self_time), Some(other_time)) => {
-                        if self_time > other_time {
-                            should_stop = true;
-                        } else if self_time == other_time {
-                            should_stop = self.should_yield_to(other);
-                        }
-                    },
-                    (None, Some(_)) => should_stop = true,
-                    (Some(_), None) => should_stop = false,
-                    _ => should_stop = self.should_yield_to(other),
-                }
-                
-                if should_stop {
-                    break;
-                }
-            }
+    }
+
+    /// Whether the vehicle is close enough to the box that it needs a reservation
+    /// to go on: within the approach distance, near enough that it would have to
+    /// start braking for the stop line, or already over the edge of the box
+    /// without having started its turn.
+    pub fn needs_reservation(&self) -> bool {
+        let horizon = APPROACH_DISTANCE.max(self.braking_distance() + self.current_speed * self.time_headway);
+        !self.turn_executed && self.distance_to_box() <= horizon
+    }
+
+    /// Whether the vehicle is at a standstill, or creeping too slowly to count.
+    pub fn is_standing(&self) -> bool {
+        self.current_speed < STANDSTILL_SPEED
+    }
+
+    /// Whether any part of the body is over the box.
+    pub fn in_box(&self) -> bool {
+        let half = ROAD_WIDTH / 2.0;
+        self.x < CENTER_X + half && self.x + self.width > CENTER_X - half &&
+        self.y < CENTER_Y + half && self.y + self.height > CENTER_Y - half
+    }
+    
+    pub fn distance_from_center(&self) -> f32 {
+        let (x, y) = self.center();
+        let (dx, dy) = (x - CENTER_X, y - CENTER_Y);
+        (dx * dx + dy * dy).sqrt()
+    }
+
+    pub fn center(&self) -> (f32, f32) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+
+    /// World position of a point on the body, given `along` its length toward the
+    /// front and `across` its width toward the right, in metres from its centre.
+    pub fn body_point(&self, along: f32, across: f32) -> (f32, f32) {
+        let (x, y) = self.center();
+        let (sin, cos) = self.heading.sin_cos();
+        (x + along * cos - across * sin, y + along * sin + across * cos)
+    }
+
+    /// The body as an oriented box, rotated to the vehicle's heading mid-turn.
+    pub fn body(&self) -> Body {
+        Body {
+            center: self.center(),
+            heading: self.heading,
+            length: self.body_length,
+            width: self.body_width,
         }
-        
-        if should_stop {
-            self.current_speed = 0;
-            self.stopped = true;
-            self.stop_reason = StopReason::IntersectionConflict;
-        } else if self.stop_reason == StopReason::IntersectionConflict {
-            self.stopped = false;
-            self.stop_reason = StopReason::None;
-        }
-    }
-
-    fn should_yield_to(&self, other: &Vehicle) -> bool {
-        match (self.direction, other.direction) {
-            (Direction::North, Direction::East) => true,
-            (Direction::East, Direction::South) => true,
-            (Direction::South, Direction::West) => true,
-            (Direction::West, Direction::North) => true,
-            
-            (Direction::North, Direction::West) => false,
-            (Direction::East, Direction::North) => false,
-            (Direction::South, Direction::East) => false,
-            (Direction::West, Direction::South) => false,
-            
-            (Direction::North, Direction::South) | (Direction::South, Direction::North) |
-            (Direction::East, Direction::West) | (Direction::West, Direction::East) => {
-                match (self.turn, other.turn) {
-                    (Turn::Left, Turn::Straight) | (Turn::Left, Turn::Right) => true,
-                    (Turn::Straight, Turn::Right) => true,
-                    (Turn::Right, _) => false,
-                    _ => self.distance_from_center() > other.distance_from_center()
-                }
-            },
-            
-            _ => self.distance_from_center() > other.distance_from_center()
-        }
-    }
-
-    fn approaching_intersection(&self) -> bool {
-        match self.direction {
-            Direction::North => self.y <= CENTER_Y + ROAD_WIDTH as i32 / 2 + 80 &&
-                               self.y > CENTER_Y + ROAD_WIDTH as i32 / 2,
-            Direction::South => self.y >= CENTER_Y - ROAD_WIDTH as i32 / 2 - 80 &&
-                               self.y < CENTER_Y - ROAD_WIDTH as i32 / 2,
-            Direction::East => self.x <= CENTER_X + ROAD_WIDTH as i32 / 2 + 80 &&
-                              self.x > CENTER_X + ROAD_WIDTH as i32 / 2,
-            Direction::West => self.x >= CENTER_X - ROAD_WIDTH as i32 / 2 - 80 &&
-                              self.x < CENTER_X - ROAD_WIDTH as i32 / 2,
+    }
+
+    // Centres the body on `center`, facing `heading`, and sizes the footprint around it
+    fn set_pose(&mut self, center: (f32, f32), heading: f32) {
+        let (sin, cos) = heading.sin_cos();
+        self.heading = heading;
+        self.width = cos.abs() * self.body_length + sin.abs() * self.body_width;
+        self.height = sin.abs() * self.body_length + cos.abs() * self.body_width;
+        self.x = center.0 - self.width / 2.0;
+        self.y = center.1 - self.height / 2.0;
+    }
+
+    /// Saves where the vehicle is, so a move that ran into another can be cut short.
+    pub fn placement(&self) -> Placement {
+        Placement {
+            x: self.x,
+            y: self.y,
+            width: self.width,
+            height: self.height,
+            heading: self.heading,
+            direction: self.direction,
+            path: self.path,
+            has_turned: self.has_turned,
         }
     }
-    
-    fn distance_from_center(&self) -> f32 {
-        let dx = self.x + self.width as i32 / 2 - CENTER_X;
-        let dy = self.y + self.height as i32 / 2 - CENTER_Y;
-        ((dx * dx + dy * dy) as f32).sqrt()
+
+    pub fn restore(&mut self, placement: Placement) {
+        self.x = placement.x;
+        self.y = placement.y;
+        self.width = placement.width;
+        self.height = placement.height;
+        self.heading = placement.heading;
+        self.direction = placement.direction;
+        self.path = placement.path;
+        self.has_turned = placement.has_turned;
     }
 
     fn in_intersection_area(&self) -> bool {
         match self.direction {
-            Direction::North => self.y <= CENTER_Y + ROAD_WIDTH as i32 / 2 + INTERSECTION_MARGIN &&
-                               self.y >= CENTER_Y - ROAD_WIDTH as i32 / 2 - INTERSECTION_MARGIN,
-            Direction::South => self.y >= CENTER_Y - ROAD_WIDTH as i32 / 2 - INTERSECTION_MARGIN &&
-                               self.y <= CENTER_Y + ROAD_WIDTH as i32 / 2 + INTERSECTION_MARGIN,
-            Direction::East => self.x <= CENTER_X + ROAD_WIDTH as i32 / 2 + INTERSECTION_MARGIN &&
-                              self.x >= CENTER_X - ROAD_WIDTH as i32 / 2 - INTERSECTION_MARGIN,
-            Direction::West => self.x >= CENTER_X - ROAD_WIDTH as i32 / 2 - INTERSECTION_MARGIN &&
-                              self.x <= CENTER_X + ROAD_WIDTH as i32 / 2 + INTERSECTION_MARGIN,
+            Direction::North => self.y <= CENTER_Y + ROAD_WIDTH / 2.0 + INTERSECTION_MARGIN &&
+                               self.y >= CENTER_Y - ROAD_WIDTH / 2.0 - INTERSECTION_MARGIN,
+            Direction::South => self.y >= CENTER_Y - ROAD_WIDTH / 2.0 - INTERSECTION_MARGIN &&
+                               self.y <= CENTER_Y + ROAD_WIDTH / 2.0 + INTERSECTION_MARGIN,
+            Direction::East => self.x <= CENTER_X + ROAD_WIDTH / 2.0 + INTERSECTION_MARGIN &&
+                              self.x >= CENTER_X - ROAD_WIDTH / 2.0 - INTERSECTION_MARGIN,
+            Direction::West => self.x >= CENTER_X - ROAD_WIDTH / 2.0 - INTERSECTION_MARGIN &&
+                              self.x <= CENTER_X + ROAD_WIDTH / 2.0 + INTERSECTION_MARGIN,
         }
     }
 
-    fn will_collide(&self, other: &Vehicle) -> bool {
-        if self.in_intersection_area() && self.current_speed > 0 {
-            return false;
-        }
-        
-        if self.stopped && other.stopped && 
-           self.stop_reason == StopReason::IntersectionConflict &&
-           other.stop_reason == StopReason::IntersectionConflict {
-            return self.global_tick > other.global_tick;
-        }
-        
-        if (self.direction == Direction::North && other.direction == Direction::South) ||
-           (self.direction == Direction::South && other.direction == Direction::North) {
-            if (self.turn == Turn::Left && other.turn != Turn::Right) ||
-               (other.turn == Turn::Left && self.turn != Turn::Right) {
-                let self_time = self.time_to_intersection();
-                let other_time = other.time_to_intersection();
-                return (self_time - other_time).abs() < 20;
-            }
-        }
-        
-        if (self.direction == Direction::East && other.direction == Direction::West) ||
-           (self.direction == Direction::West && other.direction == Direction::East) {
-            if (self.turn == Turn::Left && other.turn != Turn::Right) ||
-               (other.turn == Turn::Left && self.turn != Turn::Right) {
-                let self_time = self.time_to_intersection();
-                let other_time = other.time_to_intersection();
-                return (self_time - other_time).abs() < 20;
-            }
+    pub fn check_traffic_light(&mut self, signals: &SignalView) {
+        if !signals.signalized {
+            return;
         }
+        let distance_to_intersection = self.distance_to_stop_line();
+        let must_stop = self.kind != VehicleKind::Emergency && match signals.signal_for(self.direction, self.turn) {
+            TrafficLightState::Red => true,
+            TrafficLightState::Amber => self.can_stop_within(distance_to_intersection),
+            TrafficLightState::Green => false,
+        };
         
-        match (self.direction, other.direction) {
-            (Direction::North, Direction::East) | (Direction::South, Direction::West) |
-            (Direction::East, Direction::North) | (Direction::West, Direction::South) |
-            (Direction::North, Direction::West) | (Direction::South, Direction::East) |
-            (Direction::East, Direction::South) | (Direction::West, Direction::North) => {
-                let self_time = self.time_to_intersection();
-                let other_time = other.time_to_intersection();
-                let collision_window = 15 + (self.current_speed + other.current_speed) / 2;
-                (self_time - other_time).abs() < collision_window
-            },
-            _ => {
-                let same_lane = match self.direction {
-                    Direction::North | Direction::South => 
-                        (self.x - other.x).abs() < LANE_WIDTH as i32 / 2,
-                    Direction::East | Direction::West => 
-                        (self.y - other.y).abs() < LANE_WIDTH as i32 / 2,
-                };
-                same_lane && self.bounding_box_collision(other)
-            }
+        let in_range = distance_to_intersection < self.braking_distance() + SIGNAL_LOOKAHEAD;
+        if must_stop && in_range && distance_to_intersection >= 0.0 {
+            self.hold(StopReason::TrafficLight, distance_to_intersection);
         }
     }
 
-    pub fn bounding_box_collision(&self, other: &Vehicle) -> bool {
-        let margin = 2;
-        self.x < other.x + other.width as i32 - margin &&
-        self.x + self.width as i32 - margin > other.x &&
-        self.y < other.y + other.height as i32 - margin &&
-        self.y + self.height as i32 - margin > other.y
+    /// Extent of the vehicle along its direction of travel.
+    pub fn length(&self) -> f32 {
+        self.glimpse().length()
     }
 
-    fn time_to_intersection(&self) -> i32 {
-        if self.current_speed <= 0 {
-            return i32::MAX;
-        }
-        
+    pub fn distance_to_stop_line(&self) -> f32 {
         match self.direction {
-            Direction::North => (self.y - CENTER_Y).max(1) / self.current_speed,
-            Direction::South => (CENTER_Y - self.y).max(1) / self.current_speed,
-            Direction::East => (CENTER_X - self.x).max(1) / self.current_speed,
-            Direction::West => (self.x - CENTER_X).max(1) / self.current_speed,
+            Direction::North => self.y - (CENTER_Y + STOP_LINE_OFFSET),
+            Direction::South => (CENTER_Y - STOP_LINE_OFFSET) - (self.y + self.height),
+            Direction::East => (CENTER_X - STOP_LINE_OFFSET) - self.x - self.width,
+            Direction::West => self.x - (CENTER_X + STOP_LINE_OFFSET),
         }
     }
 
-    pub fn check_traffic_light(&mut self, traffic_system: &TrafficLightSystem) {
-        let is_red = match self.direction {
-            Direction::North => traffic_system.north_state == TrafficLightState::Red,
-            Direction::South => traffic_system.south_state == TrafficLightState::Red,
-            Direction::East => traffic_system.east_state == TrafficLightState::Red,
-            Direction::West => traffic_system.west_state == TrafficLightState::Red,
-        };
-        
-        if self.stop_reason == StopReason::TrafficLight {
-            self.stopped = false;
-            self.stop_reason = StopReason::None;
-        }
-        
-        if is_red {
-            let distance_to_intersection = match self.direction {
-                Direction::North => self.y - (CENTER_Y + ROAD_WIDTH as i32 / 2),
-                Direction::South => (CENTER_Y - ROAD_WIDTH as i32 / 2) - (self.y + self.height as i32),
-                Direction::East => (CENTER_X - ROAD_WIDTH as i32 / 2) - self.x - self.width as i32,
-                Direction::West => self.x - (CENTER_X + ROAD_WIDTH as i32 / 2),
-            };
-            
-            if distance_to_intersection < 30 && distance_to_intersection > 0 {
-                self.stopped = true;
-                self.stop_reason = StopReason::TrafficLight;
-            }
-        }
+    /// Distance needed to come to a halt from the current speed at a comfortable deceleration.
+    pub fn braking_distance(&self) -> f32 {
+        self.current_speed * self.current_speed / (2.0 * self.comfortable_deceleration)
+    }
+
+    /// Gap needed behind `leader` to keep the minimum gap should both brake to a
+    /// halt at a comfortable deceleration.
+    pub fn safe_gap_behind(&self, leader: &Vehicle) -> f32 {
+        self.minimum_gap + (self.braking_distance() - leader.current_speed.powi(2) / (2.0 * self.comfortable_deceleration)).max(0.0)
+    }
+
+    /// Dilemma-zone check on amber: a vehicle whose driver cannot react and brake
+    /// to a halt before the stop line, as hard as they are willing to, proceeds
+    /// instead of stopping.
+    fn can_stop_within(&self, distance: f32) -> bool {
+        let deceleration = (self.comfortable_deceleration * self.driver.amber_braking).min(self.max_deceleration);
+        let speed = self.current_speed;
+        distance > speed * self.driver.reaction_time + speed * speed / (2.0 * deceleration)
     }
 
-    fn in_turn_zone(&self) -> bool {
+    // Distance left before the vehicle enters the zone where it makes its turn
+    fn distance_to_turn_zone(&self) -> f32 {
         match self.direction {
-            Direction::North => (self.y - CENTER_Y).abs() <= TURN_EXECUTION_ZONE,
-            Direction::South => (self.y - CENTER_Y).abs() <= TURN_EXECUTION_ZONE,
-            Direction::East => (self.x - CENTER_X).abs() <= TURN_EXECUTION_ZONE,
-            Direction::West => (self.x - CENTER_X).abs() <= TURN_EXECUTION_ZONE,
+            Direction::North => self.y - (CENTER_Y + TURN_EXECUTION_ZONE),
+            Direction::South => (CENTER_Y - TURN_EXECUTION_ZONE) - self.y,
+            Direction::East => (CENTER_X - TURN_EXECUTION_ZONE) - self.x,
+            Direction::West => self.x - (CENTER_X + TURN_EXECUTION_ZONE),
         }
     }
 
-    fn adjust_lane_position(&mut self) {
+    fn adjust_lane_position(&mut self, dt: f32) {
         if self.in_intersection_area() || !self.has_turned {
             return;
         }
 
+        let step = LATERAL_SPEED * dt;
         match self.direction {
-            Direction::North | Direction::South => {
-                if (self.x - self.target_x).abs() > 1 {
-                    self.x += if self.x < self.target_x { 1 } else { -1 };
-                } else {
-                    self.x = self.target_x;
-                }
-            },
-            Direction::East | Direction::West => {
-                if (self.y - self.target_y).abs() > 1 {
-                    self.y += if self.y < self.target_y { 1 } else { -1 };
-                } else {
-                    self.y = self.target_y;
-                }
-            },
-        }
-    }
-
-    pub fn handle_intersection_turn(&mut self) {
-        if self.in_turn_zone() && !self.stopped && !self.turn_executed {
-            self.turn_executed = true;
+            Direction::North | Direction::South => self.x += (self.target_x - self.x).clamp(-step, step),
+            Direction::East | Direction::West => self.y += (self.target_y - self.y).clamp(-step, step),
+        }
+    }
+
+    /// Starts a turning vehicle along its curve once its front reaches the box,
+    /// and lines a vehicle going straight up with its exit lane once it reaches
+    /// the turn zone. Turning vehicles wait in the box while anyone is walking
+    /// across the crosswalk they are about to cross.
+    pub fn handle_intersection_turn(&mut self, pedestrians: &[Pedestrian]) {
+        if self.turn_executed && self.path.is_none() {
+            return;
+        }
+
+        let exit_leg = Leg::exited_by(self.direction.turned(self.turn));
+        if self.turn != Turn::Straight && (self.path.is_some() || self.in_intersection_area()) &&
+           pedestrians.iter().any(|p| p.on_crosswalk(exit_leg)) {
+            self.hold(StopReason::Pedestrian, self.distance_to_exit_crosswalk().max(0.0));
+        }
+        if self.turn_executed {
+            return;
+        }
+
+        let distance = match self.turn {
+            Turn::Straight => self.distance_to_turn_zone(),
+            Turn::Left | Turn::Right => self.distance_to_turn(),
+        };
+        // A vehicle that could not halt before the zone turns anyway. One whose
+        // turn starts at the stop line waits there, still straight, for its grant
+        if distance > 0.0 || (self.stopped && self.current_speed == 0.0) ||
+           (self.turn != Turn::Straight && self.needs_reservation() && !self.reservation_granted) {
+            return;
+        }
+
+        self.turn_executed = true;
+        self.pull_over = 0.0;
 
+        if self.turn == Turn::Straight {
             match self.direction {
-                Direction::North => {
-                    match self.turn {
-                        Turn::Left => {
-                            self.direction = Direction::West;
-                            self.x = CENTER_X - self.height as i32;
-                            self.y = CENTER_Y - LANE_WIDTH as i32 / 2;
-                            self.target_y = WESTBOUND_LANE_CENTER - 10;
-                            std::mem::swap(&mut self.width, &mut self.height);
-                        },
-                        Turn::Right => {
-                            self.direction = Direction::East;
-                            self.x = CENTER_X;
-                            self.y = CENTER_Y + LANE_WIDTH as i32 / 2;
-                            self.target_y = EASTBOUND_LANE_CENTER - 10;
-                            std::mem::swap(&mut self.width, &mut self.height);
-                        },
-                        Turn::Straight => {
-                            self.target_x = NORTHBOUND_LANE_CENTER - 10;
-                        }
-                    }
-                },
-                Direction::South => {
-                    match self.turn {
-                        Turn::Left => {
-                            self.direction = Direction::East;
-                            self.x = CENTER_X;
-                            self.y = CENTER_Y + LANE_WIDTH as i32 / 2;
-                            self.target_y = EASTBOUND_LANE_CENTER - 10;
-                            std::mem::swap(&mut self.width, &mut self.height);
-                        },
-                        Turn::Right => {
-                            self.direction = Direction::West;
-                            self.x = CENTER_X - self.height as i32;
-                            self.y = CENTER_Y - LANE_WIDTH as i32 / 2;
-                            self.target_y = WESTBOUND_LANE_CENTER - 10;
-                            std::mem::swap(&mut self.width, &mut self.height);
-                        },
-                        Turn::Straight => {
-                            self.target_x = SOUTHBOUND_LANE_CENTER - 10;
-                        }
-                    }
-                },
-                Direction::East => {
-                    match self.turn {
-                        Turn::Left => {
-                            self.direction = Direction::North;
-                            self.x = CENTER_X - LANE_WIDTH as i32 / 2;
-                            self.y = CENTER_Y - self.height as i32;
-                            self.target_x = NORTHBOUND_LANE_CENTER - 10;
-                            std::mem::swap(&mut self.width, &mut self.height);
-                        },
-                        Turn::Right => {
-                            self.direction = Direction::South;
-                            self.x = CENTER_X + LANE_WIDTH as i32 / 2;
-                            self.y = CENTER_Y;
-                            self.target_x = SOUTHBOUND_LANE_CENTER - 10;
-                            std::mem::swap(&mut self.width, &mut self.height);
-                        },
-                        Turn::Straight => {
-                            self.target_y = EASTBOUND_LANE_CENTER - 10;
-                        }
-                    }
-                },
-                Direction::West => {
-                    match self.turn {
-                        Turn::Left => {
-                            self.direction = Direction::South;
-                            self.x = CENTER_X + LANE_WIDTH as i32 / 2;
-                            self.y = CENTER_Y;
-                            self.target_x = SOUTHBOUND_LANE_CENTER - 10;
-                            std::mem::swap(&mut self.width, &mut self.height);
-                        },
-                        Turn::Right => {
-                            self.direction = Direction::North;
-                            self.x = CENTER_X - LANE_WIDTH as i32 / 2;
-                            self.y = CENTER_Y - self.height as i32;
-                            self.target_x = NORTHBOUND_LANE_CENTER - 10;
-                            std::mem::swap(&mut self.width, &mut self.height);
-                        },
-                        Turn::Straight => {
-                            self.target_y = WESTBOUND_LANE_CENTER - 10;
-                        }
-                    }
-                },
+                Direction::North | Direction::South => self.target_x = self.lane.center - self.width / 2.0,
+                Direction::East | Direction::West => self.target_y = self.lane.center - self.height / 2.0,
             }
             self.has_turned = true;
-            self.arrival_time = None;
+        } else {
+            // Already part-way in when the front crossed into the box this tick
+            let mut path = self.turn_path();
+            path.travelled = -distance;
+            self.path = Some(path);
         }
     }
 
-    pub fn randomize_turn_if_needed(&mut self) {
-        if self.approaching_intersection() && !self.in_intersection_area() && 
-           !self.has_turned && !self.stopped {
-            let mut rng = rand::thread_rng();
-            if rng.gen_bool(0.3) {
-                self.turn = Turn::random();
-                self.color = match self.turn {
-                    Turn::Left => Color::RGB(255, 0, 0),
-                    Turn::Right => Color::RGB(0, 255, 0),
-                    Turn::Straight => Color::RGB(0, 0, 255),
-                };
+    // Curve from where the front reaches the box to the centre of the exit lane at
+    // the far edge of the box, done with once the rear has followed the front out.
+    // Legs too short for the vehicle's turning radius are lengthened, starting the
+    // turn earlier and ending it further out. A long vehicle still straightening
+    // up as its front leaves the box would swing over the centre line into traffic
+    // waiting on the other side, so its turn ends as far out from the centre line
+    // as it takes to keep clear
+    fn turn_path(&self) -> TurnPath {
+        (0..=(LANE_WIDTH / SWING_STEP) as usize)
+            .map(|i| self.swung_turn_path(i as f32 * SWING_STEP))
+            .find(|&path| self.keeps_to_own_side(path))
+            .unwrap_or_else(|| self.swung_turn_path(LANE_WIDTH))
+    }
+
+    // The turn path, ending `swing` metres further from the centre line
+    fn swung_turn_path(&self, swing: f32) -> TurnPath {
+        let (edge, corner) = self.turn_corner();
+        let (sin, cos) = self.heading.sin_cos();
+        let setback = self.turn_setback();
+        let start = (edge.0 - setback * cos, edge.1 - setback * sin);
+
+        let exit = self.direction.turned(self.turn);
+        let beyond = ROAD_WIDTH / 2.0;
+        let end = match exit {
+            Direction::North => (corner.0, CENTER_Y - beyond),
+            Direction::South => (corner.0, CENTER_Y + beyond),
+            Direction::East => (CENTER_X + beyond, corner.1),
+            Direction::West => (CENTER_X - beyond, corner.1),
+        };
+        let leg = distance(corner, end).max(self.turn_radius * SQRT_2);
+        let (sin, cos) = exit.heading().sin_cos();
+        let out = exit.away_from_centre_line();
+        let corner = (corner.0 + swing * out.0, corner.1 + swing * out.1);
+        TurnPath::new(start, corner, (corner.0 + leg * cos, corner.1 + leg * sin), self.body_length)
+    }
+
+    // Whether the body stays clear of the far side of the exit leg, where traffic
+    // waits at the stop line, while following `path`
+    fn keeps_to_own_side(&self, mut path: TurnPath) -> bool {
+        let exit = self.direction.turned(self.turn);
+        let heading = exit.heading();
+        let (out_x, out_y) = exit.away_from_centre_line();
+        // A square the width of the carriageway's far half, just beyond the box
+        let half = ROAD_WIDTH / 2.0;
+        let (along, across) = (half * 1.5, half / 2.0);
+        let far_side = Body {
+            center: (CENTER_X + along * heading.cos() - across * out_x, CENTER_Y + along * heading.sin() - across * out_y),
+            heading,
+            length: half,
+            width: half,
+        };
+        while !path.is_complete() {
+            let (center, heading) = path.pose();
+            if (Body { center, heading, ..self.body() }).overlaps(&far_side) {
+                return false;
             }
+            path.travelled += SWING_SAMPLE;
         }
+        true
     }
 
-    pub fn check_traffic_and_vehicles(&mut self, vehicles: &[Vehicle], current_index: usize, traffic_system: &TrafficLightSystem) {
-        self.check_traffic_light(traffic_system);
-        
-        if self.stop_reason != StopReason::TrafficLight {
-            self.check_same_direction_vehicles(vehicles, current_index);
-            
-            if self.stop_reason != StopReason::VehicleAhead {
-                self.check_intersection_conflicts(vehicles, current_index);
+    // Where the front reaches the box, and where its line of travel crosses the
+    // centre line of the exit lane
+    fn turn_corner(&self) -> ((f32, f32), (f32, f32)) {
+        let (x, y) = self.center();
+        let ahead = self.distance_to_box() + self.body_length / 2.0;
+        let edge = (x + ahead * self.heading.cos(), y + ahead * self.heading.sin());
+        let exit = self.direction.turned(self.turn);
+        let corner = match exit {
+            Direction::North | Direction::South => (exit.lane_center(), edge.1),
+            Direction::East | Direction::West => (edge.0, exit.lane_center()),
+        };
+        (edge, corner)
+    }
+
+    // How far before the box the turn starts. The curve is tightest midway between
+    // legs of equal length, with a radius of the leg over the square root of two,
+    // so the leg in must be at least that long. Turns start no earlier than the
+    // stop line, where vehicles wait to go
+    fn turn_setback(&self) -> f32 {
+        let (edge, corner) = self.turn_corner();
+        let wanted = self.turn_radius * SQRT_2 - distance(edge, corner);
+        wanted.clamp(0.0, STOP_LINE_OFFSET - ROAD_WIDTH / 2.0)
+    }
+
+    // Distance the front can go before the vehicle starts along its turn path
+    fn distance_to_turn(&self) -> f32 {
+        self.distance_to_box() - self.turn_setback()
+    }
+
+    /// Poses the body passes through, `step` metres apart, from where it is now
+    /// until its rear has left the box.
+    pub fn route_through_box(&self, step: f32) -> Vec<Body> {
+        let path = match self.path {
+            Some(path) => Some(path),
+            None if self.turn != Turn::Straight && !self.turn_executed => Some(self.turn_path()),
+            None => None,
+        };
+        // Straight on up to the start of the turn, or right across the box
+        let straight = match path {
+            Some(_) if self.path.is_some() => 0.0,
+            Some(_) => self.distance_to_turn().max(0.0),
+            None => (self.distance_to_box() + ROAD_WIDTH + self.body_length).max(0.0),
+        };
+
+        let start = self.body();
+        let (sin, cos) = self.heading.sin_cos();
+        let mut route: Vec<Body> = (0..=(straight / step) as usize)
+            .map(|i| {
+                let along = i as f32 * step;
+                Body { center: (start.center.0 + along * cos, start.center.1 + along * sin), ..start }
+            })
+            .collect();
+        if let Some(mut path) = path {
+            path.travelled = path.travelled.max(0.0);
+            while !path.is_complete() {
+                let (center, heading) = path.pose();
+                route.push(Body { center, heading, ..start });
+                path.travelled += step;
             }
         }
+        route
+    }
+
+    /// Distance left before the front of the vehicle enters the box.
+    pub fn distance_to_box(&self) -> f32 {
+        self.distance_to_stop_line() + STOP_LINE_OFFSET - ROAD_WIDTH / 2.0
+    }
+
+    // Distance the vehicle can go before its front reaches the crosswalk on its
+    // exit leg, which starts at the far edge of the box
+    fn distance_to_exit_crosswalk(&self) -> f32 {
+        match &self.path {
+            Some(path) => path.remaining(),
+            None if self.turn == Turn::Straight => self.distance_to_box() + ROAD_WIDTH,
+            None => self.distance_to_turn() + self.turn_path().length(),
+        }
+    }
+
+    /// Whether the vehicle is on the crosswalk on `leg`, or heading for it too
+    /// fast to stop for someone stepping out.
+    pub fn bears_down_on(&self, leg: Leg) -> bool {
+        if self.body().overlaps(&leg.crosswalk()) {
+            return true;
+        }
+        let reach = self.braking_distance() + self.minimum_gap;
+        let distance = if leg == Leg::entered_by(self.direction) && !self.turn_executed {
+            self.distance_to_stop_line()
+        } else if leg == Leg::exited_by(self.direction.turned(self.turn)) &&
+            (self.turn == Turn::Straight || self.path.is_some() || !self.turn_executed) {
+            // Not yet at the turn, the crosswalk is further off still
+            if self.turn != Turn::Straight && self.path.is_none() && self.distance_to_turn() >= reach {
+                return false;
+            }
+            self.distance_to_exit_crosswalk()
+        } else {
+            return false;
+        };
+        distance >= 0.0 && distance < reach
     }
 
-    pub fn update(&mut self, vehicles: &[Vehicle], current_index: usize, traffic_system: &TrafficLightSystem) {
-        self.global_tick += 1;
+    // Without signals, pedestrians have right of way on the crosswalks. The vehicle
+    // stops short of the one on its way in while anyone is walking across it, and
+    // going straight, at the stop line while anyone is on the one on its way out.
+    // Turning vehicles wait for the one on their way out in the box, as they do
+    // under signals
+    fn give_way_to_pedestrians(&mut self, pedestrians: &[Pedestrian]) {
+        let occupied = |leg: Leg| pedestrians.iter().any(|p| p.on_crosswalk(leg));
+        let to_stop_line = self.distance_to_stop_line();
+        if to_stop_line < 0.0 || self.turn_executed {
+            return;
+        }
+        if occupied(Leg::entered_by(self.direction)) ||
+           (self.turn == Turn::Straight && occupied(Leg::exited_by(self.direction))) {
+            self.hold(StopReason::Pedestrian, to_stop_line);
+        }
+    }
+
+    // Fastest speed at which the vehicle can still take its turn: the safe speed of
+    // the curve while on it, and before it the speed that brakes down to it in time
+    fn cornering_speed(&self) -> f32 {
+        if let Some(path) = &self.path {
+            return path.safe_speed;
+        }
+        if self.turn == Turn::Straight || self.turn_executed {
+            return f32::MAX;
+        }
+        let safe_speed = self.turn_path().safe_speed;
+        (safe_speed * safe_speed + 2.0 * self.comfortable_deceleration * self.distance_to_turn().max(0.0)).sqrt()
+    }
+
+    // Moves `travel` metres along the turn path and onto the exit leg at its end
+    fn follow_path(&mut self, travel: f32) {
+        let Some(mut path) = self.path else {
+            return;
+        };
+        path.travelled += travel;
+        let (center, heading) = path.pose();
+        if !path.is_complete() {
+            self.set_pose(center, heading);
+            self.path = Some(path);
+            return;
+        }
+
+        self.path = None;
+        self.direction = self.direction.turned(self.turn);
+        self.set_pose(center, self.direction.heading());
+        self.target_x = self.x;
+        self.target_y = self.y;
+        self.has_turned = true;
+    }
+
+    /// Ordinary vehicles with an emergency vehicle close behind in their lane brake
+    /// hard and pull toward the lane edge, unless they are already in the box.
+    /// They stay pulled over until it has gone past them.
+    fn check_emergency_vehicles(&mut self, vehicles: &[Glimpse]) {
+        let me = self.glimpse();
+        self.yielding_to_emergency = self.kind != VehicleKind::Emergency &&
+            !self.turn_executed && !self.in_intersection_area() &&
+            vehicles.iter().any(|other| {
+                other.kind == VehicleKind::Emergency &&
+                other.direction == self.direction &&
+                !other.turn_executed &&
+                other.lane_holds(self.lane.center) &&
+                (-(me.length() + other.length())..EMERGENCY_YIELD_DISTANCE).contains(&me.gap_behind(other))
+            });
         
-        self.randomize_turn_if_needed();
-        self.check_traffic_and_vehicles(vehicles, current_index, traffic_system);
+        if self.yielding_to_emergency {
+            self.hold(StopReason::EmergencyVehicle, 0.0);
+        }
+    }
+
+    /// What other drivers take in of the vehicle.
+    pub fn glimpse(&self) -> Glimpse {
+        Glimpse {
+            id: self.id,
+            kind: self.kind,
+            direction: self.direction,
+            turn: self.turn,
+            lane_center: self.lane.center,
+            x: self.x,
+            y: self.y,
+            width: self.width,
+            height: self.height,
+            heading: self.heading,
+            body_length: self.body_length,
+            body_width: self.body_width,
+            current_speed: self.current_speed,
+            pull_over: self.pull_over,
+            turn_executed: self.turn_executed,
+            turn_travelled: self.path.map(|path| path.travelled),
+        }
+    }
+
+    /// Whether the two vehicles overlap across the road, so one would run into
+    /// the other. A vehicle part-way through a lane change blocks both lanes.
+    pub fn same_lane(&self, other: &Vehicle) -> bool {
+        self.glimpse().same_lane(&other.glimpse())
+    }
+
+    // Distance of the front along the turn path, counting back from its start
+    // before the vehicle reaches it
+    fn turn_progress(&self) -> f32 {
+        self.path.map_or(-self.distance_to_turn(), |path| path.travelled)
+    }
+
+    /// Cross-road coordinate of the vehicle's centre.
+    pub fn lateral_center(&self) -> f32 {
+        self.glimpse().lateral_center()
+    }
+
+    /// Whether the vehicle is still moving across into its lane.
+    pub fn changing_lanes(&self) -> bool {
+        !self.has_turned && self.pull_over == 0.0 &&
+        (self.lateral_center() - self.lane.center).abs() > LATERAL_TOLERANCE
+    }
+
+    /// Gap from the front of `other` to the rear of this vehicle, when `other` follows it.
+    pub fn gap_behind(&self, other: &Vehicle) -> f32 {
+        self.glimpse().gap_behind(&other.glimpse())
+    }
+
+    // Eases the pull-over offset toward the lane edge while yielding and back
+    // afterwards, never into a vehicle alongside in `around`
+    fn adjust_pull_over(&mut self, around: &[Glimpse], dt: f32) {
+        let target = if self.yielding_to_emergency { PULL_OVER_OFFSET.min(self.pull_over_room(around)).max(0.0) } else { 0.0 };
+        let step = LATERAL_SPEED * dt;
+        self.pull_over += (target - self.pull_over).clamp(-step, step);
+    }
+
+    // Furthest the vehicle can pull over from the centre of its lane before it
+    // comes within the clearance of a vehicle alongside it in a lane nearer the
+    // kerb. Those in its own lane pull over with it.
+    fn pull_over_room(&self, around: &[Glimpse]) -> f32 {
+        let me = self.glimpse();
+        around.iter()
+            .filter(|other| other.direction == self.direction && (other.lane_center - self.lane.center) * self.outward() > 0.0)
+            .filter(|other| other.gap_behind(&me) < self.minimum_gap && me.gap_behind(other) < self.minimum_gap)
+            .map(|other| (other.lateral_center() - self.lane.center) * self.outward() - (me.breadth() + other.breadth()) / 2.0)
+            .fold(f32::MAX, |room, other| room.min(other - PULL_OVER_CLEARANCE))
+    }
+
+    // Sign of a move across the road toward the kerb
+    fn outward(&self) -> f32 {
+        let (x, y) = self.direction.away_from_centre_line();
+        x + y
+    }
+
+    // Steers toward the centre of the vehicle's lane, which animates lane changes,
+    // shifted toward the kerb while pulling over
+    fn keep_lane(&mut self, dt: f32) {
+        if self.has_turned || self.path.is_some() {
+            return;
+        }
         
-        if !self.stopped {
-            self.handle_intersection_turn();
-            
-            match self.direction {
-                Direction::North => self.y -= self.current_speed,
-                Direction::South => self.y += self.current_speed,
-                Direction::East => self.x += self.current_speed,
-                Direction::West => self.x -= self.current_speed,
-            }
-            
-            self.adjust_lane_position();
-        } else if self.in_intersection_area() && self.stop_reason == StopReason::IntersectionConflict {
-            if self.global_tick % 100 == 0 {
-                self.stopped = false;
-                self.stop_reason = StopReason::None;
+        let center = self.lane.center + self.outward() * self.pull_over;
+        let (position, half_width) = match self.direction {
+            Direction::North | Direction::South => (&mut self.x, self.width / 2.0),
+            Direction::East | Direction::West => (&mut self.y, self.height / 2.0),
+        };
+        let step = LATERAL_SPEED * dt;
+        *position += (center - half_width - *position).clamp(-step, step);
+    }
+
+    // Sets the speed from the car-following model, no faster than the vehicle can
+    // take its turn or the model's safe speed allows. A stop is followed as if a
+    // vehicle were standing just beyond the point where this one must halt.
+    fn accelerate(&mut self, dt: f32, rng: &mut impl Rng) {
+        let mut leader = self.leader;
+        if self.stopped {
+            let stop = Leader { gap: self.stop_distance + self.minimum_gap, speed: 0.0 };
+            if leader.is_none_or(|leader| stop.gap < leader.gap) {
+                leader = Some(stop);
             }
         }
+
+        let mut acceleration = self.car_following.acceleration(self, leader);
+        if self.car_following == CarFollowingModel::Krauss {
+            acceleration -= KRAUSS_DAWDLE * self.acceleration * rng.gen_range(0.0..1.0);
+        }
+        // Never slower than braking hard allows, should the turn change late or
+        // the safe speed drop
+        let limit = self.cornering_speed()
+            .min(self.car_following.safe_speed(self, leader))
+            .max(self.current_speed - self.max_deceleration * dt);
+        self.current_speed = (self.current_speed + acceleration * dt).clamp(0.0, limit);
+        if acceleration < 0.0 && self.current_speed < STANDSTILL_SPEED {
+            self.current_speed = 0.0;
+        }
+    }
+
+    /// Moves the vehicle `travel` metres forward, along its turn path if it is on one.
+    pub fn advance(&mut self, travel: f32) {
+        if self.path.is_some() {
+            self.follow_path(travel);
+            return;
+        }
+        match self.direction {
+            Direction::North => self.y -= travel,
+            Direction::South => self.y += travel,
+            Direction::East => self.x += travel,
+            Direction::West => self.x -= travel,
+        }
     }
 
-    pub fn draw(&self, canvas: &mut Canvas<Window>) -> Result<(), String> {
-        if self.x > -100 && self.x < WINDOW_WIDTH as i32 + 100 &&
-           self.y > -100 && self.y < WINDOW_HEIGHT as i32 + 100 {
-            canvas.set_draw_color(self.color);
-            canvas.fill_rect(Rect::new(self.x, self.y, self.width, self.height))?;
+    /// Advances the vehicle by one tick of `dt` seconds, once `check_vehicles_ahead`
+    /// has weighed the other `vehicles` and the `signals` as seen a reaction time
+    /// ago. The driver still brakes at once for a vehicle of `around`, the others
+    /// as they are now, that is nearer ahead.
+    pub fn update(
+        &mut self,
+        vehicles: &[Glimpse],
+        signals: &SignalView,
+        around: &[Glimpse],
+        pedestrians: &[Pedestrian],
+        dt: f32,
+        rng: &mut impl Rng
+    ) {
+        self.check_same_direction_vehicles(around);
+        self.check_emergency_vehicles(vehicles);
+        self.adjust_pull_over(around, dt);
+        self.keep_lane(dt);
+        
+        self.handle_intersection_turn(pedestrians);
+        if !signals.signalized {
+            self.give_way_to_pedestrians(pedestrians);
         }
-        Ok(())
+        
+        self.accelerate(dt, rng);
+        self.advance(self.current_speed * dt);
+        self.adjust_lane_position(dt);
     }
 }
 
 impl Turn {
-    fn random() -> Self {
-        let mut rng = rand::thread_rng();
-        match rng.gen_range(0..3) {
-            0 => Turn::Left,
-            1 => Turn::Right,
-            _ => Turn::Straight,
+    pub fn name(self) -> &'static str {
+        match self {
+            Turn::Left => "left",
+            Turn::Right => "right",
+            Turn::Straight => "straight",
+        }
+    }
+
+    /// Right of way between movements meeting at the box, lowest first: a right
+    /// turn goes before straight on, which goes before a left turn.
+    pub fn precedence(self) -> u8 {
+        match self {
+            Turn::Right => 0,
+            Turn::Straight => 1,
+            Turn::Left => 2,
         }
     }
-}
\ No newline at end of file
+}
+
+/// A car making `turn` from the single lane of the approach travelling in
+/// `direction`, with its front `distance` upstream of the stop line.
+#[cfg(test)]
+pub(crate) fn test_vehicle(direction: Direction, turn: Turn, distance: f32) -> Vehicle {
+    use rand::SeedableRng;
+    use crate::lanes::RoadLayout;
+
+    let layout = RoadLayout::single_lane();
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+    let route = Route::of(direction, turn);
+    let mut vehicle = Vehicle::new(route, VehicleKind::Car, Driver::default(), layout.approach(direction), 0, &mut rng);
+    let (sin, cos) = vehicle.heading.sin_cos();
+    let ahead = vehicle.distance_to_stop_line() - distance;
+    vehicle.x += ahead * cos;
+    vehicle.y += ahead * sin;
+    vehicle
+}
@@ -0,0 +1,174 @@
+// vehicle_class.rs
+// Characteristics of each kind of vehicle, and the mix of kinds in the traffic.
+// Sizes are in metres, speeds in metres per second and accelerations in metres
+// per second squared.
+use rand::Rng;
+use crate::vehicle::VehicleKind;
+use crate::weighted::Weighted;
+
+/// Size and driving characteristics shared by every vehicle of a kind.
+pub struct VehicleClass {
+    pub length: f32,
+    pub width: f32,
+    // Desired speed on an open road
+    pub max_speed: f32,
+    pub acceleration: f32,
+    pub comfortable_deceleration: f32,
+    pub max_deceleration: f32,
+    // Tightest radius the vehicle can turn on
+    pub turn_radius: f32,
+    // Time gap kept to a moving vehicle ahead, in seconds
+    pub time_headway: f32,
+    // Gap left to a vehicle standing ahead
+    pub minimum_gap: f32,
+}
+
+// 50 km/h
+const CAR: VehicleClass = VehicleClass {
+    length: 4.0,
+    width: 2.0,
+    max_speed: 13.9,
+    acceleration: 2.5,
+    comfortable_deceleration: 3.0,
+    max_deceleration: 7.5,
+    turn_radius: 5.0,
+    time_headway: 1.0,
+    minimum_gap: 2.0,
+};
+
+// 40 km/h, slow to pull away and kept further back from
+const TRUCK: VehicleClass = VehicleClass {
+    length: 10.0,
+    width: 2.4,
+    max_speed: 11.1,
+    acceleration: 1.0,
+    comfortable_deceleration: 2.0,
+    max_deceleration: 5.0,
+    turn_radius: 9.0,
+    time_headway: 1.8,
+    minimum_gap: 3.0,
+};
+
+const BUS: VehicleClass = VehicleClass {
+    length: 12.0,
+    width: 2.4,
+    max_speed: 11.1,
+    acceleration: 1.2,
+    comfortable_deceleration: 2.0,
+    max_deceleration: 5.0,
+    turn_radius: 10.0,
+    time_headway: 1.6,
+    minimum_gap: 3.0,
+};
+
+// 55 km/h
+const MOTORCYCLE: VehicleClass = VehicleClass {
+    length: 2.2,
+    width: 0.8,
+    max_speed: 15.3,
+    acceleration: 4.0,
+    comfortable_deceleration: 4.0,
+    max_deceleration: 8.0,
+    turn_radius: 3.0,
+    time_headway: 0.8,
+    minimum_gap: 1.5,
+};
+
+// 18 km/h
+const BICYCLE: VehicleClass = VehicleClass {
+    length: 1.8,
+    width: 0.6,
+    max_speed: 5.0,
+    acceleration: 1.0,
+    comfortable_deceleration: 2.0,
+    max_deceleration: 4.0,
+    turn_radius: 2.0,
+    time_headway: 1.0,
+    minimum_gap: 1.0,
+};
+
+// A car, allowed 70 km/h
+const EMERGENCY: VehicleClass = VehicleClass {
+    max_speed: 19.4,
+    ..CAR
+};
+
+impl VehicleKind {
+    pub fn class(self) -> &'static VehicleClass {
+        match self {
+            VehicleKind::Car => &CAR,
+            VehicleKind::Truck => &TRUCK,
+            VehicleKind::Bus => &BUS,
+            VehicleKind::Motorcycle => &MOTORCYCLE,
+            VehicleKind::Bicycle => &BICYCLE,
+            VehicleKind::Emergency => &EMERGENCY,
+        }
+    }
+
+    /// Whether the vehicle counts toward the heavy-vehicle share of the traffic.
+    pub fn is_heavy(self) -> bool {
+        matches!(self, VehicleKind::Truck | VehicleKind::Bus)
+    }
+}
+
+/// Share of each kind of vehicle among those spawned at random.
+#[derive(Clone)]
+pub struct TrafficMix {
+    shares: Weighted<VehicleKind>,
+}
+
+impl TrafficMix {
+    /// Draws the kind of the next vehicle. A mix of one kind draws nothing from
+    /// `rng`, so runs of cars alone match those from before there was a mix.
+    pub fn pick(&self, rng: &mut impl Rng) -> VehicleKind {
+        self.shares.pick(rng)
+    }
+
+    /// Percentage of the traffic made up of trucks and buses.
+    pub fn heavy_percentage(&self) -> f32 {
+        let heavy: f32 = self.shares.shares().iter().map(|&(kind, weight)| if kind.is_heavy() { weight } else { 0.0 }).sum();
+        heavy / self.shares.total() * 100.0
+    }
+
+    pub fn describe(&self) -> String {
+        self.shares.describe(VehicleKind::name)
+    }
+}
+
+impl Default for TrafficMix {
+    fn default() -> Self {
+        TrafficMix { shares: Weighted::new([(VehicleKind::Car, 1.0)]).expect("a single positive weight is valid") }
+    }
+}
+
+/// Parses a list like `car=85,truck=10,bus=5`.
+impl std::str::FromStr for TrafficMix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(TrafficMix { shares: Weighted::parse(s, "kind")? })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_mix_of_kinds() {
+        let mix: TrafficMix = "car=85,truck=10,bus=5".parse().expect("the mix is valid");
+        assert_eq!(mix.describe(), "car=85,truck=10,bus=5");
+        assert!((mix.heavy_percentage() - 15.0).abs() < 1e-4);
+        assert_eq!(TrafficMix::default().describe(), "car=1");
+        assert_eq!(TrafficMix::default().heavy_percentage(), 0.0);
+    }
+
+    #[test]
+    fn rejects_unknown_kinds_and_bad_weights() {
+        let error = |s: &str| s.parse::<TrafficMix>().err().unwrap_or_default();
+        assert_eq!(error("car=1,tank=2"), "unknown vehicle kind 'tank'");
+        assert_eq!(error("car=1,car=2"), "car is given more than once");
+        assert_eq!(error("car=0"), "at least one weight must be positive");
+        assert_eq!(error("car"), "expected kind=weight, got 'car'");
+    }
+}
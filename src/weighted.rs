@@ -0,0 +1,182 @@
+// weighted.rs
+// Choices drawn at random in proportion to their weights, shared by the traffic
+// mix, the driver mix and the turning shares of the origin-destination matrix.
+use std::str::FromStr;
+use rand::Rng;
+
+/// Options with their weights, which need not add up to anything in particular.
+#[derive(Clone)]
+pub struct Weighted<T> {
+    // Only the options of positive weight, in the order given
+    shares: Vec<(T, f32)>,
+}
+
+impl<T: Copy + PartialEq> Weighted<T> {
+    /// Leaves out options of zero weight. Fails on a weight that is negative or
+    /// not finite, when none is positive, or when they overflow when summed.
+    pub fn new(shares: impl IntoIterator<Item = (T, f32)>) -> Result<Self, String> {
+        let mut kept = Vec::new();
+        for (option, weight) in shares {
+            if !weight.is_finite() {
+                return Err("weights must be finite numbers".to_string());
+            }
+            if weight < 0.0 {
+                return Err("weights must not be negative".to_string());
+            }
+            if weight > 0.0 {
+                kept.push((option, weight));
+            }
+        }
+        let weighted = Weighted { shares: kept };
+        if weighted.shares.is_empty() {
+            return Err("at least one weight must be positive".to_string());
+        }
+        if !weighted.total().is_finite() {
+            return Err("weights are too large to add up".to_string());
+        }
+        Ok(weighted)
+    }
+
+    /// Parses a list like `car=85,truck=10,bus=5`, where `what` names the kind
+    /// of option in errors.
+    pub fn parse(s: &str, what: &str) -> Result<Self, String>
+    where
+        T: FromStr<Err = String>,
+    {
+        let mut shares: Vec<(T, f32)> = Vec::new();
+        for entry in s.split(',') {
+            let (name, weight) = entry.split_once('=')
+                .ok_or_else(|| format!("expected {}=weight, got '{}'", what, entry))?;
+            let name = name.trim();
+            let option: T = name.parse()?;
+            let weight: f32 = weight.trim().parse()
+                .map_err(|_| format!("weight for {} must be a number", name))?;
+            if !weight.is_finite() {
+                return Err(format!("weight for {} must be a finite number", name));
+            }
+            if weight < 0.0 {
+                return Err(format!("weight for {} must not be negative", name));
+            }
+            if shares.iter().any(|&(other, _)| other == option) {
+                return Err(format!("{} is given more than once", name));
+            }
+            shares.push((option, weight));
+        }
+        Self::new(shares)
+    }
+
+    /// Draws an option. A single option draws nothing from `rng`, so runs with
+    /// one match those from before there was a choice.
+    pub fn pick(&self, rng: &mut impl Rng) -> T {
+        if let [(option, _)] = self.shares[..] {
+            return option;
+        }
+        let mut draw = rng.gen_range(0.0..self.total());
+        for &(option, weight) in &self.shares {
+            if draw < weight {
+                return option;
+            }
+            draw -= weight;
+        }
+        // Rounding can leave the draw past the last share, which then takes it
+        self.shares[self.shares.len() - 1].0
+    }
+
+    pub fn total(&self) -> f32 {
+        self.shares.iter().map(|&(_, weight)| weight).sum()
+    }
+
+    /// Options of positive weight with their weights.
+    pub fn shares(&self) -> &[(T, f32)] {
+        &self.shares
+    }
+
+    /// Lists the shares like `car=85,truck=10,bus=5`.
+    pub fn describe(&self, name: impl Fn(T) -> &'static str) -> String {
+        self.shares.iter()
+            .map(|&(option, weight)| format!("{}={}", name(option), weight))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    enum Fruit {
+        Apple,
+        Pear,
+    }
+
+    impl FromStr for Fruit {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "apple" => Ok(Fruit::Apple),
+                "pear" => Ok(Fruit::Pear),
+                _ => Err(format!("unknown fruit '{}'", s)),
+            }
+        }
+    }
+
+    fn name(fruit: Fruit) -> &'static str {
+        match fruit {
+            Fruit::Apple => "apple",
+            Fruit::Pear => "pear",
+        }
+    }
+
+    fn parse_error(s: &str) -> String {
+        Weighted::<Fruit>::parse(s, "fruit").err().unwrap_or_default()
+    }
+
+    #[test]
+    fn parses_weights_and_leaves_out_zeros() {
+        let weighted = Weighted::<Fruit>::parse(" apple = 3 , pear=0", "fruit").expect("the list is valid");
+        assert_eq!(weighted.describe(name), "apple=3");
+        assert_eq!(weighted.total(), 3.0);
+    }
+
+    #[test]
+    fn rejects_malformed_lists() {
+        assert_eq!(parse_error("apple"), "expected fruit=weight, got 'apple'");
+        assert_eq!(parse_error("plum=1"), "unknown fruit 'plum'");
+        assert_eq!(parse_error("apple=lots"), "weight for apple must be a number");
+        assert_eq!(parse_error("apple=-1"), "weight for apple must not be negative");
+        assert_eq!(parse_error("apple=inf"), "weight for apple must be a finite number");
+        assert_eq!(parse_error("apple=NaN"), "weight for apple must be a finite number");
+        assert_eq!(parse_error("apple=1,apple=2"), "apple is given more than once");
+        assert_eq!(parse_error("apple=0,pear=0"), "at least one weight must be positive");
+        assert_eq!(parse_error("apple=3e38,pear=3e38"), "weights are too large to add up");
+    }
+
+    #[test]
+    fn new_rejects_what_parse_does() {
+        assert_eq!(Weighted::new([(Fruit::Apple, -1.0)]).err().unwrap_or_default(), "weights must not be negative");
+        assert_eq!(Weighted::new([(Fruit::Apple, f32::NAN)]).err().unwrap_or_default(), "weights must be finite numbers");
+        assert_eq!(Weighted::new([(Fruit::Pear, f32::INFINITY)]).err().unwrap_or_default(), "weights must be finite numbers");
+        assert_eq!(Weighted::<Fruit>::new([]).err().unwrap_or_default(), "at least one weight must be positive");
+    }
+
+    #[test]
+    fn single_option_draws_nothing() {
+        let weighted = Weighted::new([(Fruit::Pear, 2.0), (Fruit::Apple, 0.0)]).expect("the weights are valid");
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut untouched = rng.clone();
+        assert_eq!(weighted.pick(&mut rng), Fruit::Pear);
+        assert_eq!(rng.gen_range(0..u64::MAX), untouched.gen_range(0..u64::MAX));
+    }
+
+    #[test]
+    fn picks_in_proportion_to_the_weights() {
+        let weighted = Weighted::new([(Fruit::Apple, 1.0), (Fruit::Pear, 3.0)]).expect("the weights are valid");
+        let mut rng = StdRng::seed_from_u64(1);
+        let pears = (0..10_000).filter(|_| weighted.pick(&mut rng) == Fruit::Pear).count();
+        assert!((7_200..7_800).contains(&pears));
+    }
+}
@@ -1,11 +1,13 @@
 use sdl2::pixels::Color;
 use sdl2::render::Canvas;
 use sdl2::video::Window;
+use traffic_simulation::simulation::{Simulation, WORLD_WIDTH, WORLD_HEIGHT};
+use crate::road;
 
-pub const WINDOW_WIDTH: u32 = 800;
-pub const WINDOW_HEIGHT: u32 = 600;
-pub const CENTER_X: i32 = WINDOW_WIDTH as i32 / 2;
-pub const CENTER_Y: i32 = WINDOW_HEIGHT as i32 / 2;
+// Scale of the world-to-screen transform
+pub const PIXELS_PER_METRE: f32 = 10.0;
+pub const WINDOW_WIDTH: u32 = (WORLD_WIDTH * PIXELS_PER_METRE) as u32;
+pub const WINDOW_HEIGHT: u32 = (WORLD_HEIGHT * PIXELS_PER_METRE) as u32;
 pub const BACKGROUND_COLOR: Color = Color::RGB(100, 100, 100);
 
 pub fn init() -> Result<(Canvas<Window>, sdl2::EventPump), String> {
@@ -26,4 +28,27 @@ pub fn init() -> Result<(Canvas<Window>, sdl2::EventPump), String> {
     let event_pump = sdl_context.event_pump()?;
 
     Ok((canvas, event_pump))
-}
\ No newline at end of file
+}
+
+pub fn render(canvas: &mut Canvas<Window>, simulation: &Simulation) -> Result<(), String> {
+    canvas.set_draw_color(BACKGROUND_COLOR);
+    canvas.clear();
+    road::draw_intersection(canvas, &simulation.layout, &simulation.traffic_light_system.detectors)?;
+    road::draw_reservations(canvas, &simulation.reservations)?;
+    if simulation.traffic_light_system.control.is_signalized() {
+        road::draw_traffic_lights(canvas, &simulation.traffic_light_system, simulation.clock.tick)?;
+    } else {
+        road::draw_signs(canvas, simulation.traffic_light_system.control)?;
+    }
+    road::draw_pedestrian_signals(canvas, &simulation.traffic_light_system, simulation.clock.tick)?;
+
+    for vehicle in &simulation.vehicles {
+        road::draw_vehicle(canvas, vehicle)?;
+    }
+    for pedestrian in &simulation.pedestrians {
+        road::draw_pedestrian(canvas, pedestrian)?;
+    }
+
+    canvas.present();
+    Ok(())
+}
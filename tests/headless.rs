@@ -0,0 +1,89 @@
+// headless.rs
+// Seeded runs of the whole simulation, as the headless runner makes them, that
+// must end without a collision.
+use traffic_simulation::options::Options;
+use traffic_simulation::simulation::Simulation;
+
+// Steps `steps` ticks the way the headless runner does and returns the
+// collisions, described, of the run given by `args`
+fn collisions(args: &[&str], steps: u64) -> Vec<String> {
+    let options = Options::from_args(args.iter().map(|arg| arg.to_string())).expect("the arguments are valid");
+    let mut simulation = Simulation::from_options(&options).expect("the options are valid");
+    for _ in 0..steps {
+        if simulation.demand.is_empty() {
+            simulation.spawn_from_mix();
+        }
+        simulation.spawn_random_pedestrian();
+        simulation.step();
+    }
+    simulation.collisions.iter().map(|collision| collision.describe()).collect()
+}
+
+// A few minutes of traffic from the default mix
+fn assert_no_collisions(model: &str) {
+    for seed in ["1", "2", "3"] {
+        let found = collisions(&["--seed", seed, "--car-following", model], 7200);
+        assert!(found.is_empty(), "{} with seed {}: {:?}", model, seed, found);
+    }
+}
+
+// Every emergency vehicle of the scenario through the intersection
+fn assert_no_emergency_collisions(model: &str) {
+    let scenario = concat!(env!("CARGO_MANIFEST_DIR"), "/scenarios/emergency.toml");
+    for seed in ["1", "2"] {
+        let found = collisions(&["--seed", seed, "--car-following", model, "--scenario", scenario], 4800);
+        assert!(found.is_empty(), "{} with seed {}: {:?}", model, seed, found);
+    }
+}
+
+// Trucks and buses turning from the pockets and side by side lanes of the
+// multi-lane layout, where their bodies cut across the lanes beside them
+fn assert_no_heavy_collisions(seed: &str, extra: &[&str]) {
+    let layout = concat!(env!("CARGO_MANIFEST_DIR"), "/layouts/turn_lanes.toml");
+    let mut args = vec!["--seed", seed, "--layout", layout, "--mix", "car=60,truck=25,bus=15"];
+    args.extend_from_slice(extra);
+    let found = collisions(&args, 12000);
+    assert!(found.is_empty(), "{:?} with seed {}: {:?}", extra, seed, found);
+}
+
+#[test]
+fn idm_runs_without_collisions() {
+    assert_no_collisions("idm");
+}
+
+#[test]
+fn gipps_runs_without_collisions() {
+    assert_no_collisions("gipps");
+}
+
+#[test]
+fn krauss_runs_without_collisions() {
+    assert_no_collisions("krauss");
+}
+
+#[test]
+fn idm_yields_to_emergency_vehicles_without_collisions() {
+    assert_no_emergency_collisions("idm");
+}
+
+#[test]
+fn gipps_yields_to_emergency_vehicles_without_collisions() {
+    assert_no_emergency_collisions("gipps");
+}
+
+#[test]
+fn krauss_yields_to_emergency_vehicles_without_collisions() {
+    assert_no_emergency_collisions("krauss");
+}
+
+#[test]
+fn heavy_vehicles_turn_across_multiple_lanes_without_collisions() {
+    assert_no_heavy_collisions("3", &[]);
+}
+
+#[test]
+fn heavy_vehicles_on_peak_routes_turn_without_collisions() {
+    let demand = concat!(env!("CARGO_MANIFEST_DIR"), "/demand/am_peak.toml");
+    let routes = concat!(env!("CARGO_MANIFEST_DIR"), "/routes/major_road.toml");
+    assert_no_heavy_collisions("8", &["--demand", demand, "--od-matrix", routes]);
+}